@@ -0,0 +1,112 @@
+//! `--ads`: NTFS alternate data streams of matched files.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A named stream next to a file's main content (`file.txt:Zone.Identifier`).
+#[derive(Clone, Debug)]
+pub(crate) struct Stream {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+}
+
+/// The path that opens one stream of `path` (`file.txt:name`).
+pub(crate) fn stream_path(path: &Path, name: &str) -> PathBuf {
+    let mut full = OsString::from(path.as_os_str());
+    full.push(":");
+    full.push(name);
+    PathBuf::from(full)
+}
+
+/// Named streams of a file, without the unnamed main one.
+#[cfg(windows)]
+pub(crate) fn streams(path: &Path) -> io::Result<Vec<Stream>> {
+    win32::streams(path)
+}
+
+/// Alternate data streams only exist on NTFS; the collector refuses `--ads`
+/// elsewhere before anything is walked.
+#[cfg(not(windows))]
+pub(crate) fn streams(_path: &Path) -> io::Result<Vec<Stream>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "alternate data streams are only available on Windows",
+    ))
+}
+
+/// `FindFirstStreamW` / `FindNextStreamW`, which have no safe std counterpart.
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod win32 {
+    use super::Stream;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    pub(super) fn streams(path: &Path) -> io::Result<Vec<Stream>> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut data = WIN32_FIND_STREAM_DATA::default();
+        // SAFETY: `wide` is NUL-terminated and outlives the call; `data` is
+        // the struct `FindStreamInfoStandard` fills in.
+        let handle = unsafe {
+            FindFirstStreamW(
+                wide.as_ptr(),
+                FindStreamInfoStandard,
+                (&raw mut data).cast(),
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            let error = io::Error::last_os_error();
+            // Nothing to enumerate (directories without streams, FAT volumes)
+            return if end_of_streams(&error) {
+                Ok(Vec::new())
+            } else {
+                Err(error)
+            };
+        }
+
+        let mut streams = Vec::new();
+        let error = loop {
+            streams.extend(named(&data));
+            // SAFETY: `handle` is the open search handle from above.
+            if unsafe { FindNextStreamW(handle, (&raw mut data).cast()) } == 0 {
+                break io::Error::last_os_error();
+            }
+        };
+        // SAFETY: closed exactly once, after its last use.
+        unsafe { FindClose(handle) };
+        if end_of_streams(&error) {
+            Ok(streams)
+        } else {
+            Err(error)
+        }
+    }
+
+    fn end_of_streams(error: &io::Error) -> bool {
+        error.raw_os_error() == i32::try_from(ERROR_HANDLE_EOF).ok()
+    }
+
+    /// `:Zone.Identifier:$DATA` is the stream `Zone.Identifier`; the main
+    /// stream (`::$DATA`) has no name and is left out.
+    fn named(data: &WIN32_FIND_STREAM_DATA) -> Option<Stream> {
+        let len = data
+            .cStreamName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(data.cStreamName.len());
+        let raw = String::from_utf16_lossy(data.cStreamName.get(..len)?);
+        let name = raw.strip_prefix(':')?;
+        let name = name.strip_suffix(":$DATA").unwrap_or(name);
+        (!name.is_empty()).then(|| Stream {
+            name: name.to_string(),
+            size: u64::try_from(data.StreamSize).unwrap_or(0),
+        })
+    }
+}
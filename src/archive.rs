@@ -0,0 +1,193 @@
+//! Archive writers for `--format tar` and `--format zip`.
+//! Files are stored whole (max_bytes does not apply) under their relative path,
+//! with their mode bits and mtime; symlinks stay links.
+
+use crate::collector::Entry;
+use crate::metadata::UtcTime;
+use crate::options::{CollectOptions, OutputFormat};
+use crate::owners::{known_group, known_user, mode, ownership};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tar::HeaderMode;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// Writes every entry into a single archive on `writer`.
+/// `on_error` receives per-file failures; the archive keeps going.
+pub(crate) fn write_archive(
+    entries: impl Iterator<Item = Entry>,
+    options: &CollectOptions,
+    writer: &mut dyn Write,
+    on_error: &dyn Fn(&Path, &io::Error),
+) -> io::Result<usize> {
+    match options.format {
+        OutputFormat::Zip => write_zip(entries, options, writer, on_error),
+        _ => write_tar(entries, options, writer, on_error),
+    }
+}
+
+/// Name inside the archive: relative to the base path, always '/' separated.
+pub(crate) fn archive_name(path: &Path, options: &CollectOptions) -> String {
+    let relative = path.strip_prefix(&options.base_path).unwrap_or(path);
+    relative
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn write_tar<W: Write>(
+    entries: impl Iterator<Item = Entry>,
+    options: &CollectOptions,
+    writer: W,
+    on_error: &dyn Fn(&Path, &io::Error),
+) -> io::Result<usize> {
+    let mut builder = tar::Builder::new(writer);
+    let mut count = 0;
+
+    for entry in entries {
+        let name = archive_name(entry.path(), options);
+        match append_tar(&mut builder, entry.content_path(), &name, options) {
+            Ok(()) => count += 1,
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e),
+            Err(e) => on_error(entry.path(), &e),
+        }
+    }
+
+    builder.finish()?;
+    Ok(count)
+}
+
+/// One file, or a symlink as a link, with its mode bits, mtime and owner
+/// (uid / gid plus the names, unless `numeric_owner`).
+fn append_tar<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &Path,
+    name: &str,
+    options: &CollectOptions,
+) -> io::Result<()> {
+    let meta = entry_metadata(path, options)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata_in_mode(&meta, HeaderMode::Complete);
+    if options.no_perms {
+        header.set_mode(plain_mode(&meta));
+        header.set_uid(0);
+        header.set_gid(0);
+    } else if !options.numeric_owner
+        && let Some((uid, gid)) = ownership(&meta)
+    {
+        if let Some(user) = known_user(uid) {
+            header.set_username(user)?;
+        }
+        if let Some(group) = known_group(gid) {
+            header.set_groupname(group)?;
+        }
+    }
+
+    if meta.file_type().is_symlink() {
+        header.set_size(0);
+        builder.append_link(&mut header, name, fs::read_link(path)?)
+    } else {
+        // Never more than the header announced, should the file grow meanwhile
+        let file = File::open(path)?;
+        builder.append_data(&mut header, name, file.take(meta.len()))
+    }
+}
+
+fn write_zip(
+    entries: impl Iterator<Item = Entry>,
+    options: &CollectOptions,
+    writer: &mut dyn Write,
+    on_error: &dyn Fn(&Path, &io::Error),
+) -> io::Result<usize> {
+    // Stream mode: stdout is not seekable
+    let mut zip = ZipWriter::new_stream(writer);
+    let mut count = 0;
+
+    for entry in entries {
+        let path = entry.content_path();
+        let meta = match entry_metadata(path, options) {
+            Ok(meta) => meta,
+            Err(e) => {
+                on_error(entry.path(), &e);
+                continue;
+            }
+        };
+
+        let mut file_options =
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        if let Some(mtime) = meta.modified().ok().and_then(zip_time) {
+            file_options = file_options.last_modified_time(mtime);
+        }
+        if options.no_perms {
+            file_options = file_options.unix_permissions(plain_mode(&meta));
+        } else if let Some(bits) = mode(&meta) {
+            file_options = file_options.unix_permissions(bits);
+        }
+
+        let name = archive_name(entry.path(), options);
+        if meta.file_type().is_symlink() {
+            match fs::read_link(path) {
+                Ok(target) => zip
+                    .add_symlink(name, target.to_string_lossy(), file_options)
+                    .map_err(io::Error::other)?,
+                Err(e) => {
+                    on_error(entry.path(), &e);
+                    continue;
+                }
+            }
+        } else {
+            let file = match File::open(path) {
+                Ok(f) => f,
+                Err(e) => {
+                    on_error(entry.path(), &e);
+                    continue;
+                }
+            };
+            zip.start_file(name, file_options)
+                .map_err(io::Error::other)?;
+            io::copy(&mut file.take(meta.len()), &mut zip)?;
+        }
+        count += 1;
+    }
+
+    zip.finish().map_err(io::Error::other)?;
+    Ok(count)
+}
+
+/// The entry itself: a symlink is stored as a link unless links are followed.
+fn entry_metadata(path: &Path, options: &CollectOptions) -> io::Result<Metadata> {
+    if options.follow_symlinks {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    }
+}
+
+/// `no_perms`: `0755` for what its owner may execute (and links), `0644`
+/// for the rest, so execute bits survive but the local umask does not.
+fn plain_mode(meta: &Metadata) -> u32 {
+    match mode(meta) {
+        _ if meta.file_type().is_symlink() => 0o777,
+        Some(bits) if bits & 0o100 != 0 => 0o755,
+        _ => 0o644,
+    }
+}
+
+/// DOS timestamps only cover 1980..=2107; anything else keeps the default.
+fn zip_time(time: std::time::SystemTime) -> Option<DateTime> {
+    let t = UtcTime::from(time);
+    DateTime::from_date_and_time(
+        u16::try_from(t.year).ok()?,
+        u8::try_from(t.month).ok()?,
+        u8::try_from(t.day).ok()?,
+        u8::try_from(t.hour).ok()?,
+        u8::try_from(t.minute).ok()?,
+        u8::try_from(t.second).ok()?,
+    )
+    .ok()
+}
@@ -0,0 +1,228 @@
+//! Binary detection (`--binary-check`) and rendering for `--binary` (hex
+//! dump, base64 or raw bytes).
+
+use crate::options::{BinaryCheck, BinaryMode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use memchr::memchr;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Hex dumps are bounded even without `max_bytes`: they are ~4x the input.
+pub(crate) const HEX_DUMP_LIMIT: u64 = 4096;
+
+/// Input bytes per base64 line (76 output characters, as in MIME).
+const BASE64_LINE: usize = 57;
+
+/// Leading bytes that decide whether a file is binary.
+pub(crate) const PROBE_LEN: usize = 8192;
+
+/// Signatures of binary formats whose first bytes can look like text.
+const MAGIC: &[&[u8]] = &[
+    b"%PDF-",
+    b"\x7fELF",
+    b"\x89PNG\r\n\x1a\n",
+    b"\xff\xd8\xff",
+    b"GIF87a",
+    b"GIF89a",
+    b"PK\x03\x04",
+    b"\x1f\x8b",
+    b"\x28\xb5\x2f\xfd",
+    b"\xfd7zXZ\x00",
+    b"7z\xbc\xaf\x27\x1c",
+    b"Rar!\x1a\x07",
+    b"\xca\xfe\xba\xbe",
+    b"\xcf\xfa\xed\xfe",
+    b"\x00asm",
+    b"SQLite format 3\x00",
+    b"OggS",
+    b"RIFF",
+    b"fLaC",
+    b"wOFF",
+    b"wOF2",
+];
+
+/// Share of control characters (in percent) above which content is binary.
+const MAX_CONTROL_PERCENT: usize = 5;
+
+pub(crate) const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Byte order of UTF-16 text, from its BOM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Utf16 {
+    Le,
+    Be,
+}
+
+/// UTF-16 text behind a BOM, which `Off` leaves undecoded. The UTF-32 LE
+/// BOM starts like the UTF-16 LE one and is not UTF-16.
+pub(crate) fn utf16_bom(head: &[u8], check: BinaryCheck) -> Option<Utf16> {
+    match head {
+        _ if check == BinaryCheck::Off => None,
+        [0xFF, 0xFE, 0, 0, ..] => None,
+        [0xFF, 0xFE, ..] => Some(Utf16::Le),
+        [0xFE, 0xFF, ..] => Some(Utf16::Be),
+        _ => None,
+    }
+}
+
+/// UTF-16 bytes (BOM included) as a string; bad surrogates and an odd
+/// trailing byte become U+FFFD.
+pub(crate) fn decode_utf16(bytes: &[u8], order: Utf16) -> String {
+    let body = bytes.get(2..).unwrap_or_default();
+    let units = body.chunks_exact(2).map(|pair| {
+        let pair = <[u8; 2]>::try_from(pair).unwrap_or_default();
+        match order {
+            Utf16::Le => u16::from_le_bytes(pair),
+            Utf16::Be => u16::from_be_bytes(pair),
+        }
+    });
+    let mut text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if body.len() % 2 == 1 {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+/// Classifies content by its first `PROBE_LEN` bytes. Text with a UTF-8 or
+/// UTF-16 BOM is text; known binary signatures are binary; otherwise it is
+/// binary when more than `MAX_CONTROL_PERCENT` of the bytes are control
+/// characters. `Strict` also counts any null byte as binary, `Off` never
+/// says binary.
+pub(crate) fn looks_binary(head: &[u8], check: BinaryCheck) -> bool {
+    if check == BinaryCheck::Off || head.starts_with(UTF8_BOM) || utf16_bom(head, check).is_some() {
+        return false;
+    }
+    if MAGIC.iter().any(|magic| head.starts_with(magic)) {
+        return true;
+    }
+    if check == BinaryCheck::Strict && memchr(0, head).is_some() {
+        return true;
+    }
+    // Tab, newlines, form feed, backspace and ANSI escapes occur in text
+    let control = head
+        .iter()
+        .filter(|&&b| {
+            (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x08 | 0x0c | 0x1b)) || b == 0x7f
+        })
+        .count();
+    control * 100 > head.len() * MAX_CONTROL_PERCENT
+}
+
+/// Same heuristic as the streamer, on the first `PROBE_LEN` bytes.
+pub(crate) fn is_binary_file(path: &Path, check: BinaryCheck) -> io::Result<bool> {
+    let mut head = [0u8; PROBE_LEN];
+    let n = File::open(path)?.read(&mut head)?;
+    Ok(looks_binary(head.get(..n).unwrap_or_default(), check))
+}
+
+/// Bytes of a binary file that get rendered.
+pub(crate) fn binary_limit(mode: BinaryMode, max_bytes: Option<u64>) -> u64 {
+    match mode {
+        BinaryMode::Hex => max_bytes.unwrap_or(HEX_DUMP_LIMIT),
+        _ => max_bytes.unwrap_or(u64::MAX),
+    }
+}
+
+/// Output size for `input` rendered bytes (used by token estimates).
+pub(crate) fn rendered_len(mode: BinaryMode, input: u64) -> u64 {
+    match mode {
+        BinaryMode::Suppress | BinaryMode::Skip => 0,
+        // "00000000: " + 8 groups + ascii column + newline per 16 bytes
+        BinaryMode::Hex => input.div_ceil(16) * 68,
+        BinaryMode::Base64 => input.div_ceil(3) * 4 + input.div_ceil(BASE64_LINE as u64),
+        BinaryMode::Raw => input,
+    }
+}
+
+/// Renders binary content in the requested mode. `wrap` splits base64 into
+/// 76 character lines (text output); structured formats want one string.
+pub(crate) fn write_binary(
+    reader: impl Read,
+    writer: &mut dyn Write,
+    mode: BinaryMode,
+    max_bytes: Option<u64>,
+    wrap: bool,
+) -> io::Result<()> {
+    let limit = binary_limit(mode, max_bytes);
+    let mut reader = reader.take(limit);
+
+    match mode {
+        BinaryMode::Suppress | BinaryMode::Skip => writer.write_all(b"<Binary content suppressed>"),
+        BinaryMode::Raw => io::copy(&mut reader, writer).map(|_| ()),
+        BinaryMode::Hex => {
+            let mut line = [0u8; 16];
+            let mut offset: u64 = 0;
+            loop {
+                let n = read_full(&mut reader, &mut line)?;
+                if n == 0 {
+                    break;
+                }
+                write_hex_line(writer, offset, line.get(..n).unwrap_or_default())?;
+                offset += n as u64;
+            }
+            Ok(())
+        }
+        BinaryMode::Base64 => {
+            // Whole lines per read keep the padding at the very end
+            let mut chunk = vec![0u8; BASE64_LINE * 1024];
+            loop {
+                let n = read_full(&mut reader, &mut chunk)?;
+                if n == 0 {
+                    break;
+                }
+                let data = chunk.get(..n).unwrap_or_default();
+                if wrap {
+                    for line in data.chunks(BASE64_LINE) {
+                        writeln!(writer, "{}", STANDARD.encode(line))?;
+                    }
+                } else {
+                    writer.write_all(STANDARD.encode(data).as_bytes())?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `00000010: 0102 0304 ... 0f10  ................` (xxd layout).
+fn write_hex_line(writer: &mut dyn Write, offset: u64, bytes: &[u8]) -> io::Result<()> {
+    write!(writer, "{offset:08x}:")?;
+    for i in 0..16 {
+        if i % 2 == 0 {
+            writer.write_all(b" ")?;
+        }
+        match bytes.get(i) {
+            Some(b) => write!(writer, "{b:02x}")?,
+            None => writer.write_all(b"  ")?,
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    writeln!(writer, "  {ascii}")
+}
+
+/// Fills `buf` unless EOF comes first; returns the bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while let Some(rest) = buf.get_mut(filled..).filter(|r| !r.is_empty()) {
+        match reader.read(rest) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
@@ -0,0 +1,535 @@
+//! `--interactive` / `--pick`: a full-screen fuzzy browser over the matched
+//! paths, drawn with ratatui on stderr so stdout keeps the output.
+
+use crate::collector::Collector;
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::Frame;
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Padding, Paragraph};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Stderr};
+use std::path::{Path, PathBuf};
+
+/// Bytes of a file shown in the preview pane.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
+/// Narrowest terminal that still gets a preview pane.
+const PREVIEW_MIN_WIDTH: u16 = 60;
+
+/// Walks once, lets the user narrow the matches down and returns the chosen
+/// paths; `None` when cancelled. `picker` (`--pick`) has no preview pane,
+/// starts from `query` and takes a single match for it without asking.
+pub fn browse(collector: &Collector, query: String, picker: bool) -> Result<Option<Vec<PathBuf>>> {
+    let base = &collector.options().base_path;
+    let items: Vec<Item> = collector
+        .entries()?
+        .map(|entry| Item::new(entry.path(), base))
+        .collect();
+    if items.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    let browser = Browser::new(items, query, !picker);
+    if picker && !browser.query.is_empty() && browser.hits.len() == 1 {
+        return Ok(Some(browser.chosen()));
+    }
+    let mut screen = Screen::open()?;
+    browser.run(&mut screen)
+}
+
+/// The terminal in raw mode on the alternate screen; restored when dropped,
+/// also on errors.
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stderr>>,
+}
+
+impl Screen {
+    fn open() -> Result<Self> {
+        anyhow::ensure!(io::stderr().is_terminal(), "--interactive needs a terminal");
+        let terminal = Terminal::new(CrosstermBackend::new(io::stderr()))?;
+        enable_raw_mode().context("--interactive needs a terminal")?;
+        let mut screen = Self { terminal };
+        execute!(screen.terminal.backend_mut(), EnterAlternateScreen)?;
+        screen.terminal.hide_cursor()?;
+        Ok(screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+        let _ = disable_raw_mode();
+    }
+}
+
+/// One match as the browser lists it.
+#[derive(Debug)]
+struct Item {
+    path: PathBuf,
+    /// Relative to --path, `/` separated, directories with a trailing `/`.
+    label: String,
+}
+
+impl Item {
+    fn new(path: &Path, base: &Path) -> Self {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        let mut label = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if label.is_empty() {
+            label.push('.');
+        }
+        if path.is_dir() {
+            label.push('/');
+        }
+        Self {
+            path: path.to_path_buf(),
+            label,
+        }
+    }
+}
+
+/// An item passing the query, with the label characters that matched.
+#[derive(Debug)]
+struct Hit {
+    item: usize,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Continue,
+    Accept,
+    Cancel,
+}
+
+#[derive(Debug)]
+struct Browser {
+    items: Vec<Item>,
+    query: String,
+    hits: Vec<Hit>,
+    /// Index into `hits` and the first hit on screen.
+    cursor: usize,
+    top: usize,
+    /// Rows of the list as last drawn, what PageUp / PageDown move by.
+    page: usize,
+    /// Item indices, so the output keeps the walk's order.
+    selected: BTreeSet<usize>,
+    /// Whether there is a preview pane, the preview of one item and how far
+    /// it is scrolled.
+    show_preview: bool,
+    preview: Option<(usize, Vec<String>)>,
+    preview_top: usize,
+}
+
+impl Browser {
+    fn new(items: Vec<Item>, query: String, show_preview: bool) -> Self {
+        let mut browser = Self {
+            items,
+            query,
+            hits: Vec::new(),
+            cursor: 0,
+            top: 0,
+            page: 1,
+            selected: BTreeSet::new(),
+            show_preview,
+            preview: None,
+            preview_top: 0,
+        };
+        browser.refilter();
+        browser
+    }
+
+    /// Draws, then waits for the next event. A resize only needs the
+    /// redraw: the terminal picks up its new size when drawing.
+    fn run(mut self, screen: &mut Screen) -> Result<Option<Vec<PathBuf>>> {
+        loop {
+            screen.terminal.draw(|frame| self.render(frame))?;
+            let step = match event::read()? {
+                // Windows also reports releases
+                Event::Key(key) if key.kind != KeyEventKind::Release => self.handle(key),
+                _ => Step::Continue,
+            };
+            match step {
+                Step::Continue => {}
+                Step::Cancel => return Ok(None),
+                Step::Accept => return Ok(Some(self.chosen())),
+            }
+        }
+    }
+
+    /// The marked items, or the one under the cursor when none are.
+    fn chosen(self) -> Vec<PathBuf> {
+        let current = self.hits.get(self.cursor).map(|hit| hit.item);
+        let mut items = self.items;
+        let indices: Vec<usize> = if self.selected.is_empty() {
+            current.into_iter().collect()
+        } else {
+            self.selected.into_iter().collect()
+        };
+        indices
+            .into_iter()
+            .filter_map(|i| items.get_mut(i).map(|item| std::mem::take(&mut item.path)))
+            .collect()
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Step {
+        let page = isize::try_from(self.page).unwrap_or(1);
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('u') => {
+                    self.query.clear();
+                    self.refilter();
+                }
+                KeyCode::Char('p' | 'k') => self.move_cursor(-1),
+                KeyCode::Char('n' | 'j') => self.move_cursor(1),
+                KeyCode::Char('a') => self.toggle_all(),
+                KeyCode::Char('f') => self.preview_top += self.page / 2,
+                KeyCode::Char('b') => {
+                    self.preview_top = self.preview_top.saturating_sub(self.page / 2);
+                }
+                KeyCode::Char('c' | 'g' | 'q') => return Step::Cancel,
+                _ => {}
+            }
+            return Step::Continue;
+        }
+        match key.code {
+            // Alt+key is left alone
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-page),
+            KeyCode::PageDown => self.move_cursor(page),
+            KeyCode::Tab => {
+                self.toggle_current();
+                self.move_cursor(1);
+            }
+            KeyCode::BackTab => {
+                self.toggle_current();
+                self.move_cursor(-1);
+            }
+            KeyCode::Enter => return Step::Accept,
+            KeyCode::Esc => return Step::Cancel,
+            _ => {}
+        }
+        Step::Continue
+    }
+
+    fn refilter(&mut self) {
+        self.hits = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(item, entry)| {
+                let (score, positions) = fuzzy_match(&self.query, &entry.label)?;
+                Some(Hit {
+                    item,
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        // Stable: equal scores keep the walk's order
+        self.hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+        self.cursor = 0;
+        self.top = 0;
+        self.preview_top = 0;
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        let last = self.hits.len().saturating_sub(1);
+        let cursor = self.cursor.saturating_add_signed(by).min(last);
+        if cursor != self.cursor {
+            self.preview_top = 0;
+        }
+        self.cursor = cursor;
+    }
+
+    fn toggle_current(&mut self) {
+        if let Some(hit) = self.hits.get(self.cursor)
+            && !self.selected.remove(&hit.item)
+        {
+            self.selected.insert(hit.item);
+        }
+    }
+
+    /// Selects every hit, or clears them when all already are.
+    fn toggle_all(&mut self) {
+        let all = self
+            .hits
+            .iter()
+            .all(|hit| self.selected.contains(&hit.item));
+        for hit in &self.hits {
+            if all {
+                self.selected.remove(&hit.item);
+            } else {
+                self.selected.insert(hit.item);
+            }
+        }
+    }
+
+    /// The whole screen: prompt, list (with a preview pane when there is
+    /// room) and a key hint.
+    fn render(&mut self, frame: &mut Frame<'_>) {
+        let [prompt_row, rule, body, hint] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let status = format!(
+            "{}/{}  {} selected",
+            self.hits.len(),
+            self.items.len(),
+            self.selected.len()
+        );
+        let [prompt, counts] = Layout::horizontal([
+            Constraint::Min(0),
+            Constraint::Length(u16::try_from(status.len()).unwrap_or(u16::MAX)),
+        ])
+        .areas(prompt_row);
+        frame.render_widget(Paragraph::new(format!("> {}", self.query).bold()), prompt);
+        frame.render_widget(Paragraph::new(status.dim()), counts);
+        frame.render_widget(
+            Block::new()
+                .borders(Borders::TOP)
+                .border_style(Style::new().dim()),
+            rule,
+        );
+
+        let (list, preview) = if self.show_preview && body.width >= PREVIEW_MIN_WIDTH {
+            let [list, preview] =
+                Layout::horizontal([Constraint::Percentage(40), Constraint::Min(0)]).areas(body);
+            (list, Some(preview))
+        } else {
+            (body, None)
+        };
+        self.render_list(frame, list);
+        if let Some(area) = preview {
+            self.render_preview(frame, area);
+        }
+
+        let keys = if self.show_preview {
+            "Tab select  ^A all  ^F/^B scroll preview  Enter accept  Esc cancel"
+        } else {
+            "Tab select  ^A all  Enter accept  Esc cancel"
+        };
+        frame.render_widget(Paragraph::new(keys.dim()), hint);
+    }
+
+    /// The hits around the cursor, scrolled to keep it in view.
+    fn render_list(&mut self, frame: &mut Frame<'_>, area: Rect) {
+        let rows = usize::from(area.height).max(1);
+        self.page = rows;
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + rows {
+            self.top = self.cursor + 1 - rows;
+        }
+        let width = usize::from(area.width);
+        let lines: Vec<Line<'_>> = self
+            .hits
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(rows)
+            .map(|(index, hit)| self.list_line(hit, index == self.cursor, width))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    /// `> * path` fitted to `width`, matched characters highlighted. Long
+    /// paths keep their end, where the file name is.
+    fn list_line(&self, hit: &Hit, current: bool, width: usize) -> Line<'_> {
+        let label = self
+            .items
+            .get(hit.item)
+            .map_or("", |item| item.label.as_str());
+        let marker = match (current, self.selected.contains(&hit.item)) {
+            (true, true) => ">* ",
+            (true, false) => ">  ",
+            (false, true) => " * ",
+            (false, false) => "   ",
+        };
+        let base = if current {
+            Style::new().bold()
+        } else {
+            Style::new()
+        };
+        let room = width.saturating_sub(marker.len());
+        let skip = label.chars().count().saturating_sub(room);
+        let mut spans = vec![Span::styled(marker, base)];
+        for (i, c) in label.chars().enumerate().skip(skip) {
+            let span = if i == skip && skip > 0 {
+                Span::styled("…", base)
+            } else if hit.positions.contains(&i) {
+                Span::styled(c.to_string(), base.yellow())
+            } else {
+                Span::styled(c.to_string(), base)
+            };
+            spans.push(span);
+        }
+        Line::from(spans)
+    }
+
+    /// The start of the file under the cursor, behind a divider.
+    fn render_preview(&mut self, frame: &mut Frame<'_>, area: Rect) {
+        self.load_preview();
+        let lines: Vec<Line<'_>> = self
+            .preview
+            .as_ref()
+            .map_or(&[][..], |(_, lines)| lines.as_slice())
+            .iter()
+            .skip(self.preview_top)
+            .take(usize::from(area.height))
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        let divider = Block::new()
+            .borders(Borders::LEFT)
+            .border_style(Style::new().dim())
+            .padding(Padding::horizontal(1));
+        frame.render_widget(Paragraph::new(lines).block(divider), area);
+    }
+
+    /// Reads the file under the cursor into the preview, once per file.
+    fn load_preview(&mut self) {
+        let Some(item) = self.hits.get(self.cursor).map(|hit| hit.item) else {
+            self.preview = None;
+            return;
+        };
+        if self
+            .preview
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != item)
+        {
+            let lines = self
+                .items
+                .get(item)
+                .map(|item| preview(&item.path))
+                .unwrap_or_default();
+            self.preview = Some((item, lines));
+        }
+    }
+}
+
+/// The start of a file as printable lines: tabs expanded, control
+/// characters shown as `?`; binary files and directories get a note.
+fn preview(path: &Path) -> Vec<String> {
+    if path.is_dir() {
+        return vec!["(directory)".to_string()];
+    }
+    let mut bytes = Vec::new();
+    if let Err(e) =
+        File::open(path).and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut bytes))
+    {
+        return vec![format!("(unreadable: {e})")];
+    }
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        let size = fs::metadata(path).map_or(0, |m| m.len());
+        return vec![format!("(binary, {size} bytes)")];
+    }
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .map(|line| {
+            line.replace('\t', "    ")
+                .chars()
+                .map(|c| if c.is_control() { '?' } else { c })
+                .collect()
+        })
+        .collect()
+}
+
+/// fzf-style matching of `query` (space separated terms, all required)
+/// against `text`: each term's characters in order, scored higher when they
+/// are consecutive, start a word or path component, or fall in the file
+/// name, and when the text is short. Smart case: a term with an uppercase
+/// letter is matched case-sensitively. Returns the matched character indices.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let name_start = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut positions = Vec::new();
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.chars().collect();
+        let exact = term.iter().any(|c| c.is_uppercase());
+        let same = |a: char, b: char| {
+            if exact {
+                a == b
+            } else {
+                a.to_lowercase().eq(b.to_lowercase())
+            }
+        };
+
+        // Earliest end of a match, then its latest start: the tightest window
+        let mut next = term.iter().copied().peekable();
+        let mut end = None;
+        for (i, &c) in chars.iter().enumerate() {
+            if next.peek().is_some_and(|&t| same(c, t)) {
+                next.next();
+                if next.peek().is_none() {
+                    end = Some(i);
+                    break;
+                }
+            }
+        }
+        let end = end?;
+        let mut found = Vec::with_capacity(term.len());
+        let mut wanted = term.iter().rev().copied().peekable();
+        for i in (0..=end).rev() {
+            if let Some(&t) = wanted.peek()
+                && chars.get(i).is_some_and(|&c| same(c, t))
+            {
+                wanted.next();
+                found.push(i);
+            }
+        }
+        found.reverse();
+
+        let mut last: Option<usize> = None;
+        for &i in &found {
+            score += 16;
+            let boundary = i
+                .checked_sub(1)
+                .and_then(|p| chars.get(p))
+                .is_none_or(|c| matches!(c, '/' | '_' | '-' | '.' | ' '));
+            if boundary {
+                score += 10;
+            }
+            if i >= name_start {
+                score += 4;
+            }
+            match last {
+                Some(prev) if prev + 1 == i => score += 12,
+                Some(prev) => score -= i64::try_from(i - prev - 1).unwrap_or(i64::MAX).min(8),
+                None => {}
+            }
+            last = Some(i);
+        }
+        positions.extend(found);
+    }
+    score -= i64::try_from(chars.len() / 8).unwrap_or(i64::MAX);
+    positions.sort_unstable();
+    Some((score, positions))
+}
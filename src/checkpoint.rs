@@ -0,0 +1,74 @@
+//! `--checkpoint`: progress records that let an interrupted run resume.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// The last entry whose record was completely written, and how long the
+/// output was at that point. Checkpointed walks are sorted by file name, so
+/// everything up to `path` is done.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    /// Relative to the base path, `/` separated.
+    pub path: String,
+    /// Output bytes up to and including the record of `path`.
+    pub offset: u64,
+    /// Records written so far, over all resumed runs.
+    pub files: usize,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(path: String, offset: u64, files: usize) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            path,
+            offset,
+            files,
+        }
+    }
+
+    /// `None` when there is nothing to resume (no checkpoint file).
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open checkpoint {}", path.display()));
+            }
+        };
+        let checkpoint: Self = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid checkpoint {}", path.display()))?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            anyhow::bail!(
+                "Unsupported checkpoint version {} in {}",
+                checkpoint.version,
+                path.display()
+            );
+        }
+        Ok(Some(checkpoint))
+    }
+
+    /// Replaces `path` through a rename, so a crash mid-save keeps the
+    /// previous checkpoint.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let mut file = File::create(&temp)?;
+        serde_json::to_writer(&mut file, self)?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    }
+
+    /// The resume point as a path relative to the base path.
+    pub(crate) fn relative_path(&self) -> PathBuf {
+        self.path.split('/').collect()
+    }
+}
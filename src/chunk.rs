@@ -0,0 +1,88 @@
+//! `--chunk-size` / `--chunk-overlap`: file content cut into overlapping
+//! pieces for embedding pipelines, each emitted as a record of its own.
+
+use std::ops::Range;
+
+/// One piece of a file's emitted content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Chunk<'a> {
+    /// Position among the file's chunks, from 0.
+    pub(crate) index: usize,
+    /// Byte range in the emitted content (after redaction and the line
+    /// options), end exclusive.
+    pub(crate) bytes: Range<usize>,
+    /// First and last line, 1-based.
+    pub(crate) lines: (usize, usize),
+    pub(crate) text: &'a str,
+}
+
+/// Cuts `text` into chunks of at most `size` bytes that end at line breaks;
+/// only a line longer than `size` is cut inside (at a character boundary).
+/// Each chunk after the first repeats the whole lines, up to `overlap`
+/// bytes, that ended the previous one. Empty text has no chunks.
+pub(crate) fn chunks(text: &str, size: usize, overlap: usize) -> Vec<Chunk<'_>> {
+    let size = size.max(1);
+    let units = units(text, size);
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while let Some(start) = units.get(first).map(|u| u.start) {
+        // As many whole units as fit, at least one
+        let mut last = first;
+        while units
+            .get(last + 1)
+            .is_some_and(|next| next.end - start <= size)
+        {
+            last += 1;
+        }
+        let end = units.get(last).map_or(start, |u| u.end);
+        chunks.push(Chunk {
+            index: chunks.len(),
+            bytes: start..end,
+            lines: (line_of(start), line_of(end.saturating_sub(1).max(start))),
+            text: text.get(start..end).unwrap_or_default(),
+        });
+        if last + 1 >= units.len() {
+            break;
+        }
+
+        // The next chunk starts at the earliest unit after `first` from
+        // which the rest of this chunk fits in the overlap
+        let mut next = last + 1;
+        while next > first + 1
+            && units
+                .get(next - 1)
+                .is_some_and(|u| end - u.start <= overlap)
+        {
+            next -= 1;
+        }
+        first = next;
+    }
+    chunks
+}
+
+/// Lines (with their newline), and lines longer than `size` in pieces.
+fn units(text: &str, size: usize) -> Vec<Range<usize>> {
+    let mut units = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        let mut start = offset;
+        while end - start > size {
+            let mut cut = text.floor_char_boundary(start + size);
+            if cut == start {
+                // A single character wider than `size`
+                cut = text.ceil_char_boundary(start + 1);
+            }
+            units.push(start..cut);
+            start = cut;
+        }
+        units.push(start..end);
+        offset = end;
+    }
+    units
+}
@@ -0,0 +1,2529 @@
+//! Traversal engine: walker setup, entry evaluation and the write pipeline.
+
+use crate::archive::{archive_name, write_archive};
+use crate::binary::is_binary_file;
+use crate::checkpoint::Checkpoint;
+use crate::documents::extracts;
+use crate::errors::{ErrorLog, io_kind, walk_error_details, walk_loop};
+use crate::expr::Candidate;
+use crate::filter::Filters;
+use crate::frontmatter::{Fields, read_front_matter};
+use crate::generated::{Generated, detect_generated};
+use crate::git::{ExportIgnore, GitCommit, GitFilter, GitLog, changed_paths};
+use crate::hash::hash_file;
+use crate::html::write_html;
+use crate::image::is_image;
+use crate::incremental::{Stamp, State};
+use crate::languages::{LanguageCount, count_lines};
+use crate::lfs::{LfsPointer, read_pointer, smudge};
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::metadata::format_time;
+use crate::mime::{detect_mime, mime_matches};
+use crate::options::{
+    Backend, BinaryMode, BudgetStrategy, CollectOptions, Column, Compression, EntryType,
+    HashAlgorithm, LfsMode, LineEnding, OutputFormat, PathStyle, SortKey, SymlinkMode,
+};
+use crate::output::{DirectOutput, display_path, process_file, write_delimited_header};
+use crate::paths;
+use crate::redact::Redactor;
+use crate::search::{LineMatch, content_matches, search_file_content};
+use crate::secrets::{Finding, SecretRules};
+use crate::template::{Placeholder, Template, Templates, unescape};
+use crate::todos::{Todo, TodoScanner};
+use crate::tokens::{self, estimate_tokens};
+use crate::tree::write_tree;
+use crate::units::format_size;
+use crate::unpack::{Scratch, Unpacked, archive_kind, member_path};
+use crate::uring::Prefetch;
+use anyhow::{Context, Result, bail};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, Walk, WalkBuilder, WalkState};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry as Slot;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Project-level ignore file with gitignore syntax, read in every directory.
+const IGNORE_FILENAME: &str = ".collectignore";
+
+/// How often `checkpoint` is rewritten while records are written.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Entries the walk may run ahead of the writer.
+const PIPELINE_DEPTH: usize = 64;
+
+/// A file that passed every filter and is ready to be written.
+#[derive(Debug)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    meta: Option<Metadata>,
+    matches: Vec<LineMatch>,
+    tokens: Option<u64>,
+    aliases: Vec<PathBuf>,
+    git: Option<Arc<GitCommit>>,
+    /// Git LFS pointer shown as such (`lfs` flag, or a failed smudge).
+    lfs: Option<LfsPointer>,
+    /// Local copy of the LFS object the file points to (`lfs` smudge).
+    object: Option<PathBuf>,
+    /// Markdown front matter (`frontmatter` or `frontmatter_filters`).
+    front_matter: Option<Fields>,
+    /// Why the content is left out (`skip_generated`).
+    generated: Option<Generated>,
+    /// The archive an `archives` member comes from, unpacked on first read.
+    member: Option<Arc<Unpacked>>,
+}
+
+impl Entry {
+    /// Path as produced by the walker (rooted at the base path).
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Depth below the base path (1 = direct child).
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// File metadata; only loaded when an output option needs it.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.meta.as_ref()
+    }
+
+    /// Lines matched by the content pattern (with `show_matches`).
+    pub fn matches(&self) -> &[LineMatch] {
+        &self.matches
+    }
+
+    /// Estimated token count (with `count_tokens` / `max_tokens`).
+    pub fn tokens(&self) -> Option<u64> {
+        self.tokens
+    }
+
+    /// Other hardlinks to the same file, folded into this entry (with
+    /// `dedupe_hardlinks`).
+    pub fn aliases(&self) -> &[PathBuf] {
+        &self.aliases
+    }
+
+    /// The last commit that touched the file (with `git_meta`; `None` for
+    /// untracked files).
+    pub fn git_commit(&self) -> Option<&GitCommit> {
+        self.git.as_deref()
+    }
+
+    /// Where the content is read from: the file, or the LFS object it
+    /// points to.
+    pub(crate) fn content_path(&self) -> &Path {
+        if let Some(member) = &self.member {
+            member.unpack();
+        }
+        self.object.as_deref().unwrap_or(&self.path)
+    }
+
+    pub(crate) fn lfs_pointer(&self) -> Option<&LfsPointer> {
+        self.lfs.as_ref()
+    }
+
+    pub(crate) fn generated(&self) -> Option<Generated> {
+        self.generated
+    }
+
+    /// What content output shows in place of the content: the LFS pointer
+    /// or generated file marker.
+    pub(crate) fn content_marker(&self) -> Option<String> {
+        match (&self.lfs, self.generated) {
+            (Some(pointer), _) => Some(pointer.marker()),
+            (None, Some(generated)) => Some(generated.marker()),
+            (None, None) => None,
+        }
+    }
+
+    pub(crate) fn front_matter(&self) -> Option<&Fields> {
+        self.front_matter.as_ref()
+    }
+}
+
+/// Totals of a finished `Collector::write` or `Collector::count` run.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub files: usize,
+    /// Directories visited by the walker (including the base path).
+    pub dirs: usize,
+    /// Total size of the matched files (only computed by `count`).
+    pub bytes: Option<u64>,
+    /// Per-extension totals, keyed by lowercase extension ("" = none). Only filled by `count`.
+    pub extensions: BTreeMap<String, ExtensionCount>,
+    /// Content bytes that would be emitted, after `max_bytes` (only computed by `stats`).
+    pub content_bytes: Option<u64>,
+    /// Matched files whose content would be suppressed as binary (only computed by `stats`).
+    pub binary_files: Option<usize>,
+    /// Estimated tokens emitted, when token counting is enabled.
+    pub tokens: Option<u64>,
+    /// Symlinked directories skipped as loops (with `follow_symlinks`).
+    pub loops: usize,
+    /// Set when a `max_files` / `max_output_bytes` limit ended the run early.
+    pub stopped: Option<Limit>,
+    pub elapsed: Duration,
+}
+
+/// A hard limit that stopped a run before every match was processed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Files(usize),
+    OutputBytes(u64),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Files(n) => write!(f, "file limit ({n}) reached"),
+            Self::OutputBytes(n) => write!(f, "output limit ({}) reached", format_size(*n)),
+        }
+    }
+}
+
+/// Outcome of [`Collector::write_split`].
+#[derive(Clone, Debug)]
+pub struct SplitSummary {
+    pub summary: Summary,
+    /// The parts written, in order.
+    pub parts: Vec<PathBuf>,
+}
+
+/// `OUTPUT.001`, `OUTPUT.002`, ... (more digits past 999).
+fn part_path(output: &Path, n: usize) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(format!(".{n:03}"));
+    PathBuf::from(path)
+}
+
+/// First line of a text part; `files` is the 1-based range it holds.
+fn part_banner(n: usize, parts: usize, files: (usize, usize), total: usize) -> String {
+    format!(
+        "--- part {n} of {parts} (files {}-{} of {total}) ---\n\n",
+        files.0, files.1
+    )
+}
+
+/// Snapshot of a running collection, from [`Collector::progress`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    /// Entries the walk has looked at so far.
+    pub scanned: usize,
+    /// Entries that passed the filters.
+    pub matched: usize,
+    /// Output bytes written (uncompressed).
+    pub written: u64,
+    /// Number of matches, once known (buffered or sorted runs).
+    pub total: Option<usize>,
+}
+
+/// Files and bytes matched for one extension.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtensionCount {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Matched bytes under one directory (`Collector::disk_usage`) or of one
+/// file (`Collector::largest`, where `files` is 1).
+#[derive(Clone, Debug)]
+pub struct Usage {
+    /// Relative to the base path, `/`-separated; "." is the base path itself.
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Size from the metadata the filters loaded, else one more stat.
+pub(crate) fn entry_size(entry: &Entry) -> u64 {
+    match (&entry.meta, &entry.object) {
+        (_, Some(object)) => fs::metadata(object).map_or(0, |m| m.len()),
+        (Some(meta), None) => meta.len(),
+        (None, None) => fs::metadata(entry.content_path()).map_or(0, |m| m.len()),
+    }
+}
+
+/// Directories a `follow_symlinks` walk went through, by device and inode:
+/// a symlink to one of them is a loop (or a second route to the same tree)
+/// and is not followed. The walker itself only catches links to ancestors.
+#[derive(Debug, Default)]
+struct WalkedDirs {
+    ids: Mutex<HashSet<(u64, u64)>>,
+    loops: AtomicUsize,
+}
+
+impl WalkedDirs {
+    /// Starts a new walk from `base`.
+    fn reset(&self, base: &Path) {
+        let mut ids = self.ids.lock().unwrap_or_else(PoisonError::into_inner);
+        ids.clear();
+        ids.extend(dir_id(base));
+        self.loops.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a directory about to be walked; `false` (and a warning) for a
+    /// symlink to a directory that was walked already.
+    fn enter(&self, path: &Path, symlink: bool, quiet: bool) -> bool {
+        let Some(id) = dir_id(path) else {
+            return true;
+        };
+        let new = self
+            .ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id);
+        if new || !symlink {
+            return true;
+        }
+        self.loops.fetch_add(1, Ordering::Relaxed);
+        if !quiet {
+            log::warn!(
+                "Skipped symlink loop: {} leads to a directory already walked",
+                path.display()
+            );
+        }
+        false
+    }
+}
+
+/// Device and inode of the directory a path resolves to.
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// No stable file ids without extra handles: only the walker's ancestor
+/// check applies.
+#[cfg(not(unix))]
+fn dir_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// `dedupe_hardlinks`: the first entry of each file with several links
+/// stays, and the paths of the later ones become its aliases.
+fn fold_hardlinks(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut first: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut kept: Vec<Entry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if let Some(id) = hardlink_id(&entry) {
+            match first.entry(id) {
+                Slot::Occupied(slot) => {
+                    if let Some(owner) = kept.get_mut(*slot.get()) {
+                        owner.aliases.push(entry.path);
+                        continue;
+                    }
+                }
+                Slot::Vacant(slot) => {
+                    slot.insert(kept.len());
+                }
+            }
+        }
+        kept.push(entry);
+    }
+    kept
+}
+
+/// Device and inode of a regular file with more than one link.
+#[cfg(unix)]
+fn hardlink_id(entry: &Entry) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let id = |m: &Metadata| (m.is_file() && m.nlink() > 1).then(|| (m.dev(), m.ino()));
+    match &entry.meta {
+        Some(meta) => id(meta),
+        None => fs::metadata(&entry.path).ok().as_ref().and_then(id),
+    }
+}
+
+#[cfg(not(unix))]
+fn hardlink_id(_entry: &Entry) -> Option<(u64, u64)> {
+    None
+}
+
+/// Traversal + filter pipeline. Build once, then iterate or write.
+#[derive(Debug)]
+pub struct Collector {
+    options: CollectOptions,
+    filters: Filters,
+    /// Compiled --glob / --exclude patterns (also applied to listed paths).
+    overrides: Option<Override>,
+    /// Compiled --template (replacing the built-in text layout) and
+    /// --header-template.
+    templates: Templates,
+    /// Compiled --redact patterns.
+    redactor: Option<Redactor>,
+    /// Patterns read from --exclude-from files (pruned during the walk).
+    exclude_file: Option<Gitignore>,
+    /// Paths selected by --git (runs git once, up front).
+    git: Option<GitFilter>,
+    /// Paths changed since --since-ref, relative to the base path and
+    /// sorted; they replace the walk.
+    changed: Option<Vec<PathBuf>>,
+    /// Paths excluded by --export-ignore (runs git once, up front).
+    export_ignore: Option<ExportIgnore>,
+    /// Where --archives extracts the members it matches.
+    scratch: Option<Scratch>,
+    /// Last commit per file for --git-meta (one `git log`, up front).
+    git_log: Option<GitLog>,
+    /// Directories seen by `evaluate` during the current run.
+    dirs: AtomicUsize,
+    /// Directories walked through so far, for symlink loop detection.
+    walked: Arc<WalkedDirs>,
+    /// Limit that ended the current run, if any.
+    stopped: Mutex<Option<Limit>>,
+    /// Progress loaded from `checkpoint`; cleared once a run completes.
+    resume: Mutex<Option<Checkpoint>>,
+    /// `incremental` state of the previous run, and the stamps recorded by
+    /// the current one (unchanged paths plus everything written).
+    previous: Mutex<State>,
+    seen: Mutex<BTreeMap<String, Stamp>>,
+    /// Sidecar for `errors`.
+    error_log: Option<ErrorLog>,
+    /// Errors reported during the current run.
+    errors: AtomicUsize,
+    /// Live counters behind `progress`.
+    scanned: AtomicUsize,
+    matched: AtomicUsize,
+    written: AtomicU64,
+    total: Mutex<Option<usize>>,
+}
+
+impl Collector {
+    /// Validates the options and compiles every pattern.
+    pub fn new(mut options: CollectOptions) -> Result<Self> {
+        if let (Some(min), Some(max)) = (options.min_depth, options.depth)
+            && min > max
+        {
+            bail!("--min-depth ({min}) is greater than --depth ({max})");
+        }
+        if options.threads == 0 {
+            options.threads = thread::available_parallelism().map_or(1, |n| n.get());
+        }
+        if options.max_tokens.is_some() {
+            options.count_tokens = true;
+        }
+        if options.stable {
+            if options.absolute_path {
+                bail!("--stable output cannot contain absolute paths");
+            }
+            // A checkpointed walk is in name order already
+            if options.sort == SortKey::None && options.checkpoint.is_none() {
+                options.sort = SortKey::Name;
+            }
+            if options.path_style == PathStyle::Native {
+                options.path_style = PathStyle::Forward;
+            }
+            options.normalize_eol.get_or_insert(LineEnding::Lf);
+            options.no_perms |= options.format.is_archive();
+        }
+        if options.since_ref.is_some()
+            && (options.files_from.is_some() || options.checkpoint.is_some())
+        {
+            bail!(
+                "--since-ref replaces the walk and cannot be combined with --files-from or --checkpoint"
+            );
+        }
+        if let Some(dir) = &options.relative_to {
+            if options.absolute_path {
+                bail!("--relative-to cannot be combined with --absolute");
+            }
+            let canonical = paths::canonicalize(dir)
+                .with_context(|| format!("Cannot resolve --relative-to {}", dir.display()))?;
+            if !canonical.is_dir() {
+                bail!("--relative-to {} is not a directory", dir.display());
+            }
+            options.relative_to = Some(canonical);
+        }
+        if options.print0 && (options.format != OutputFormat::Text || options.read_content) {
+            bail!("NUL-separated output is only supported for text path listings");
+        }
+        if options.format.is_delimited() && options.read_content {
+            bail!("CSV / TSV output is a listing and cannot include content");
+        }
+        if options.types.contains(&EntryType::Dir)
+            && (options.read_content || options.format.is_archive())
+        {
+            bail!("Directories can only be listed, not read or archived");
+        }
+        if options.types.contains(&EntryType::Special)
+            && (options.read_content || options.format.is_archive() || options.hash.is_some())
+        {
+            bail!("Special files (FIFOs, sockets, devices) can only be listed, not read");
+        }
+        if options.ads_content {
+            options.ads = true;
+        }
+        if (options.owner.is_some() || options.group.is_some() || options.perm.is_some())
+            && !cfg!(unix)
+        {
+            bail!("--owner, --group and --perm need Unix file ownership and mode bits");
+        }
+        if options.xattrs && !xattr::SUPPORTED_PLATFORM {
+            bail!("--xattrs is not supported on this platform");
+        }
+        if options.ads && !cfg!(windows) {
+            bail!("--ads needs NTFS alternate data streams, which only exist on Windows");
+        }
+        if options.follow_symlinks && options.symlinks == SymlinkMode::Skip {
+            bail!("--symlinks skip cannot be combined with --follow-symlinks");
+        }
+        if options.numeric_owner && options.format != OutputFormat::Tar {
+            bail!("--numeric-owner only applies to --format tar (zip stores no owners)");
+        }
+        if options.no_perms && !options.format.is_archive() {
+            bail!("--no-perms only applies to --format tar / zip");
+        }
+        if options.format == OutputFormat::Tree && options.read_content {
+            bail!("Tree output is a listing and cannot include content");
+        }
+        if options.checkpoint.is_some() {
+            if options.incremental.is_some() {
+                bail!("--checkpoint cannot be combined with --incremental");
+            }
+            if options.compress.is_some() {
+                bail!("--checkpoint cannot resume compressed output");
+            }
+            if matches!(options.format, OutputFormat::Tree | OutputFormat::Html)
+                || options.format.is_archive()
+            {
+                bail!("--checkpoint needs a record format (text, json, ndjson, csv, tsv)");
+            }
+            if options.sort != SortKey::None || options.reverse || options.files_from.is_some() {
+                bail!(
+                    "--checkpoint resumes a walk in name order; --sort and --files-from cannot be used"
+                );
+            }
+        }
+        if options.split_bytes.is_some() || options.split_files.is_some() {
+            if options.split_bytes == Some(0) || options.split_files == Some(0) {
+                bail!("--split-bytes / --split-files must be greater than 0");
+            }
+            if options.compress.is_some() || options.checkpoint.is_some() {
+                bail!("Split output cannot be compressed or resumed with --checkpoint");
+            }
+            if matches!(options.format, OutputFormat::Tree | OutputFormat::Html)
+                || options.format.is_archive()
+            {
+                bail!("Split output needs a record format (text, json, ndjson, csv, tsv)");
+            }
+        }
+        if options.max_line_bytes == Some(0) {
+            bail!("--max-line-bytes must be greater than 0");
+        }
+        if let Some(size) = options.chunk_size {
+            if size == 0 {
+                bail!("--chunk-size must be greater than 0");
+            }
+            if options.chunk_overlap >= size {
+                bail!("--chunk-overlap must be smaller than --chunk-size");
+            }
+            if !matches!(
+                options.format,
+                OutputFormat::Text | OutputFormat::Json | OutputFormat::Ndjson
+            ) || options.template.is_some()
+                || options.print0
+            {
+                bail!(
+                    "--chunk-size emits text, json or ndjson records and cannot be combined with --template or --print0"
+                );
+            }
+            options.read_content = true;
+        }
+        if !options.lines.is_empty()
+            && (options.head_lines.is_some() || options.tail_lines.is_some())
+        {
+            bail!("--lines cannot be combined with --head-lines / --tail-lines");
+        }
+        if options.format.is_delimited() && options.columns.contains(&Column::Tokens) {
+            options.count_tokens = true;
+        }
+        if options.format.is_delimited() && options.columns.iter().any(|c| c.is_git()) {
+            options.git_meta = true;
+        }
+        let template = options
+            .template
+            .as_deref()
+            .map(|t| Template::parse(t, "--template"))
+            .transpose()?;
+        if template.is_some() && (options.format != OutputFormat::Text || options.print0) {
+            bail!(
+                "--template replaces the text layout and cannot be combined with --format or --print0"
+            );
+        }
+        if template
+            .as_ref()
+            .is_some_and(|t| t.uses(Placeholder::Tokens))
+        {
+            options.count_tokens = true;
+        }
+        if template.as_ref().is_some_and(|t| t.uses_git()) {
+            options.git_meta = true;
+        }
+        let header = options
+            .header_template
+            .as_deref()
+            .map(|t| Template::parse(t, "--header-template"))
+            .transpose()?;
+        if let Some(header) = &header {
+            if template.is_some() || options.format != OutputFormat::Text {
+                bail!("--header-template only applies to the built-in text layout");
+            }
+            if header.uses(Placeholder::Content) {
+                bail!("--header-template cannot use {{content}}: it only replaces the header line");
+            }
+            if header.uses(Placeholder::Tokens) {
+                options.count_tokens = true;
+            }
+            if header.uses_git() {
+                options.git_meta = true;
+            }
+        }
+        if (options.template_header.is_some() || options.template_footer.is_some())
+            && (options.format != OutputFormat::Text || options.print0)
+        {
+            bail!("--preamble / --epilogue only apply to text output");
+        }
+        let templates = Templates {
+            record: template,
+            header,
+        };
+        let redactor = Redactor::new(&options)?;
+        let filters = Filters::new(&options)?;
+        let overrides = build_overrides(&options)?;
+        let exclude_file = build_exclude_file(&options)?;
+        let git = if options.git.is_empty() {
+            None
+        } else {
+            Some(GitFilter::new(&options.base_path, &options.git)?)
+        };
+        let changed = match &options.since_ref {
+            Some(reference) => Some(changed_since(&options, reference)?),
+            None => None,
+        };
+        let scratch = if options.archives {
+            Some(
+                Scratch::new(options.quiet)
+                    .context("Failed to create a directory for archive members")?,
+            )
+        } else {
+            None
+        };
+        let git_log = if options.git_meta {
+            Some(GitLog::new(&options.base_path)?)
+        } else {
+            None
+        };
+        let export_ignore = if options.export_ignore {
+            Some(ExportIgnore::new(&options.base_path)?)
+        } else {
+            None
+        };
+        let resume = match &options.checkpoint {
+            Some(path) => Checkpoint::load(path)?,
+            None => None,
+        };
+        if let Some(resume) = &resume {
+            log::info!(
+                "Resuming after {} ({} files, {} bytes written)",
+                resume.path,
+                resume.files,
+                resume.offset
+            );
+        }
+        let error_log = options
+            .errors
+            .as_deref()
+            .map(ErrorLog::create)
+            .transpose()?;
+        let previous = match &options.incremental {
+            Some(path) => State::load(path)?,
+            None => State::default(),
+        };
+        if options.incremental.is_some() {
+            log::debug!("Incremental state: {} known paths", previous.files.len());
+        }
+        Ok(Self {
+            options,
+            filters,
+            overrides,
+            templates,
+            redactor,
+            exclude_file,
+            git,
+            changed,
+            scratch,
+            export_ignore,
+            git_log,
+            dirs: AtomicUsize::new(0),
+            walked: Arc::default(),
+            stopped: Mutex::new(None),
+            resume: Mutex::new(resume),
+            previous: Mutex::new(previous),
+            seen: Mutex::new(BTreeMap::new()),
+            error_log,
+            errors: AtomicUsize::new(0),
+            scanned: AtomicUsize::new(0),
+            matched: AtomicUsize::new(0),
+            written: AtomicU64::new(0),
+            total: Mutex::new(None),
+        })
+    }
+
+    pub fn options(&self) -> &CollectOptions {
+        &self.options
+    }
+
+    /// Per-file errors (unreadable files, traversal failures, failed commands)
+    /// reported by the last run; they are skipped, not fatal.
+    pub fn error_count(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Counters of the run in progress; safe to poll from another thread.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            scanned: self.scanned.load(Ordering::Relaxed),
+            matched: self.matched.load(Ordering::Relaxed),
+            written: self.written.load(Ordering::Relaxed),
+            total: *self.total.lock().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    /// Path-only check for a single path below the base path: hidden files,
+    /// `globs` / `exclude` and the name / path filters. Ignore files are not
+    /// consulted. Works for paths that no longer exist (watch mode deletions).
+    pub fn accepts_path(&self, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        let relative = path.strip_prefix(&self.options.base_path).unwrap_or(path);
+
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.') && c.as_os_str() != "..");
+        if hidden && !self.options.include_hidden && !self.options.no_default_excludes {
+            return false;
+        }
+
+        if let Some(overrides) = &self.overrides
+            && overrides.matched(path, is_dir).is_ignore()
+        {
+            return false;
+        }
+        if self.excluded_by_file(path, is_dir) {
+            return false;
+        }
+
+        self.filters.should_process(path, is_dir)
+    }
+
+    /// Iterates over matching entries. With more than one thread (or any
+    /// sort order) the tree is walked up front and the entries come back sorted.
+    /// With `files_from`, the listed paths replace the traversal.
+    pub fn entries(&self) -> Result<Entries<'_>> {
+        let options = &self.options;
+        // Checkpoints rely on the sequential walk's name order
+        let parallel = options.threads > 1
+            && options.files_from.is_none()
+            && options.selected.is_none()
+            && options.since_ref.is_none()
+            && options.checkpoint.is_none();
+        let resume_after = self.resume().map(|c| c.relative_path());
+
+        log::debug!(
+            "Collecting from {} ({} thread{})",
+            options
+                .files_from
+                .as_deref()
+                .unwrap_or(&options.base_path)
+                .display(),
+            options.threads,
+            if options.threads == 1 { "" } else { "s" }
+        );
+        let inner = if let Some(paths) = &options.selected {
+            EntriesInner::Listed(listed(paths.clone()))
+        } else if let Some(source) = &options.files_from {
+            let paths = read_path_list(source)
+                .with_context(|| format!("Failed to read file list {}", source.display()))?;
+            EntriesInner::Listed(listed(paths))
+        } else if let Some(changed) = &self.changed {
+            let base = &options.base_path;
+            EntriesInner::Listed(
+                changed
+                    .iter()
+                    .map(|relative| ListedPath {
+                        path: base.join(relative),
+                        depth: relative.components().count(),
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )
+        } else if parallel {
+            EntriesInner::Sorted(self.collect_parallel(&self.walk_builder()).into_iter())
+        } else {
+            EntriesInner::Sequential(Box::new(self.walk_builder().build()))
+        };
+
+        let drop_largest =
+            options.max_tokens.is_some() && options.budget_strategy == BudgetStrategy::DropLargest;
+        let stop_budget = options.max_tokens.filter(|_| !drop_largest);
+        self.set_stopped(None);
+        self.scanned.store(0, Ordering::Relaxed);
+        self.matched.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        *self.total.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+
+        if !parallel
+            && !drop_largest
+            && !options.dedupe_hardlinks
+            && options.sort == SortKey::None
+            && !options.reverse
+        {
+            return Ok(Entries {
+                collector: self,
+                inner,
+                remaining_tokens: stop_budget,
+                remaining_files: options.max_files,
+                resume_after,
+                pending: VecDeque::new(),
+            });
+        }
+
+        // Buffered: the budget is applied to the final (sorted) order
+        let mut entries: Vec<Entry> = Entries {
+            collector: self,
+            inner,
+            remaining_tokens: None,
+            remaining_files: None,
+            resume_after,
+            pending: VecDeque::new(),
+        }
+        .collect();
+        self.sort_entries(&mut entries, parallel);
+        if options.dedupe_hardlinks {
+            entries = fold_hardlinks(entries);
+        }
+        // Counted again as they come out in order, now against a known total
+        self.matched.store(0, Ordering::Relaxed);
+        *self.total.lock().unwrap_or_else(PoisonError::into_inner) = Some(entries.len());
+        if let Some(budget) = options.max_tokens.filter(|_| drop_largest) {
+            let dropped = tokens::drop_largest(&mut entries, budget);
+            if dropped > 0 {
+                self.warn(&format!(
+                    "Token budget: dropped the {dropped} largest files to stay within {budget} tokens"
+                ));
+            }
+        }
+        Ok(Entries {
+            collector: self,
+            inner: EntriesInner::Sorted(entries.into_iter()),
+            remaining_tokens: stop_budget,
+            remaining_files: options.max_files,
+            resume_after: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Runs the whole pipeline into `writer`.
+    /// Per-file read errors are reported as warnings; write errors
+    /// (including BrokenPipe) are returned so the caller can decide.
+    /// With `compress`, the whole stream goes through the encoder.
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<Summary> {
+        match self.options.compress {
+            None => self.write_records(writer, None),
+            Some(Compression::Gzip) => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+                let summary = self.write_records(&mut encoder, None)?;
+                encoder.finish()?;
+                Ok(summary)
+            }
+            Some(Compression::Zstd) => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, 0)?;
+                let summary = self.write_records(&mut encoder, None)?;
+                encoder.finish()?;
+                Ok(summary)
+            }
+        }
+    }
+
+    /// Like `write`, into a file at its current position. Content that is
+    /// emitted unchanged (no line numbers, highlighting or compression) is
+    /// then copied file to file by the kernel where supported.
+    pub fn write_file(&self, file: &File) -> io::Result<Summary> {
+        let mut writer = BufWriter::with_capacity(64 * 1024, file);
+        let summary = if self.options.compress.is_some() {
+            self.write(&mut writer)?
+        } else {
+            self.write_records(&mut writer, Some(file))?
+        };
+        writer.flush()?;
+        Ok(summary)
+    }
+
+    fn write_records(&self, writer: &mut dyn Write, direct: Option<&File>) -> io::Result<Summary> {
+        let start = Instant::now();
+        self.dirs.store(0, Ordering::Relaxed);
+
+        let written = &self.written;
+        written.store(0, Ordering::Relaxed);
+        let mut counting = CountingWriter {
+            inner: writer,
+            written,
+        };
+        let writer: &mut dyn Write = &mut counting;
+        // Records are never cut: the limit is checked before each one starts
+        let output_left = || match self.options.max_output_bytes {
+            Some(max) if written.load(Ordering::Relaxed) >= max => {
+                self.set_stopped(Some(Limit::OutputBytes(max)));
+                false
+            }
+            _ => true,
+        };
+
+        // A resumed run continues the output: headers were written already
+        let resume = self.resume();
+        if resume.is_none() {
+            self.write_prologue(writer)?;
+        }
+
+        let mut tokens = 0u64;
+        let walk = self.entries().map_err(io::Error::other)?;
+        let count = self.pipelined(walk, |entries| {
+            let entries = entries.take_while(|_| output_left()).inspect(|e| {
+                tokens += e.tokens.unwrap_or(0);
+                self.record_written(e);
+            });
+            self.write_entries(entries, writer, direct, resume.as_ref(), &output_left)
+        })?;
+
+        self.write_epilogue(writer, count > 0 || resume.is_some())?;
+        self.save_incremental()?;
+        if let Some(checkpoint) = &self.options.checkpoint {
+            // Complete: the next run starts over
+            writer.flush()?;
+            match fs::remove_file(checkpoint) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            *self.resume.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+
+        Ok(Summary {
+            files: count,
+            dirs: self.dirs.load(Ordering::Relaxed),
+            loops: self.walked.loops.load(Ordering::Relaxed),
+            bytes: None,
+            extensions: BTreeMap::new(),
+            content_bytes: None,
+            binary_files: None,
+            tokens: self.options.count_tokens.then_some(tokens),
+            stopped: self.stopped(),
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Opens a document: the JSON bracket, the CSV / TSV header row, the
+    /// template header.
+    fn write_prologue(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let format = self.options.format;
+        if format == OutputFormat::Json {
+            writer.write_all(b"[\n")?;
+        }
+        if format.is_delimited() {
+            write_delimited_header(&self.options, writer)?;
+        }
+        if let Some(header) = &self.options.template_header {
+            writer.write_all(unescape(header).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Closes a document; `any` tells whether it holds a record.
+    fn write_epilogue(&self, writer: &mut dyn Write, any: bool) -> io::Result<()> {
+        if self.options.format == OutputFormat::Json {
+            writer.write_all(if any { b"\n]\n" } else { b"]\n" })?;
+        }
+        if let Some(footer) = &self.options.template_footer {
+            writer.write_all(unescape(footer).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Stores the stamps of this run for the next `incremental` one.
+    fn save_incremental(&self) -> io::Result<()> {
+        if let Some(state) = &self.options.incremental {
+            let files =
+                std::mem::take(&mut *self.seen.lock().unwrap_or_else(PoisonError::into_inner));
+            let current = State::new(files);
+            current.save(state)?;
+            *self.previous.lock().unwrap_or_else(PoisonError::into_inner) = current;
+        }
+        Ok(())
+    }
+
+    /// Like `write_file`, into numbered parts `OUTPUT.001`, `OUTPUT.002`, ...
+    /// that each hold whole records and stay within `split_bytes` /
+    /// `split_files` (a single larger record gets a part of its own). Every
+    /// part is a complete document in the format, and text parts open with a
+    /// `--- part N of M ---` banner. Parts left over from an earlier, longer
+    /// run are removed.
+    pub fn write_split(&self, output: &Path) -> io::Result<SplitSummary> {
+        let start = Instant::now();
+        let options = &self.options;
+        self.dirs.store(0, Ordering::Relaxed);
+        self.written.store(0, Ordering::Relaxed);
+
+        // Every part repeats the prologue and epilogue; the banner is
+        // written last, when the number of parts is known
+        let mut prologue = Vec::new();
+        self.write_prologue(&mut prologue)?;
+        let mut epilogue = Vec::new();
+        self.write_epilogue(&mut epilogue, true)?;
+        let banner = options.format == OutputFormat::Text && !options.print0;
+        let room = if banner {
+            part_banner(usize::MAX, usize::MAX, (usize::MAX, usize::MAX), usize::MAX).len()
+        } else {
+            0
+        };
+        let budget = options
+            .split_bytes
+            .map(|max| max.saturating_sub((prologue.len() + epilogue.len() + room) as u64));
+
+        let mut parts: Vec<(PathBuf, usize, usize)> = Vec::new();
+        let mut current: Option<(BufWriter<File>, u64)> = None;
+        let mut tokens = 0u64;
+        let mut count = 0;
+        let mut record = Vec::new();
+        for entry in self.entries().map_err(io::Error::other)? {
+            if let Some(max) = options.max_output_bytes
+                && self.written.load(Ordering::Relaxed) >= max
+            {
+                self.set_stopped(Some(Limit::OutputBytes(max)));
+                break;
+            }
+            record.clear();
+            if let Err(e) = process_file(
+                &entry,
+                options,
+                &self.templates,
+                self.redactor.as_ref(),
+                count,
+                &mut record,
+                None,
+            ) {
+                self.report("processing", &entry.path, &e);
+            }
+
+            let held = parts.last().map_or(0, |&(_, _, records)| records);
+            let full = current.as_ref().is_some_and(|(_, bytes)| {
+                budget.is_some_and(|budget| bytes + record.len() as u64 > budget)
+                    || options.split_files.is_some_and(|max| held >= max)
+            });
+            if current.is_none() || full {
+                if let Some((mut part, _)) = current.take() {
+                    part.write_all(&epilogue)?;
+                    part.flush()?;
+                }
+                let path = part_path(output, parts.len() + 1);
+                let mut part = BufWriter::with_capacity(64 * 1024, File::create(&path)?);
+                part.write_all(&prologue)?;
+                current = Some((part, 0));
+                parts.push((path, count, 0));
+            }
+            // The JSON separator belongs between records of the same part
+            let opening = parts.last().is_none_or(|&(_, _, records)| records == 0);
+            let body = match record.strip_prefix(b",\n") {
+                Some(body) if options.format == OutputFormat::Json && opening => body,
+                _ => record.as_slice(),
+            };
+            if let (Some((part, bytes)), Some((path, _, records))) =
+                (current.as_mut(), parts.last_mut())
+            {
+                if *records == 0 && budget.is_some_and(|budget| body.len() as u64 > budget) {
+                    self.warn(&format!(
+                        "{} alone exceeds --split-bytes; {} is larger",
+                        entry.path.display(),
+                        path.display()
+                    ));
+                }
+                part.write_all(body)?;
+                *bytes += body.len() as u64;
+                *records += 1;
+            }
+            self.written
+                .fetch_add(record.len() as u64, Ordering::Relaxed);
+            tokens += entry.tokens.unwrap_or(0);
+            self.record_written(&entry);
+            count += 1;
+        }
+        if let Some((mut part, _)) = current.take() {
+            part.write_all(&epilogue)?;
+            part.flush()?;
+        }
+
+        if banner {
+            for (n, (path, first, records)) in parts.iter().enumerate() {
+                let content = fs::read(path)?;
+                let mut part = BufWriter::new(File::create(path)?);
+                let files = (first + 1, first + records);
+                part.write_all(part_banner(n + 1, parts.len(), files, count).as_bytes())?;
+                part.write_all(&content)?;
+                part.flush()?;
+            }
+        }
+        // A shorter run than the last one: drop the parts it left behind
+        let mut stale = parts.len() + 1;
+        while fs::remove_file(part_path(output, stale)).is_ok() {
+            stale += 1;
+        }
+        self.save_incremental()?;
+
+        Ok(SplitSummary {
+            summary: Summary {
+                files: count,
+                dirs: self.dirs.load(Ordering::Relaxed),
+                loops: self.walked.loops.load(Ordering::Relaxed),
+                bytes: None,
+                extensions: BTreeMap::new(),
+                content_bytes: None,
+                binary_files: None,
+                tokens: options.count_tokens.then_some(tokens),
+                stopped: self.stopped(),
+                elapsed: start.elapsed(),
+            },
+            parts: parts.into_iter().map(|(path, _, _)| path).collect(),
+        })
+    }
+
+    /// Renders the entries in the output format. Returns how many were written.
+    fn write_entries(
+        &self,
+        entries: impl Iterator<Item = Entry>,
+        writer: &mut dyn Write,
+        direct: Option<&File>,
+        resume: Option<&Checkpoint>,
+        output_left: &dyn Fn() -> bool,
+    ) -> io::Result<usize> {
+        let format = self.options.format;
+        let written = &self.written;
+        let count = if format.is_archive() {
+            write_archive(entries, &self.options, writer, &|path, e| {
+                self.report("archiving", path, e);
+            })?
+        } else if format == OutputFormat::Tree {
+            write_tree(entries, &self.options, writer)?
+        } else if format == OutputFormat::Html {
+            write_html(entries, &self.options, self.redactor.as_ref(), writer)?
+        } else if self.options.threads > 1 && self.options.checkpoint.is_none() {
+            // Parallel: render on all cores, write back in order
+            let entries: Vec<Entry> = entries.collect();
+            self.write_parallel(&entries, writer, output_left)?
+        } else {
+            let (offset, done) = resume.map_or((0, 0), |c| (c.offset, c.files));
+            let mut last_save = Instant::now();
+            let mut count = 0;
+            for entry in entries {
+                // Handle IO errors directly
+                // (record index `done + count` keeps JSON separators right after a resume)
+                if let Err(e) = process_file(
+                    &entry,
+                    &self.options,
+                    &self.templates,
+                    self.redactor.as_ref(),
+                    done + count,
+                    writer,
+                    direct.map(|file| DirectOutput { file, written }),
+                ) {
+                    if e.kind() == io::ErrorKind::BrokenPipe {
+                        return Err(e);
+                    }
+                    self.report("processing", &entry.path, &e);
+                }
+                count += 1;
+
+                if let Some(checkpoint) = &self.options.checkpoint
+                    && last_save.elapsed() >= CHECKPOINT_INTERVAL
+                {
+                    // The offset must never cover bytes that are still buffered
+                    writer.flush()?;
+                    let name = archive_name(&entry.path, &self.options);
+                    Checkpoint::new(name, offset + written.load(Ordering::Relaxed), done + count)
+                        .save(checkpoint)?;
+                    last_save = Instant::now();
+                }
+            }
+            count
+        };
+        Ok(count)
+    }
+
+    /// Walks and filters on a second thread, handing entries to `consume`
+    /// through a bounded channel: reading and matching the next files
+    /// overlaps with writing the current one (and with compression).
+    fn pipelined<T>(
+        &self,
+        entries: Entries<'_>,
+        consume: impl FnOnce(&mut dyn Iterator<Item = Entry>) -> T,
+    ) -> T {
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::sync_channel(PIPELINE_DEPTH);
+            scope.spawn(move || {
+                for entry in entries {
+                    // The writer stopped (limit, error): stop walking too
+                    if tx.send(entry).is_err() {
+                        break;
+                    }
+                }
+            });
+            consume(&mut rx.into_iter())
+        })
+    }
+
+    /// Walks and filters like `write`, but only adds up what matched.
+    pub fn count(&self) -> Result<Summary> {
+        self.tally(false)
+    }
+
+    /// Dry run: `count` plus how much content `read_content` would emit
+    /// (binary files and `max_bytes` taken into account). Reads the first
+    /// chunk of every matched file, but writes nothing.
+    pub fn stats(&self) -> Result<Summary> {
+        self.tally(true)
+    }
+
+    /// Files and code / comment / blank lines per language over the matched
+    /// files, most code first. Binary files are left out.
+    pub fn languages(&self) -> Result<Vec<LanguageCount>> {
+        let mut totals: BTreeMap<&str, LanguageCount> = BTreeMap::new();
+        for entry in self.entries()? {
+            let size = entry_size(&entry);
+            match count_lines(entry.content_path(), size, self.options.binary_check) {
+                Ok(Some((language, lines))) => {
+                    let total = totals.entry(language).or_insert_with(|| LanguageCount {
+                        language: language.to_string(),
+                        ..LanguageCount::default()
+                    });
+                    total.add(&lines);
+                }
+                Ok(None) => {}
+                Err(e) => self.report("reading", &entry.path, &e),
+            }
+        }
+        let mut rows: Vec<_> = totals.into_values().collect();
+        rows.sort_by(|a, b| {
+            b.code
+                .cmp(&a.code)
+                .then_with(|| a.language.cmp(&b.language))
+        });
+        Ok(rows)
+    }
+
+    /// Likely credentials (cloud and service tokens, private keys, high-entropy
+    /// assignments) in the matched text files, in walk order.
+    pub fn scan_secrets(&self) -> Result<Vec<Finding>> {
+        let rules = SecretRules::new()?;
+        let mut findings = Vec::new();
+        for entry in self.entries()? {
+            let name = archive_name(&entry.path, &self.options);
+            match rules.scan(
+                entry.content_path(),
+                &name,
+                entry_size(&entry),
+                self.options.binary_check,
+            ) {
+                Ok(found) => findings.extend(found),
+                Err(e) => self.report("reading", &entry.path, &e),
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Lines carrying one of `markers` (`TODO`, `FIXME`... as whole,
+    /// case-sensitive words) in the matched text files, in walk order.
+    pub fn todos(&self, markers: &[String]) -> Result<Vec<Todo>> {
+        let scanner = TodoScanner::new(markers)?;
+        let mut todos = Vec::new();
+        for entry in self.entries()? {
+            let name = archive_name(&entry.path, &self.options);
+            match scanner.scan(
+                entry.content_path(),
+                &name,
+                entry_size(&entry),
+                self.options.binary_check,
+            ) {
+                Ok(found) => todos.extend(found),
+                Err(e) => self.report("reading", &entry.path, &e),
+            }
+        }
+        Ok(todos)
+    }
+
+    /// Bytes of the matched files added up per directory, descendants
+    /// included (like `du`), largest first. Only directories that contain
+    /// a match are listed.
+    pub fn disk_usage(&self) -> Result<Vec<Usage>> {
+        let mut dirs: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        for entry in self.entries()? {
+            let size = entry_size(&entry);
+            let name = archive_name(&entry.path, &self.options);
+            let mut parents: Vec<&str> = vec!["."];
+            parents.extend(name.match_indices('/').filter_map(|(i, _)| name.get(..i)));
+            for dir in parents {
+                let totals = dirs.entry(dir.to_string()).or_default();
+                totals.0 += 1;
+                totals.1 += size;
+            }
+        }
+        let mut rows: Vec<_> = dirs
+            .into_iter()
+            .map(|(path, (files, bytes))| Usage { path, files, bytes })
+            .collect();
+        rows.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        Ok(rows)
+    }
+
+    /// The `n` largest matched files, largest first.
+    pub fn largest(&self, n: usize) -> Result<Vec<Usage>> {
+        // Min-heap of the n largest so far
+        let mut heap = BinaryHeap::with_capacity(n.saturating_add(1));
+        for entry in self.entries()? {
+            let size = entry_size(&entry);
+            heap.push(Reverse((
+                size,
+                Reverse(archive_name(&entry.path, &self.options)),
+            )));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+        Ok(heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((bytes, Reverse(path)))| Usage {
+                path,
+                files: 1,
+                bytes,
+            })
+            .collect())
+    }
+
+    /// Hashes every matched file into a manifest (see `Manifest::diff`).
+    /// Files that cannot be read are reported and left out.
+    pub fn snapshot(&self, algorithm: HashAlgorithm) -> Result<Manifest> {
+        let mut files = Vec::new();
+        for entry in self.entries()? {
+            let meta = match &entry.meta {
+                Some(meta) => Ok(meta.clone()),
+                None => fs::metadata(entry.content_path()),
+            };
+            let record = meta.and_then(|meta| {
+                Ok(ManifestEntry {
+                    path: archive_name(&entry.path, &self.options),
+                    size: meta.len(),
+                    mtime: format_time(meta.modified().ok()),
+                    digest: hash_file(entry.content_path(), algorithm)?,
+                })
+            });
+            match record {
+                Ok(record) => files.push(record),
+                Err(e) => self.report("hashing", &entry.path, &e),
+            }
+        }
+        Ok(Manifest::new(algorithm, files))
+    }
+
+    fn tally(&self, content: bool) -> Result<Summary> {
+        let start = Instant::now();
+        self.dirs.store(0, Ordering::Relaxed);
+
+        let mut files = 0;
+        let mut bytes = 0;
+        let mut tokens = 0;
+        let mut content_bytes = 0;
+        let mut binary_files = 0;
+        let mut extensions = BTreeMap::new();
+        for entry in self.entries()? {
+            let size = entry_size(&entry);
+            let extension = entry
+                .path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+
+            let totals: &mut ExtensionCount = extensions.entry(extension).or_default();
+            totals.files += 1;
+            totals.bytes += size;
+            files += 1;
+            bytes += size;
+
+            if let Some(marker) = entry.content_marker().filter(|_| content) {
+                content_bytes += marker.len() as u64;
+                tokens += tokens::tokens_for(marker.len() as u64);
+            } else if content {
+                match tokens::emitted_bytes(
+                    entry.content_path(),
+                    entry.meta.as_ref().filter(|_| entry.object.is_none()),
+                    &self.options,
+                ) {
+                    Ok(Some(emitted)) => {
+                        content_bytes += emitted;
+                        tokens += tokens::tokens_for(emitted);
+                    }
+                    Ok(None) => binary_files += 1,
+                    Err(e) => self.report("reading", &entry.path, &e),
+                }
+            } else {
+                tokens += entry.tokens.unwrap_or(0);
+            }
+        }
+
+        Ok(Summary {
+            files,
+            dirs: self.dirs.load(Ordering::Relaxed),
+            loops: self.walked.loops.load(Ordering::Relaxed),
+            bytes: Some(bytes),
+            extensions,
+            content_bytes: content.then_some(content_bytes),
+            binary_files: content.then_some(binary_files),
+            tokens: (content || self.options.count_tokens).then_some(tokens),
+            stopped: self.stopped(),
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Per-file problems go to the `log` facade (the CLI prints them on stderr).
+    pub(crate) fn warn(&self, message: &str) {
+        if !self.options.quiet {
+            log::warn!("{message}");
+        }
+    }
+
+    /// A failed `operation` ("reading", "hashing", ...) on `path`: warned
+    /// about and recorded in `errors`.
+    pub(crate) fn report(&self, operation: &str, path: &Path, error: &io::Error) {
+        self.warn(&format!("Error {operation} {}: {error}", path.display()));
+        self.record_error(Some(path), operation, &io_kind(error), &error.to_string());
+    }
+
+    fn report_walk(&self, error: &ignore::Error) {
+        // The walker's own check: a link to one of the directory's ancestors
+        if let Some((child, ancestor)) = walk_loop(error) {
+            self.walked.loops.fetch_add(1, Ordering::Relaxed);
+            self.warn(&format!(
+                "Skipped symlink loop: {} points to its ancestor {}",
+                child.display(),
+                ancestor.display()
+            ));
+            return;
+        }
+        self.warn(&format!("Traversal Error: {error}"));
+        let (path, kind) = walk_error_details(error);
+        self.record_error(path, "walking", &kind, &error.to_string());
+    }
+
+    /// Counts the error and adds a record to the `errors` file, if there is one.
+    pub(crate) fn record_error(
+        &self,
+        path: Option<&Path>,
+        operation: &str,
+        kind: &str,
+        message: &str,
+    ) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        if let Some(log) = &self.error_log {
+            log.record(path, operation, kind, message);
+        }
+    }
+
+    fn stopped(&self) -> Option<Limit> {
+        *self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// --incremental: `entry` is being written, so the next run can skip it
+    /// while it stays the same.
+    fn record_written(&self, entry: &Entry) {
+        if self.options.incremental.is_none() {
+            return;
+        }
+        if let Some(meta) = &entry.meta {
+            self.seen
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(archive_name(&entry.path, &self.options), Stamp::of(meta));
+        }
+    }
+
+    fn resume(&self) -> Option<Checkpoint> {
+        self.resume
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The first limit that stopped the run wins (`None` resets): the walk
+    /// runs ahead of the writer, so it may reach its own limit later.
+    fn set_stopped(&self, limit: Option<Limit>) {
+        let mut stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        if limit.is_none() || stopped.is_none() {
+            *stopped = limit;
+        }
+    }
+
+    /// Setup Walker (The Traversal Engine)
+    fn walk_builder(&self) -> WalkBuilder {
+        let options = &self.options;
+        let mut builder = WalkBuilder::new(&options.base_path);
+        builder
+            .standard_filters(!options.no_default_excludes)
+            .hidden(!options.include_hidden)
+            .follow_links(options.follow_symlinks)
+            .same_file_system(options.same_file_system)
+            .max_depth(options.depth)
+            .threads(options.threads); // Single thread keeps the walker's natural order
+
+        if !options.no_default_excludes {
+            builder.add_custom_ignore_filename(IGNORE_FILENAME);
+        }
+
+        if let Some(overrides) = &self.overrides {
+            builder.overrides(overrides.clone());
+        }
+        if options.checkpoint.is_some() {
+            builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+
+        let exclude_file = self.exclude_file.clone();
+        let resume_after = self.resume().map(|c| c.relative_path());
+        let walked = options.follow_symlinks.then(|| {
+            self.walked.reset(&options.base_path);
+            (Arc::clone(&self.walked), options.quiet)
+        });
+        let prefetch = self.prefetch();
+        let skipped = self.skipped_paths();
+        if exclude_file.is_some()
+            || resume_after.is_some()
+            || walked.is_some()
+            || prefetch.is_some()
+            || !skipped.is_empty()
+        {
+            let base = options.base_path.clone();
+            // Pruning here keeps excluded (or already written) directories from being walked at all
+            builder.filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|f| f.is_dir());
+                if let Some((walked, quiet)) = &walked
+                    && is_dir
+                    && !walked.enter(entry.path(), entry.path_is_symlink(), *quiet)
+                {
+                    return false;
+                }
+                if let Some(exclude_file) = &exclude_file
+                    && exclude_file.matched(entry.path(), is_dir).is_ignore()
+                {
+                    return false;
+                }
+                if skipped.iter().any(|path| path == entry.path()) {
+                    return false;
+                }
+                // Name order: whatever sorts before the resume point and is not
+                // one of its ancestors was completely written already
+                let keep = resume_after.as_ref().is_none_or(|resume_after| {
+                    let relative = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+                    relative > resume_after.as_path() || resume_after.starts_with(relative)
+                });
+                // Only directories the walk will enter are read ahead
+                if let Some(prefetch) = &prefetch
+                    && keep
+                    && is_dir
+                {
+                    prefetch.directory(entry.path());
+                }
+                keep
+            });
+        }
+
+        builder
+    }
+
+    /// The `skip_files` inside the walked tree, spelled the way the walker
+    /// reaches them (under `base_path`), so entries compare without a
+    /// `canonicalize` each. The files need not exist yet.
+    fn skipped_paths(&self) -> Vec<PathBuf> {
+        let options = &self.options;
+        if options.skip_files.is_empty() {
+            return Vec::new();
+        }
+        let Ok(base) = fs::canonicalize(&options.base_path) else {
+            return Vec::new();
+        };
+        options
+            .skip_files
+            .iter()
+            .filter_map(|path| {
+                let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+                let parent = fs::canonicalize(parent.unwrap_or(Path::new("."))).ok()?;
+                let path = parent.join(path.file_name()?);
+                let relative = path.strip_prefix(&base).ok()?;
+                Some(options.base_path.join(relative))
+            })
+            .collect()
+    }
+
+    /// `backend = io-uring`: the read-ahead for one walk, already fed the
+    /// base path. `None` with the standard backend, or (after a warning)
+    /// where io_uring cannot be set up.
+    fn prefetch(&self) -> Option<Prefetch> {
+        let options = &self.options;
+        if options.backend != Backend::IoUring {
+            return None;
+        }
+        let contents = options.read_content
+            || options.pattern.is_some()
+            || !self.filters.content_regex.is_empty();
+        match Prefetch::start(contents) {
+            Ok(prefetch) => {
+                prefetch.directory(&options.base_path);
+                Some(prefetch)
+            }
+            Err(e) => {
+                self.warn(&format!(
+                    "io_uring is unavailable ({e}); using the standard backend"
+                ));
+                None
+            }
+        }
+    }
+
+    /// --exclude-from check for paths the walker did not prune: the path
+    /// itself or any parent directory below the base path.
+    fn excluded_by_file(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(exclude_file) = &self.exclude_file else {
+            return false;
+        };
+        let relative = path.strip_prefix(&self.options.base_path).unwrap_or(path);
+        relative
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| exclude_file.matched(p, is_dir || p != relative).is_ignore())
+    }
+
+    /// `archives`: the matched members of a zip / tar file, evaluated in
+    /// place of the file itself. `None` for anything that is not an archive.
+    /// Only members whose virtual path passes the path filters are kept, and
+    /// their content is unpacked up front only for filters that read it.
+    fn expand(&self, entry: &impl EntrySource) -> Option<Vec<Entry>> {
+        let scratch = self.scratch.as_ref()?;
+        let path = entry.path();
+        if entry.is_dir() || entry.is_special() || entry.depth() == 0 {
+            return None;
+        }
+        let kind = archive_kind(path)?;
+        let depth = |name: &str| entry.depth() + name.split('/').count();
+        let want = |name: &str| {
+            let virtual_path = member_path(path, name);
+            self.options.depth.is_none_or(|max| depth(name) <= max)
+                && self
+                    .overrides
+                    .as_ref()
+                    .is_none_or(|o| !o.matched(&virtual_path, false).is_ignore())
+                && !self.excluded_by_file(&virtual_path, false)
+                && self.filters.should_process(&virtual_path, false)
+        };
+        let unpacked = match scratch.open(path, kind, &want) {
+            Ok(unpacked) => Arc::new(unpacked),
+            Err(e) => {
+                self.report("unpacking", path, &e);
+                return Some(Vec::new());
+            }
+        };
+        log::debug!(
+            "{}: {} members matched",
+            path.display(),
+            unpacked.members.len()
+        );
+        if self.filters_read_content() {
+            unpacked.unpack();
+        }
+        Some(
+            unpacked
+                .members
+                .iter()
+                .filter_map(|member| {
+                    let mut entry = self.evaluate(&MemberSource {
+                        path: member_path(path, &member.name),
+                        depth: depth(&member.name),
+                        stat: member.stat.clone(),
+                        data: member.data.clone(),
+                    })?;
+                    entry.member = Some(Arc::clone(&unpacked));
+                    Some(entry)
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether `evaluate` reads the content of files, not only their
+    /// metadata.
+    fn filters_read_content(&self) -> bool {
+        let options = &self.options;
+        self.filters.pattern.is_some()
+            || !self.filters.content_regex.is_empty()
+            || !options.mime.is_empty()
+            || options.frontmatter
+            || !options.frontmatter_filters.is_empty()
+            || options.binary == BinaryMode::Skip
+            || options.lfs == LfsMode::Skip
+            || (options.lfs != LfsMode::Keep && options.read_content)
+            || options.skip_generated
+            || options.count_tokens
+    }
+
+    /// Runs the full filter chain for a walker entry or listed path
+    /// (shared by sequential, parallel and --files-from runs).
+    fn evaluate(&self, entry: &impl EntrySource) -> Option<Entry> {
+        self.scanned.fetch_add(1, Ordering::Relaxed);
+        let is_dir = entry.is_dir();
+        if is_dir {
+            self.dirs.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Skip root itself (and anything above min_depth)
+        if entry.depth() == 0
+            || self
+                .options
+                .min_depth
+                .is_some_and(|min| entry.depth() < min)
+        {
+            return None;
+        }
+
+        let path = entry.path();
+        let source = entry.source();
+        if self.options.symlinks == SymlinkMode::Skip && entry.is_link() {
+            return None;
+        }
+
+        // Entry kind: directories and special files are only emitted when asked for
+        let special = !is_dir && entry.is_special();
+        let kind = if special {
+            EntryType::Special
+        } else if entry.is_symlink() && !(self.options.follow_symlinks && path.exists()) {
+            EntryType::Symlink
+        } else if is_dir {
+            EntryType::Dir
+        } else {
+            EntryType::File
+        };
+        let wanted = if self.options.types.is_empty() {
+            !is_dir && !special
+        } else {
+            self.options.types.contains(&kind)
+        };
+
+        // Apply Filters
+        if !wanted || !self.filters.should_process(path, is_dir) {
+            return None;
+        }
+        if self.git.as_ref().is_some_and(|git| !git.contains(path)) {
+            return None;
+        }
+        if self
+            .export_ignore
+            .as_ref()
+            .is_some_and(|ignore| ignore.contains(path))
+        {
+            return None;
+        }
+
+        // Metadata filters (one stat, reused for output below)
+        let mut meta = None;
+        if self.filters.needs_metadata() {
+            let m = match entry.metadata() {
+                Ok(m) => m,
+                Err(e) => {
+                    self.report("reading metadata", path, &e);
+                    return None;
+                }
+            };
+            if !self.filters.matches_metadata(&m) {
+                return None;
+            }
+            meta = Some(m);
+        }
+        if !self.filters.exprs.is_empty() {
+            let relative = archive_name(path, &self.options);
+            let candidate = Candidate {
+                path: &relative,
+                depth: entry.depth(),
+                kind,
+                meta: meta.as_ref(),
+            };
+            if !self
+                .filters
+                .exprs
+                .iter()
+                .all(|expr| expr.matches(&candidate))
+            {
+                return None;
+            }
+        }
+
+        // Unchanged since the previous --incremental run: carried over, not emitted
+        if self.options.incremental.is_some() {
+            if meta.is_none() {
+                meta = entry.metadata().ok();
+            }
+            if let Some(m) = &meta {
+                let name = archive_name(path, &self.options);
+                let stamp = Stamp::of(m);
+                let previous = self.previous.lock().unwrap_or_else(PoisonError::into_inner);
+                if previous.unchanged(&name, stamp) {
+                    drop(previous);
+                    self.seen
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(name, stamp);
+                    return None;
+                }
+            }
+        }
+
+        // Directories have no content to match, special files are never read
+        if (is_dir || special)
+            && (!self.filters.content_regex.is_empty()
+                || self.filters.pattern.is_some()
+                || !self.options.mime.is_empty()
+                || !self.options.frontmatter_filters.is_empty())
+        {
+            return None;
+        }
+
+        if !self.options.mime.is_empty() {
+            match detect_mime(source, self.options.binary_check) {
+                Ok(mime) if self.options.mime.iter().any(|p| mime_matches(p, mime)) => {}
+                Ok(_) => return None,
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        }
+
+        // Content filters run after the cheap ones
+        if !self.filters.content_regex.is_empty() {
+            match content_matches(
+                source,
+                &self.filters.content_regex,
+                self.filters.regex_mode,
+                self.options.max_bytes,
+                self.options.binary_check,
+            ) {
+                Ok(Some(found)) if found != self.filters.regex_inv => {}
+                Ok(_) => return None,
+                Err(e) => {
+                    self.report("searching", path, &e);
+                    return None;
+                }
+            }
+        }
+
+        let front_matter = if (self.options.frontmatter
+            || !self.options.frontmatter_filters.is_empty())
+            && !is_dir
+            && !special
+        {
+            match read_front_matter(source) {
+                Ok(fields) => fields,
+                // A broken block is reported, and filtered on as if absent
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    self.warn(&format!("Invalid front matter in {}: {e}", path.display()));
+                    None
+                }
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+        if !self
+            .options
+            .frontmatter_filters
+            .iter()
+            .all(|filter| filter.matches(front_matter.as_ref()))
+        {
+            return None;
+        }
+
+        if self.options.binary == BinaryMode::Skip
+            && !is_dir
+            && !special
+            && !extracts(source, BinaryMode::Skip)
+        {
+            match is_binary_file(source, self.options.binary_check) {
+                Ok(false) => {}
+                // Images are described by --image-meta rather than dropped
+                Ok(true) if self.options.image_meta && is_image(source).unwrap_or(false) => {}
+                Ok(true) => return None,
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        }
+
+        // Pointer files are told apart before their text is taken as content
+        let mut lfs = None;
+        let mut object = (source != path).then(|| source.to_path_buf());
+        let check_lfs = match self.options.lfs {
+            LfsMode::Keep => false,
+            LfsMode::Skip => true,
+            LfsMode::Flag | LfsMode::Smudge => self.options.read_content,
+        };
+        if check_lfs && !is_dir && !special {
+            match read_pointer(source) {
+                Ok(Some(_)) if self.options.lfs == LfsMode::Skip => return None,
+                // Only pointers checked out in a repository can be smudged
+                Ok(Some(pointer)) if self.options.lfs == LfsMode::Smudge && object.is_none() => {
+                    object = smudge(path, &pointer);
+                    if object.is_none() {
+                        self.warn(&format!(
+                            "Cannot fetch the Git LFS object of {} (is git-lfs installed?)",
+                            path.display()
+                        ));
+                        lfs = Some(pointer);
+                    }
+                }
+                Ok(pointer) => lfs = pointer,
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        }
+
+        let generated = if self.options.skip_generated && lfs.is_none() && !is_dir && !special {
+            let content = object.as_deref().unwrap_or(source);
+            match detect_generated(path, content, self.options.binary_check) {
+                Ok(generated) => generated,
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+
+        let matches = match &self.filters.pattern {
+            Some(pattern) => match search_file_content(
+                source,
+                pattern,
+                self.options.show_matches,
+                self.options.binary_check,
+            ) {
+                Ok(Some(matches)) => matches,
+                Ok(None) => return None,
+                Err(e) => {
+                    self.report("searching", path, &e);
+                    return None;
+                }
+            },
+            None => Vec::new(),
+        };
+
+        // Only stat when metadata was requested (structured formats always carry size)
+        if meta.is_none()
+            && (!self.options.metadata.is_empty()
+                || self.options.format != OutputFormat::Text
+                || self.options.count_tokens
+                || matches!(self.options.sort, SortKey::Size | SortKey::Mtime))
+        {
+            meta = entry.metadata().ok();
+        }
+
+        let tokens = if let Some(generated) = generated.filter(|_| self.options.count_tokens) {
+            Some(tokens::tokens_for(generated.marker().len() as u64))
+        } else if self.options.count_tokens && !is_dir && !special {
+            // Smudged files and archive members count the content they show
+            let (source, source_meta) = match &object {
+                Some(object) => (object.as_path(), None),
+                None => (path, meta.as_ref()),
+            };
+            match estimate_tokens(source, source_meta, &self.options) {
+                Ok(n) => Some(n),
+                Err(e) => {
+                    self.report("reading", path, &e);
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+
+        let path = match self.options.symlinks {
+            // Archive members are never links
+            SymlinkMode::Target if source == path => self.resolved_path(path),
+            SymlinkMode::Target => path.to_path_buf(),
+            SymlinkMode::Report => {
+                if kind == EntryType::Symlink && !path.exists() {
+                    self.warn(&format!("Broken symlink: {}", path.display()));
+                }
+                path.to_path_buf()
+            }
+            SymlinkMode::Skip => path.to_path_buf(),
+        };
+        let git = self.git_log.as_ref().and_then(|log| log.commit(&path));
+        Some(Entry {
+            path,
+            depth: entry.depth(),
+            meta,
+            matches,
+            tokens,
+            aliases: Vec::new(),
+            git,
+            lfs,
+            object,
+            front_matter,
+            generated,
+            member: None,
+        })
+    }
+
+    /// `symlinks = target`: where a path leads once every link on the way
+    /// is resolved, under the base path when it stays inside it. Broken
+    /// links keep their own path.
+    fn resolved_path(&self, path: &Path) -> PathBuf {
+        let Ok(target) = paths::canonicalize(path) else {
+            self.warn(&format!("Broken symlink: {}", path.display()));
+            return path.to_path_buf();
+        };
+        match paths::canonicalize(&self.options.base_path)
+            .ok()
+            .and_then(|base| target.strip_prefix(base).ok().map(Path::to_path_buf))
+        {
+            Some(relative) => self.options.base_path.join(relative),
+            None => target,
+        }
+    }
+
+    /// Orders collected entries by the sort key (ties broken by path).
+    /// Parallel walks have no natural order, so they fall back to path order.
+    fn sort_entries(&self, entries: &mut [Entry], parallel: bool) {
+        let mtime = |e: &Entry| e.meta.as_ref().and_then(|m| m.modified().ok());
+        let size = |e: &Entry| e.meta.as_ref().map_or(0, Metadata::len);
+
+        match self.options.sort {
+            SortKey::None if !parallel => {}
+            SortKey::None | SortKey::Name => entries.sort_unstable_by(|a, b| a.path.cmp(&b.path)),
+            SortKey::Size => {
+                entries.sort_unstable_by(|a, b| size(a).cmp(&size(b)).then(a.path.cmp(&b.path)));
+            }
+            SortKey::Mtime => {
+                entries.sort_unstable_by(|a, b| mtime(a).cmp(&mtime(b)).then(a.path.cmp(&b.path)));
+            }
+        }
+
+        if self.options.reverse {
+            entries.reverse();
+        }
+    }
+
+    /// Walks the tree with `WalkParallel` and returns every matching entry.
+    fn collect_parallel(&self, builder: &WalkBuilder) -> Vec<Entry> {
+        let (tx, rx) = mpsc::channel();
+
+        builder.build_parallel().run(|| {
+            let tx = tx.clone();
+            Box::new(move |result| {
+                match result {
+                    Ok(entry) => {
+                        // Receiver lives until run() returns
+                        if let Some(members) = self.expand(&entry) {
+                            for member in members {
+                                let _ = tx.send(member);
+                            }
+                        } else if let Some(entry) = self.evaluate(&entry) {
+                            let _ = tx.send(entry);
+                        }
+                    }
+                    Err(err) => self.report_walk(&err),
+                }
+                WalkState::Continue
+            })
+        });
+        drop(tx);
+
+        rx.into_iter().collect()
+    }
+
+    /// Renders entries on worker threads into private buffers and writes them
+    /// back in index order through a reordering map, so output stays deterministic.
+    fn write_parallel(
+        &self,
+        entries: &[Entry],
+        writer: &mut dyn Write,
+        output_left: &dyn Fn() -> bool,
+    ) -> io::Result<usize> {
+        let threads = self.options.threads;
+        let next = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::sync_channel::<(usize, Vec<u8>)>(threads * 4);
+
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let tx = tx.clone();
+                let next = &next;
+                scope.spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(entry) = entries.get(index) else {
+                            break;
+                        };
+
+                        let mut buffer = Vec::new();
+                        if let Err(e) = process_file(
+                            entry,
+                            &self.options,
+                            &self.templates,
+                            self.redactor.as_ref(),
+                            index,
+                            &mut buffer,
+                            None,
+                        ) {
+                            self.report("processing", &entry.path, &e);
+                        }
+
+                        // Writer side hung up (e.g. BrokenPipe): stop working
+                        if tx.send((index, buffer)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut pending = BTreeMap::new();
+            let mut next_out = 0;
+            for (index, buffer) in rx {
+                pending.insert(index, buffer);
+                while let Some(buffer) = pending.remove(&next_out) {
+                    // Dropping rx makes the workers' next send fail, so they stop too
+                    if !output_left() {
+                        return Ok(next_out);
+                    }
+                    writer.write_all(&buffer)?;
+                    next_out += 1;
+                }
+            }
+            Ok(next_out)
+        })
+    }
+}
+
+/// Iterator over the entries of a [`Collector`].
+#[derive(Debug)]
+pub struct Entries<'a> {
+    collector: &'a Collector,
+    inner: EntriesInner,
+    /// Token budget left for the `stop` strategy.
+    remaining_tokens: Option<u64>,
+    /// Entries left before `max_files` stops the run.
+    remaining_files: Option<usize>,
+    /// Relative path of the last entry a resumed run already wrote.
+    resume_after: Option<PathBuf>,
+    /// Matched members of the last archive opened with `archives`.
+    pending: VecDeque<Entry>,
+}
+
+enum EntriesInner {
+    Sequential(Box<Walk>),
+    Sorted(std::vec::IntoIter<Entry>),
+    Listed(std::vec::IntoIter<ListedPath>),
+}
+
+impl std::fmt::Debug for EntriesInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sequential(_) => f.write_str("Sequential"),
+            Self::Sorted(iter) => write!(f, "Sorted({} left)", iter.len()),
+            Self::Listed(iter) => write!(f, "Listed({} left)", iter.len()),
+        }
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        let mut entry = self.next_unbudgeted()?;
+        // Only the ancestors of the resume point survive the walk's pruning,
+        // and once past it, everything else is new
+        if let Some(resume_after) = self.resume_after.take() {
+            let base = &self.collector.options.base_path;
+            while entry.path.strip_prefix(base).unwrap_or(&entry.path) <= resume_after.as_path() {
+                entry = self.next_unbudgeted()?;
+            }
+        }
+
+        if let Some(remaining) = self.remaining_files {
+            // Only reported once another match shows the limit actually cut the run
+            if remaining == 0 {
+                let limit = self.collector.options.max_files.unwrap_or(0);
+                self.collector.set_stopped(Some(Limit::Files(limit)));
+                self.inner = EntriesInner::Sorted(Vec::new().into_iter());
+                return None;
+            }
+            self.remaining_files = Some(remaining - 1);
+        }
+
+        if let Some(remaining) = self.remaining_tokens {
+            let tokens = entry.tokens.unwrap_or(0);
+            if tokens > remaining {
+                self.collector.warn(&format!(
+                    "Token budget reached at {}; remaining files skipped",
+                    display_path(&entry.path, &self.collector.options).display()
+                ));
+                self.inner = EntriesInner::Sorted(Vec::new().into_iter());
+                return None;
+            }
+            self.remaining_tokens = Some(remaining - tokens);
+        }
+
+        self.collector.matched.fetch_add(1, Ordering::Relaxed);
+        log::trace!("Matched {}", entry.path.display());
+        Some(entry)
+    }
+}
+
+impl Entries<'_> {
+    fn next_unbudgeted(&mut self) -> Option<Entry> {
+        if let Some(entry) = self.pending.pop_front() {
+            return Some(entry);
+        }
+        match &mut self.inner {
+            EntriesInner::Sorted(iter) => iter.next(),
+            EntriesInner::Listed(paths) => loop {
+                let listed = paths.next()?;
+                if !listed.path.exists() {
+                    let error = io::Error::new(io::ErrorKind::NotFound, "listed path not found");
+                    self.collector.report("listing", &listed.path, &error);
+                    continue;
+                }
+                // The walker applies overrides itself; listed paths need it here
+                if let Some(overrides) = &self.collector.overrides
+                    && overrides.matched(&listed.path, listed.is_dir()).is_ignore()
+                {
+                    continue;
+                }
+                if self
+                    .collector
+                    .excluded_by_file(&listed.path, listed.is_dir())
+                {
+                    continue;
+                }
+                if let Some(members) = self.collector.expand(&listed) {
+                    self.pending.extend(members);
+                    match self.pending.pop_front() {
+                        Some(entry) => return Some(entry),
+                        None => continue,
+                    }
+                }
+                if let Some(entry) = self.collector.evaluate(&listed) {
+                    return Some(entry);
+                }
+            },
+            EntriesInner::Sequential(walk) => loop {
+                match walk.next()? {
+                    Ok(entry) => {
+                        if let Some(members) = self.collector.expand(&entry) {
+                            self.pending.extend(members);
+                            match self.pending.pop_front() {
+                                Some(entry) => return Some(entry),
+                                None => continue,
+                            }
+                        }
+                        if let Some(entry) = self.collector.evaluate(&entry) {
+                            return Some(entry);
+                        }
+                    }
+                    Err(err) => self.collector.report_walk(&err),
+                }
+            },
+        }
+    }
+}
+
+/// What `evaluate` needs from a candidate: a walker entry or a listed path.
+trait EntrySource {
+    fn path(&self) -> &Path;
+    fn depth(&self) -> usize;
+    fn is_dir(&self) -> bool;
+    /// Symlink not resolved by the walker (`follow_symlinks` off or broken).
+    fn is_symlink(&self) -> bool;
+    /// The path itself is a symlink, followed or not.
+    fn is_link(&self) -> bool;
+    /// A FIFO, socket or device file, or a link to one.
+    fn is_special(&self) -> bool;
+    fn metadata(&self) -> io::Result<Metadata>;
+    /// Where the content is read from (an extracted archive member).
+    fn source(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl EntrySource for DirEntry {
+    fn path(&self) -> &Path {
+        self.path()
+    }
+
+    fn depth(&self) -> usize {
+        self.depth()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type().is_some_and(|f| f.is_dir())
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.file_type().is_some_and(|f| f.is_symlink())
+    }
+
+    fn is_link(&self) -> bool {
+        self.path_is_symlink()
+    }
+
+    fn is_special(&self) -> bool {
+        match self.file_type() {
+            Some(f) if f.is_symlink() => {
+                fs::metadata(self.path()).is_ok_and(|m| special_file(m.file_type()))
+            }
+            Some(f) => special_file(f),
+            None => false,
+        }
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        self.metadata().map_err(io::Error::other)
+    }
+}
+
+/// A path read from `--files-from`; it bypasses the walker (and ignore files).
+struct ListedPath {
+    path: PathBuf,
+    depth: usize,
+}
+
+impl ListedPath {
+    /// A path as listed, one level deep per component.
+    fn new(path: PathBuf) -> Self {
+        let depth = path.components().count();
+        Self { path, depth }
+    }
+}
+
+impl EntrySource for ListedPath {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn is_dir(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    fn is_symlink(&self) -> bool {
+        fs::symlink_metadata(&self.path).is_ok_and(|m| m.is_symlink())
+    }
+
+    fn is_link(&self) -> bool {
+        self.is_symlink()
+    }
+
+    fn is_special(&self) -> bool {
+        fs::metadata(&self.path).is_ok_and(|m| special_file(m.file_type()))
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        fs::metadata(&self.path)
+    }
+}
+
+/// FIFOs, sockets and devices: opening one for reading can block forever.
+#[cfg(unix)]
+fn special_file(file_type: fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    file_type.is_fifo()
+        || file_type.is_socket()
+        || file_type.is_block_device()
+        || file_type.is_char_device()
+}
+
+#[cfg(not(unix))]
+fn special_file(_file_type: fs::FileType) -> bool {
+    false
+}
+
+/// Compiles --glob and --exclude into a single override matcher.
+fn build_overrides(options: &CollectOptions) -> Result<Option<Override>> {
+    let excludes = options.exclude.as_deref().unwrap_or_default();
+    if excludes.is_empty() && options.globs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut override_builder = OverrideBuilder::new(&options.base_path);
+
+    // Globs use override syntax directly: "x" includes, "!x" excludes
+    for glob in &options.globs {
+        override_builder.add(glob)?;
+    }
+
+    for exc in excludes {
+        // ! negates the ignore, meaning "include", but in .gitignore syntax
+        // ! matches mean exclude if using ignore builder carefully.
+        // But here standard convention for cli override is just passed patterns.
+        // Let's assume standard gitignore logic: "foo" ignores foo.
+        override_builder.add(&format!("!{}", exc))?;
+    }
+
+    Ok(Some(override_builder.build()?))
+}
+
+/// Loads every --exclude-from file into one gitignore matcher rooted at the
+/// base path (comments, `!` negation and trailing `/` work as in .gitignore).
+fn build_exclude_file(options: &CollectOptions) -> Result<Option<Gitignore>> {
+    if options.exclude_from.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GitignoreBuilder::new(&options.base_path);
+    for file in &options.exclude_from {
+        if let Some(e) = builder.add(file) {
+            bail!("Failed to read exclude file: {e}");
+        }
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Passes writes through while counting the bytes (for `max_output_bytes`).
+struct CountingWriter<'a> {
+    inner: &'a mut dyn Write,
+    written: &'a AtomicU64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Paths changed since `reference`, filtered like the walk would (depth
+/// bounds, hidden files) so only the rest of the filter chain remains.
+fn changed_since(options: &CollectOptions, reference: &str) -> Result<Vec<PathBuf>> {
+    let show_hidden = options.include_hidden || options.no_default_excludes;
+    let mut paths: Vec<PathBuf> = changed_paths(&options.base_path, reference)?
+        .into_iter()
+        .filter(|relative| {
+            let depth = relative.components().count();
+            options.depth.is_none_or(|max| depth <= max)
+                && (show_hidden
+                    || !relative
+                        .components()
+                        .any(|c| c.as_os_str().to_string_lossy().starts_with('.')))
+        })
+        .collect();
+    paths.sort();
+    log::debug!("{} paths changed since {reference}", paths.len());
+    Ok(paths)
+}
+
+/// A file inside an archive opened with `archives`, under its virtual path.
+struct MemberSource {
+    path: PathBuf,
+    /// Stand-in with the member's metadata, and where its content goes.
+    stat: PathBuf,
+    data: PathBuf,
+    depth: usize,
+}
+
+impl EntrySource for MemberSource {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn is_dir(&self) -> bool {
+        false
+    }
+
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
+    fn is_link(&self) -> bool {
+        false
+    }
+
+    fn is_special(&self) -> bool {
+        false
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        fs::metadata(&self.stat)
+    }
+
+    fn source(&self) -> &Path {
+        &self.data
+    }
+}
+
+/// `--files-from` and interactive selections, depth counted as listed.
+fn listed(paths: Vec<PathBuf>) -> std::vec::IntoIter<ListedPath> {
+    paths
+        .into_iter()
+        .map(ListedPath::new)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Reads newline separated paths from a file, or stdin for `-`.
+fn read_path_list(source: &Path) -> io::Result<Vec<PathBuf>> {
+    let reader: Box<dyn BufRead> = if source == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(source)?))
+    };
+
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    Ok(paths)
+}
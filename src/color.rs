@@ -0,0 +1,131 @@
+//! `--color`: listed paths colored by file type and extension, following `LS_COLORS`.
+
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// GNU `dircolors` defaults for the types, used when `LS_COLORS` is unset.
+const DEFAULT_LS_COLORS: &str = "di=01;34:ln=01;36:so=01;35:pi=40;33:ex=01;32:\
+    bd=40;33;01:cd=40;33;01:or=40;31;01";
+
+/// A parsed `LS_COLORS` value: SGR codes per file type (`di`, `ln`, `ex`, ...)
+/// and per name suffix (`*.rs`).
+#[derive(Debug, Default)]
+struct LsColors {
+    types: Vec<(String, String)>,
+    /// Lowercased suffixes, longest first so `*.tar.gz` wins over `*.gz`.
+    suffixes: Vec<(String, String)>,
+}
+
+impl LsColors {
+    fn parse(spec: &str) -> Self {
+        let mut colors = Self::default();
+        for (key, sgr) in spec.split(':').filter_map(|item| item.split_once('=')) {
+            if sgr.is_empty() || !sgr.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                continue;
+            }
+            match key.strip_prefix('*') {
+                Some(suffix) => colors
+                    .suffixes
+                    .push((suffix.to_lowercase(), sgr.to_string())),
+                None => colors.types.push((key.to_string(), sgr.to_string())),
+            }
+        }
+        colors
+            .suffixes
+            .sort_by_key(|(s, _)| std::cmp::Reverse(s.len()));
+        colors
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, sgr)| sgr.as_str())
+    }
+
+    /// Same precedence as `ls`: special types first, then executables, then
+    /// the name suffix, then `fi`.
+    fn style(&self, path: &Path, meta: Option<&Metadata>) -> Option<&str> {
+        let Some(meta) = meta else {
+            return self.get("or");
+        };
+        let file_type = meta.file_type();
+        if file_type.is_symlink() {
+            let key = if fs::metadata(path).is_ok() {
+                "ln"
+            } else {
+                "or"
+            };
+            return self.get(key).or_else(|| self.get("ln"));
+        }
+        if file_type.is_dir() {
+            return self.get("di");
+        }
+        if let Some(key) = special_type(meta) {
+            return self.get(key);
+        }
+        if is_executable(meta)
+            && let Some(sgr) = self.get("ex")
+        {
+            return Some(sgr);
+        }
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        self.suffixes
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, sgr)| sgr.as_str())
+            .or_else(|| self.get("fi"))
+    }
+}
+
+#[cfg(unix)]
+fn special_type(meta: &Metadata) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+    let file_type = meta.file_type();
+    if file_type.is_fifo() {
+        Some("pi")
+    } else if file_type.is_socket() {
+        Some("so")
+    } else if file_type.is_block_device() {
+        Some("bd")
+    } else if file_type.is_char_device() {
+        Some("cd")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_type(_meta: &Metadata) -> Option<&'static str> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &Metadata) -> bool {
+    false
+}
+
+fn ls_colors() -> &'static LsColors {
+    static COLORS: OnceLock<LsColors> = OnceLock::new();
+    COLORS.get_or_init(|| match std::env::var("LS_COLORS") {
+        Ok(spec) if !spec.is_empty() => LsColors::parse(&spec),
+        _ => LsColors::parse(DEFAULT_LS_COLORS),
+    })
+}
+
+/// `display` wrapped in the escape codes `LS_COLORS` assigns to `path`.
+/// Uncolored types are returned as is.
+pub(crate) fn paint_path(path: &Path, display: &str) -> String {
+    let meta = fs::symlink_metadata(path).ok();
+    match ls_colors().style(path, meta.as_ref()) {
+        Some(sgr) => format!("\x1b[{sgr}m{display}\x1b[0m"),
+        None => display.to_string(),
+    }
+}
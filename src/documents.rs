@@ -0,0 +1,326 @@
+//! Plain text out of PDF, Word (`.docx`) and Excel (`.xlsx`) files, so
+//! `--content` shows what they say instead of the binary placeholder. Only
+//! built with the `documents` cargo feature.
+
+use crate::options::BinaryMode;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Document {
+    Pdf,
+    Docx,
+    Xlsx,
+}
+
+fn document_kind(path: &Path) -> Option<Document> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "pdf" => Some(Document::Pdf),
+        "docx" => Some(Document::Docx),
+        "xlsx" => Some(Document::Xlsx),
+        _ => None,
+    }
+}
+
+/// Whether `path` is a document this build reads as text under `binary`:
+/// only where the file would be suppressed otherwise.
+pub(crate) fn extracts(path: &Path, binary: BinaryMode) -> bool {
+    cfg!(feature = "documents")
+        && matches!(binary, BinaryMode::Suppress | BinaryMode::Skip)
+        && document_kind(path).is_some()
+}
+
+/// The text `--content` shows for a document; `None` when `extracts` says
+/// no.
+pub(crate) fn document_text(path: &Path, binary: BinaryMode) -> Option<io::Result<String>> {
+    if !extracts(path, binary) {
+        return None;
+    }
+    extract_text(path)
+}
+
+/// Cells of a spreadsheet are tab separated, one row per line, under a
+/// `# <sheet>` heading per sheet.
+#[cfg(feature = "documents")]
+fn extract_text(path: &Path) -> Option<io::Result<String>> {
+    let text = match document_kind(path)? {
+        Document::Pdf => extract::pdf(path),
+        Document::Docx => extract::docx(path),
+        Document::Xlsx => extract::xlsx(path),
+    };
+    Some(text)
+}
+
+#[cfg(not(feature = "documents"))]
+fn extract_text(_path: &Path) -> Option<io::Result<String>> {
+    None
+}
+
+#[cfg(feature = "documents")]
+mod extract {
+    use quick_xml::Reader;
+    use quick_xml::escape::resolve_predefined_entity;
+    use quick_xml::events::{BytesRef, Event};
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::path::Path;
+    use zip::ZipArchive;
+
+    type Archive = ZipArchive<File>;
+
+    pub(super) fn pdf(path: &Path) -> io::Result<String> {
+        pdf_extract::extract_text(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Paragraphs of the main document part, one per line.
+    pub(super) fn docx(path: &Path) -> io::Result<String> {
+        let mut archive = open(path)?;
+        let xml = part(&mut archive, "word/document.xml")?
+            .ok_or_else(|| invalid("no word/document.xml part"))?;
+
+        let mut text = String::new();
+        let mut in_run = false;
+        let mut in_text = false;
+        walk(&xml, |event| match event {
+            Node::Start(b"r") => in_run = true,
+            Node::End(b"r") => in_run = false,
+            Node::Start(b"t") => in_text = true,
+            Node::End(b"t") => in_text = false,
+            // Tab stops in paragraph properties are `tab` elements too
+            Node::Start(b"tab") if in_run => text.push('\t'),
+            Node::Start(b"br" | b"cr") if in_run => text.push('\n'),
+            Node::End(b"p") => text.push('\n'),
+            Node::Text(s) if in_text => text.push_str(s),
+            _ => {}
+        })?;
+        Ok(text)
+    }
+
+    /// Every sheet in workbook order: a `# name` line, then one line per
+    /// row with the cells tab separated (gaps kept as empty cells).
+    pub(super) fn xlsx(path: &Path) -> io::Result<String> {
+        let mut archive = open(path)?;
+        let strings = match part(&mut archive, "xl/sharedStrings.xml")? {
+            Some(xml) => shared_strings(&xml)?,
+            None => Vec::new(),
+        };
+
+        let mut text = String::new();
+        for (name, target) in sheets(&mut archive)? {
+            let Some(xml) = part(&mut archive, &target)? else {
+                continue;
+            };
+            text.push_str(&format!("# {name}\n"));
+            sheet_rows(&xml, &strings, &mut text)?;
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// `(name, part)` of each sheet, from the workbook and its relations.
+    fn sheets(archive: &mut Archive) -> io::Result<Vec<(String, String)>> {
+        let workbook =
+            part(archive, "xl/workbook.xml")?.ok_or_else(|| invalid("no xl/workbook.xml part"))?;
+        let rels = part(archive, "xl/_rels/workbook.xml.rels")?.unwrap_or_default();
+
+        let mut targets = HashMap::new();
+        walk_elements(&rels, |name, attrs| {
+            if name == b"Relationship"
+                && let (Some(id), Some(target)) = (attrs.get("Id"), attrs.get("Target"))
+            {
+                targets.insert(id.clone(), target.clone());
+            }
+        })?;
+
+        let mut sheets = Vec::new();
+        walk_elements(&workbook, |name, attrs| {
+            if name == b"sheet"
+                && let (Some(sheet), Some(target)) = (
+                    attrs.get("name"),
+                    attrs.get("id").and_then(|id| targets.get(id)),
+                )
+            {
+                // Targets are relative to xl/, or absolute from the root
+                let target = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None => format!("xl/{target}"),
+                };
+                sheets.push((sheet.clone(), target));
+            }
+        })?;
+        Ok(sheets)
+    }
+
+    /// The shared string table (`<si>` items, rich text runs joined).
+    fn shared_strings(xml: &str) -> io::Result<Vec<String>> {
+        let mut strings = Vec::new();
+        let mut current = String::new();
+        let mut in_text = false;
+        walk(xml, |event| match event {
+            Node::Start(b"t") => in_text = true,
+            Node::End(b"t") => in_text = false,
+            Node::End(b"si") => strings.push(std::mem::take(&mut current)),
+            Node::Text(s) if in_text => current.push_str(s),
+            _ => {}
+        })?;
+        Ok(strings)
+    }
+
+    fn sheet_rows(xml: &str, strings: &[String], text: &mut String) -> io::Result<()> {
+        let mut row: Vec<String> = Vec::new();
+        let mut cell = Cell::default();
+        let mut value = String::new();
+        let mut in_value = false;
+        walk_with_attrs(xml, |event, attrs| match event {
+            Node::Start(b"c") => {
+                cell = Cell {
+                    column: attrs.get("r").and_then(|r| column_index(r)),
+                    kind: attrs.get("t").cloned().unwrap_or_default(),
+                };
+                value.clear();
+            }
+            Node::Start(b"v" | b"t") => in_value = true,
+            Node::End(b"v" | b"t") => in_value = false,
+            Node::Text(s) if in_value => value.push_str(s),
+            Node::End(b"c") => {
+                let column = cell.column.unwrap_or(row.len());
+                if row.len() < column {
+                    row.resize(column, String::new());
+                }
+                let shown = match cell.kind.as_str() {
+                    "s" => value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| strings.get(i).cloned())
+                        .unwrap_or_default(),
+                    "b" => (if value == "1" { "TRUE" } else { "FALSE" }).to_string(),
+                    _ => value.clone(),
+                };
+                row.push(shown.replace(['\t', '\n'], " "));
+            }
+            Node::End(b"row") => {
+                text.push_str(row.join("\t").trim_end_matches('\t'));
+                text.push('\n');
+                row.clear();
+            }
+            _ => {}
+        })
+    }
+
+    #[derive(Default)]
+    struct Cell {
+        /// Zero-based, from the `A1` style reference.
+        column: Option<usize>,
+        /// `s` (shared string), `b`, `inlineStr`, `str`... empty for numbers.
+        kind: String,
+    }
+
+    /// `C7` -> 2.
+    fn column_index(reference: &str) -> Option<usize> {
+        let letters = reference
+            .bytes()
+            .take_while(u8::is_ascii_alphabetic)
+            .map(|b| usize::from(b.to_ascii_uppercase() - b'A') + 1);
+        letters
+            .fold(None, |acc: Option<usize>, n| {
+                Some(acc.unwrap_or(0) * 26 + n)
+            })
+            .map(|n| n - 1)
+    }
+
+    fn open(path: &Path) -> io::Result<Archive> {
+        ZipArchive::new(File::open(path)?).map_err(io::Error::other)
+    }
+
+    /// One part of the package as text; `None` when it is missing.
+    fn part(archive: &mut Archive, name: &str) -> io::Result<Option<String>> {
+        let mut entry = match archive.by_name(name) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(io::Error::other(e)),
+        };
+        let mut xml = String::new();
+        entry.read_to_string(&mut xml)?;
+        Ok(Some(xml))
+    }
+
+    fn invalid(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    /// What the extractors see of the XML: element names without their
+    /// namespace prefix and unescaped text.
+    enum Node<'a> {
+        Start(&'a [u8]),
+        End(&'a [u8]),
+        Text(&'a str),
+    }
+
+    type Attrs = HashMap<String, String>;
+
+    fn walk(xml: &str, mut visit: impl FnMut(Node<'_>)) -> io::Result<()> {
+        walk_with_attrs(xml, |node, _| visit(node))
+    }
+
+    /// Start (and empty) elements with their attributes, by local name.
+    fn walk_elements(xml: &str, mut visit: impl FnMut(&[u8], &Attrs)) -> io::Result<()> {
+        walk_with_attrs(xml, |node, attrs| {
+            if let Node::Start(name) = node {
+                visit(name, attrs);
+            }
+        })
+    }
+
+    /// Streams `xml` into `visit`; an empty element is a start right
+    /// followed by its end. Attributes come with starts only.
+    fn walk_with_attrs(xml: &str, mut visit: impl FnMut(Node<'_>, &Attrs)) -> io::Result<()> {
+        let mut reader = Reader::from_str(xml);
+        let none = Attrs::new();
+        loop {
+            match reader.read_event().map_err(invalid_xml)? {
+                Event::Start(e) => visit(Node::Start(e.local_name().as_ref()), &attributes(&e)?),
+                Event::Empty(e) => {
+                    let name = e.local_name();
+                    visit(Node::Start(name.as_ref()), &attributes(&e)?);
+                    visit(Node::End(name.as_ref()), &none);
+                }
+                Event::End(e) => visit(Node::End(e.local_name().as_ref()), &none),
+                Event::Text(e) => visit(Node::Text(&e.decode().map_err(invalid_xml)?), &none),
+                Event::CData(e) => visit(Node::Text(&e.decode().map_err(invalid_xml)?), &none),
+                Event::GeneralRef(e) => {
+                    if let Some(resolved) = resolve_ref(&e)? {
+                        visit(Node::Text(&resolved), &none);
+                    }
+                }
+                Event::Eof => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_ref(reference: &BytesRef<'_>) -> io::Result<Option<String>> {
+        if let Some(c) = reference.resolve_char_ref().map_err(invalid_xml)? {
+            return Ok(Some(c.to_string()));
+        }
+        let name = reference.decode().map_err(invalid_xml)?;
+        Ok(resolve_predefined_entity(&name).map(str::to_string))
+    }
+
+    /// Attributes by local name (`r:id` is `id`), unescaped.
+    fn attributes(element: &quick_xml::events::BytesStart<'_>) -> io::Result<Attrs> {
+        let mut attrs = Attrs::new();
+        for attr in element.attributes() {
+            let attr = attr.map_err(invalid_xml)?;
+            let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+            let value = attr.unescape_value().map_err(invalid_xml)?.into_owned();
+            attrs.insert(key, value);
+        }
+        Ok(attrs)
+    }
+
+    fn invalid_xml(error: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
@@ -0,0 +1,76 @@
+//! `--errors`: every per-file failure as one JSON line (path, operation, kind, message).
+
+use crate::metadata::format_time;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Append-only sidecar file, shared by the walker threads.
+#[derive(Debug)]
+pub(crate) struct ErrorLog {
+    file: Mutex<File>,
+}
+
+impl ErrorLog {
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create error file {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// One record per line. Failing to write it is not worth aborting the run for.
+    pub(crate) fn record(&self, path: Option<&Path>, operation: &str, kind: &str, message: &str) {
+        let record = serde_json::json!({
+            "time": format_time(Some(SystemTime::now())),
+            "path": path.map(|p| p.to_string_lossy()),
+            "operation": operation,
+            "kind": kind,
+            "message": message,
+        });
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = writeln!(file, "{record}");
+    }
+}
+
+/// Stable name of an I/O error kind (`NotFound`, `PermissionDenied`, ...).
+pub(crate) fn io_kind(error: &io::Error) -> String {
+    format!("{:?}", error.kind())
+}
+
+/// The path and kind behind a walker error, looking through its wrappers.
+pub(crate) fn walk_error_details(error: &ignore::Error) -> (Option<&Path>, String) {
+    let kind = match error.io_error() {
+        Some(e) => io_kind(e),
+        None if walk_loop(error).is_some() => "FilesystemLoop".to_string(),
+        None => "Other".to_string(),
+    };
+    (walk_error_path(error), kind)
+}
+
+fn walk_error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_path(err)
+        }
+        ignore::Error::Partial(errors) => errors.first().and_then(walk_error_path),
+        _ => None,
+    }
+}
+
+/// `(child, ancestor)` of a symlink loop the walker refused to follow.
+pub(crate) fn walk_loop(error: &ignore::Error) -> Option<(&Path, &Path)> {
+    match error {
+        ignore::Error::Loop { child, ancestor } => Some((child, ancestor)),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => walk_loop(err),
+        _ => None,
+    }
+}
@@ -0,0 +1,273 @@
+//! Command execution for `--exec` / `--exec-batch`.
+//!
+//! Commands are split into arguments here and spawned directly (no shell),
+//! so paths with spaces or quotes reach the program unchanged.
+
+use crate::collector::{Collector, Entry};
+use crate::errors::io_kind;
+use anyhow::{Result, bail};
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Upper bound for the paths passed to one `--exec-batch` invocation
+/// (Windows caps a whole command line at 32K characters).
+const BATCH_ARG_BYTES: usize = if cfg!(windows) { 30_000 } else { 128 * 1024 };
+
+/// Placeholders, longest first: path, basename, parent, path without
+/// extension, basename without extension.
+const PLACEHOLDERS: [&str; 5] = ["{/.}", "{//}", "{/}", "{.}", "{}"];
+
+/// A parsed `--exec` command line.
+#[derive(Clone, Debug)]
+pub struct CommandTemplate {
+    args: Vec<String>,
+    /// Without placeholders the path(s) are appended at the end.
+    has_placeholder: bool,
+}
+
+/// Outcome of an exec run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExecSummary {
+    /// Commands started.
+    pub runs: usize,
+    /// Commands that could not start or exited unsuccessfully.
+    pub failures: usize,
+}
+
+impl CommandTemplate {
+    /// Splits `cmd {} --flag 'quoted arg'` into arguments. Single and double
+    /// quotes group words; a backslash escapes whitespace, quotes and itself.
+    pub fn parse(command: &str) -> Result<Self> {
+        let args = split_command(command)?;
+        let has_placeholder = args
+            .iter()
+            .skip(1)
+            .any(|arg| PLACEHOLDERS.iter().any(|p| arg.contains(p)));
+        Ok(Self {
+            args,
+            has_placeholder,
+        })
+    }
+
+    /// Arguments for one path.
+    fn expand(&self, path: &Path) -> Vec<OsString> {
+        self.expand_batch(std::slice::from_ref(&path.to_path_buf()))
+    }
+
+    /// Arguments for many paths: every placeholder argument is repeated per path.
+    fn expand_batch(&self, paths: &[PathBuf]) -> Vec<OsString> {
+        let mut argv: Vec<OsString> = Vec::with_capacity(self.args.len() + paths.len());
+        for (i, arg) in self.args.iter().enumerate() {
+            if i == 0 || !PLACEHOLDERS.iter().any(|p| arg.contains(p)) {
+                argv.push(arg.into());
+            } else {
+                argv.extend(paths.iter().map(|path| expand_arg(arg, path)));
+            }
+        }
+        if !self.has_placeholder {
+            argv.extend(paths.iter().map(|p| p.as_os_str().to_os_string()));
+        }
+        argv
+    }
+}
+
+impl Collector {
+    /// Runs `template` for every matched file (or once per batch of files
+    /// with `batch`), on up to `jobs` processes at a time. With more than one
+    /// job, each command's output is captured and printed in one piece.
+    pub fn exec(
+        &self,
+        template: &CommandTemplate,
+        batch: bool,
+        jobs: usize,
+    ) -> Result<ExecSummary> {
+        // Archive members only have virtual paths, nothing a command can open
+        if self.options().archives {
+            bail!("--exec cannot be used with --archives");
+        }
+        let paths: Vec<PathBuf> = self
+            .entries()?
+            .map(|e: Entry| e.path().to_path_buf())
+            .collect();
+
+        let commands: Vec<Vec<OsString>> = if batch {
+            batches(&paths)
+                .map(|chunk| template.expand_batch(chunk))
+                .collect()
+        } else {
+            paths.iter().map(|path| template.expand(path)).collect()
+        };
+
+        let jobs = jobs.clamp(1, commands.len().max(1));
+        let next = AtomicUsize::new(0);
+        let failures = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    while let Some(argv) = commands.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if !self.run_command(argv, jobs > 1) {
+                            failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(ExecSummary {
+            runs: commands.len(),
+            failures: failures.into_inner(),
+        })
+    }
+
+    /// Spawns one command; returns whether it succeeded.
+    fn run_command(&self, argv: &[OsString], capture: bool) -> bool {
+        let Some((program, args)) = argv.split_first() else {
+            return false;
+        };
+        let mut command = Command::new(program);
+        command.args(args).stdin(Stdio::null());
+
+        let status = if capture {
+            command.output().map(
+                |Output {
+                     status,
+                     stdout,
+                     stderr,
+                 }| {
+                    // Whole-command chunks keep parallel output readable
+                    let _ = io::stdout().lock().write_all(&stdout);
+                    let _ = io::stderr().lock().write_all(&stderr);
+                    status
+                },
+            )
+        } else {
+            command.status()
+        };
+
+        match status {
+            Ok(status) => status.success(),
+            Err(e) => {
+                let program = Path::new(program);
+                self.warn(&format!("Failed to run {}: {}", program.display(), e));
+                self.record_error(Some(program), "running", &io_kind(&e), &e.to_string());
+                false
+            }
+        }
+    }
+}
+
+/// Splits paths into chunks whose combined length stays under `BATCH_ARG_BYTES`.
+fn batches(paths: &[PathBuf]) -> impl Iterator<Item = &[PathBuf]> {
+    let mut rest = paths;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut bytes = 0;
+        let mut len = 0;
+        for path in rest {
+            bytes += path.as_os_str().len() + 1;
+            if len > 0 && bytes > BATCH_ARG_BYTES {
+                break;
+            }
+            len += 1;
+        }
+        let (chunk, tail) = rest.split_at(len);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// Substitutes the placeholders of one argument. A bare `{}` keeps the path
+/// byte-exact; mixed arguments go through lossy UTF-8.
+fn expand_arg(arg: &str, path: &Path) -> OsString {
+    if arg == "{}" {
+        return path.as_os_str().to_os_string();
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or_else(|| ".".into(), |p| p.to_string_lossy());
+    let without_ext = path.with_extension("");
+
+    let mut out = String::with_capacity(arg.len() + path.as_os_str().len());
+    let mut rest = arg;
+    while !rest.is_empty() {
+        let Some((placeholder, len)) = PLACEHOLDERS
+            .iter()
+            .find(|p| rest.starts_with(**p))
+            .map(|p| (*p, p.len()))
+        else {
+            let mut chars = rest.chars();
+            out.extend(chars.next());
+            rest = chars.as_str();
+            continue;
+        };
+        match placeholder {
+            "{/.}" => out.push_str(&stem),
+            "{//}" => out.push_str(&parent),
+            "{/}" => out.push_str(&name),
+            "{.}" => out.push_str(&without_ext.to_string_lossy()),
+            _ => out.push_str(&path.to_string_lossy()),
+        }
+        rest = rest.get(len..).unwrap_or_default();
+    }
+    out.into()
+}
+
+/// Minimal shell-like word splitting (quotes and backslash escapes, no expansion).
+fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (_, '\\')
+                if chars
+                    .peek()
+                    .is_some_and(|n| n.is_whitespace() || matches!(n, '\'' | '"' | '\\')) =>
+            {
+                current.extend(chars.next());
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        bail!("Unterminated quote in command: {command}");
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        bail!("Empty command");
+    }
+    Ok(args)
+}
@@ -0,0 +1,378 @@
+//! `--filter` expressions: a small boolean language over entry attributes,
+//! e.g. `ext == 'rs' && size > 10KB && path !~ 'tests/'`.
+
+use crate::options::EntryType;
+use crate::units::{parse_size, parse_time};
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use std::fs::Metadata;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use std::time::SystemTime;
+
+/// A parsed filter, evaluated once per entry.
+#[derive(Debug)]
+pub(crate) enum Expr {
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+    Compare(Field, CmpOp, Value),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Field {
+    Path,
+    Name,
+    Ext,
+    Size,
+    Mtime,
+    Depth,
+    Type,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Name => "name",
+            Self::Ext => "ext",
+            Self::Size => "size",
+            Self::Mtime => "mtime",
+            Self::Depth => "depth",
+            Self::Type => "type",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+/// Right-hand side, already converted to the field's type.
+#[derive(Debug)]
+pub(crate) enum Value {
+    Text(String),
+    Regex(Regex),
+    Number(u64),
+    Time(SystemTime),
+    Kind(EntryType),
+}
+
+/// What an expression can look at for one entry.
+pub(crate) struct Candidate<'a> {
+    /// Relative to the base path, '/' separated.
+    pub path: &'a str,
+    pub depth: usize,
+    pub kind: EntryType,
+    /// Present whenever the expression uses `size` or `mtime`.
+    pub meta: Option<&'a Metadata>,
+}
+
+impl Expr {
+    pub(crate) fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => bail!("Invalid --filter: unexpected {}", token.describe()),
+        }
+    }
+
+    /// True when evaluation needs `stat` data.
+    pub(crate) fn needs_metadata(&self) -> bool {
+        match self {
+            Self::And(a, b) | Self::Or(a, b) => a.needs_metadata() || b.needs_metadata(),
+            Self::Not(inner) => inner.needs_metadata(),
+            Self::Compare(field, ..) => matches!(field, Field::Size | Field::Mtime),
+        }
+    }
+
+    pub(crate) fn matches(&self, candidate: &Candidate<'_>) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(candidate) && b.matches(candidate),
+            Self::Or(a, b) => a.matches(candidate) || b.matches(candidate),
+            Self::Not(inner) => !inner.matches(candidate),
+            Self::Compare(field, op, value) => compare(*field, *op, value, candidate),
+        }
+    }
+}
+
+fn compare(field: Field, op: CmpOp, value: &Value, candidate: &Candidate<'_>) -> bool {
+    match (field, value) {
+        (Field::Path | Field::Name | Field::Ext, Value::Text(expected)) => {
+            let text = text_field(field, candidate.path);
+            (text == *expected) == (op == CmpOp::Eq)
+        }
+        (Field::Path | Field::Name | Field::Ext, Value::Regex(regex)) => {
+            let text = text_field(field, candidate.path);
+            regex.is_match(&text) == (op == CmpOp::Match)
+        }
+        (Field::Size, Value::Number(expected)) => candidate
+            .meta
+            .is_some_and(|m| ordered(op, m.len(), *expected)),
+        (Field::Depth, Value::Number(expected)) => ordered(op, candidate.depth as u64, *expected),
+        // Files without an mtime can't satisfy a time comparison
+        (Field::Mtime, Value::Time(expected)) => candidate
+            .meta
+            .and_then(|m| m.modified().ok())
+            .is_some_and(|mtime| ordered(op, mtime, *expected)),
+        (Field::Type, Value::Kind(kind)) => (candidate.kind == *kind) == (op == CmpOp::Eq),
+        // The parser only builds the pairs above
+        _ => false,
+    }
+}
+
+fn text_field(field: Field, path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match field {
+        Field::Name => name.to_string(),
+        // Same rule as --extension: lowercase, no dot, "" when missing
+        Field::Ext => match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => ext.to_lowercase(),
+            _ => String::new(),
+        },
+        _ => path.to_string(),
+    }
+}
+
+fn ordered<T: PartialOrd>(op: CmpOp, actual: T, expected: T) -> bool {
+    match op {
+        CmpOp::Eq => actual == expected,
+        CmpOp::Ne => actual != expected,
+        CmpOp::Lt => actual < expected,
+        CmpOp::Le => actual <= expected,
+        CmpOp::Gt => actual > expected,
+        CmpOp::Ge => actual >= expected,
+        CmpOp::Match | CmpOp::NotMatch => false,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Field names and bare values (`rs`, `file`).
+    Word(String),
+    /// Quoted strings, and numbers with optional units (`10KB`, `2d`).
+    Literal(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(w) | Self::Literal(w) => format!("'{w}'"),
+            Self::Op(_) => "comparison operator".to_string(),
+            Self::And => "'&&'".to_string(),
+            Self::Or => "'||'".to_string(),
+            Self::Not => "'!'".to_string(),
+            Self::Open => "'('".to_string(),
+            Self::Close => "')'".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is(&mut chars, '&') => Token::And,
+            '|' if next_is(&mut chars, '|') => Token::Or,
+            '=' if next_is(&mut chars, '=') => Token::Op(CmpOp::Eq),
+            '=' if next_is(&mut chars, '~') => Token::Op(CmpOp::Match),
+            '!' if next_is(&mut chars, '=') => Token::Op(CmpOp::Ne),
+            '!' if next_is(&mut chars, '~') => Token::Op(CmpOp::NotMatch),
+            '!' => Token::Not,
+            '<' if next_is(&mut chars, '=') => Token::Op(CmpOp::Le),
+            '<' => Token::Op(CmpOp::Lt),
+            '>' if next_is(&mut chars, '=') => Token::Op(CmpOp::Ge),
+            '>' => Token::Op(CmpOp::Gt),
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        // Backslash escapes the quote (and itself)
+                        Some((_, '\\'))
+                            if chars.peek().is_some_and(|&(_, n)| n == c || n == '\\') =>
+                        {
+                            text.extend(chars.next().map(|(_, n)| n));
+                        }
+                        Some((_, ch)) => text.push(ch),
+                        None => bail!("Invalid --filter: unterminated string at position {pos}"),
+                    }
+                }
+                Token::Literal(text)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, n)) = chars.next_if(|&(_, n)| is_word_char(n)) {
+                    word.push(n);
+                }
+                if c.is_ascii_digit() {
+                    Token::Literal(word)
+                } else {
+                    Token::Word(word)
+                }
+            }
+            other => bail!("Invalid --filter: unexpected '{other}' at position {pos}"),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | ':')
+}
+
+fn next_is(chars: &mut Peekable<CharIndices<'_>>, expected: char) -> bool {
+    chars.next_if(|&(_, c)| c == expected).is_some()
+}
+
+/// Recursive descent: `||` binds loosest, then `&&`, then `!`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            if !self.eat(&Token::Close) {
+                bail!("Invalid --filter: missing ')'");
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let field = match self.next() {
+            Some(Token::Word(word)) => match word.as_str() {
+                "path" => Field::Path,
+                "name" => Field::Name,
+                "ext" => Field::Ext,
+                "size" => Field::Size,
+                "mtime" => Field::Mtime,
+                "depth" => Field::Depth,
+                "type" => Field::Type,
+                other => bail!(
+                    "Invalid --filter: unknown field '{other}' (path, name, ext, size, mtime, depth, type)"
+                ),
+            },
+            Some(token) => bail!(
+                "Invalid --filter: expected a field, found {}",
+                token.describe()
+            ),
+            None => bail!("Invalid --filter: expected a field"),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => *op,
+            _ => bail!(
+                "Invalid --filter: expected a comparison after '{}'",
+                field.name()
+            ),
+        };
+        let raw = match self.next() {
+            Some(Token::Word(text) | Token::Literal(text)) => text.clone(),
+            _ => bail!(
+                "Invalid --filter: expected a value after '{}'",
+                field.name()
+            ),
+        };
+
+        let regex_op = matches!(op, CmpOp::Match | CmpOp::NotMatch);
+        let equality = matches!(op, CmpOp::Eq | CmpOp::Ne);
+        let value = match field {
+            Field::Path | Field::Name | Field::Ext if regex_op => {
+                Value::Regex(Regex::new(&raw).map_err(|e| anyhow!("Invalid --filter regex: {e}"))?)
+            }
+            Field::Path | Field::Name | Field::Ext if equality => Value::Text(match field {
+                Field::Ext => raw.trim_start_matches('.').to_lowercase(),
+                _ => raw,
+            }),
+            Field::Size if !regex_op => {
+                Value::Number(parse_size(&raw).map_err(|e| anyhow!("Invalid --filter: {e}"))?)
+            }
+            Field::Depth if !regex_op => Value::Number(
+                raw.parse()
+                    .map_err(|_| anyhow!("Invalid --filter: depth '{raw}' is not a number"))?,
+            ),
+            Field::Mtime if !regex_op => {
+                Value::Time(parse_time(&raw).map_err(|e| anyhow!("Invalid --filter: {e}"))?)
+            }
+            Field::Type if equality => Value::Kind(match raw.as_str() {
+                "f" | "file" => EntryType::File,
+                "d" | "dir" => EntryType::Dir,
+                "l" | "symlink" => EntryType::Symlink,
+                "s" | "special" => EntryType::Special,
+                other => {
+                    bail!("Invalid --filter: unknown type '{other}' (file, dir, symlink, special)")
+                }
+            }),
+            _ => bail!(
+                "Invalid --filter: operator not supported for '{}'",
+                field.name()
+            ),
+        };
+        Ok(Expr::Compare(field, op, value))
+    }
+}
@@ -0,0 +1,273 @@
+//! Path filters, compiled once and evaluated for every walker entry.
+
+use crate::expr::Expr;
+use crate::options::{CollectOptions, RegexMode, Scope};
+use crate::owners::{group_id, mode, ownership, user_id};
+use anyhow::{Context, Result};
+use regex::bytes::{Regex as BytesRegex, RegexBuilder as BytesRegexBuilder};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Runtime filters optimized for lookup speed.
+#[derive(Debug)]
+pub(crate) struct Filters {
+    extensions: Option<Vec<String>>,
+    extension_inv: bool,
+    regex: Vec<Regex>,
+    pub(crate) regex_inv: bool,
+    /// `--regex` with `Scope::Content`, checked after the path filters.
+    pub(crate) content_regex: Vec<BytesRegex>,
+    pub(crate) regex_mode: RegexMode,
+    scope: Scope,
+    pub(crate) pattern: Option<BytesRegex>,
+    /// `--filter` expressions (all must match).
+    pub(crate) exprs: Vec<Expr>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+    owner: Option<IdMatch>,
+    group: Option<IdMatch>,
+    perm: Option<PermMatch>,
+}
+
+impl Filters {
+    pub(crate) fn new(options: &CollectOptions) -> Result<Self> {
+        let mut regex = Vec::new();
+        let mut content_regex = Vec::new();
+        for re_str in &options.regex {
+            if options.scope == Scope::Content {
+                content_regex.push(bytes_regex(re_str, options).context("Invalid Regex format")?);
+            } else {
+                regex.push(
+                    RegexBuilder::new(&regex_source(re_str, options))
+                        .case_insensitive(options.ignore_case)
+                        .build()
+                        .context("Invalid Regex format")?,
+                );
+            }
+        }
+
+        let pattern = if let Some(pat_str) = &options.pattern {
+            Some(bytes_regex(pat_str, options).context("Invalid --pattern format")?)
+        } else {
+            None
+        };
+
+        let exprs = options
+            .filter
+            .iter()
+            .map(|source| Expr::parse(source))
+            .collect::<Result<_>>()?;
+
+        // Normalize extensions to lowercase for case-insensitive comparison
+        let extensions = options.extensions.as_ref().map(|exts| {
+            exts.iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .collect()
+        });
+
+        let owner = options
+            .owner
+            .as_deref()
+            .map(|spec| IdMatch::parse(spec, user_id))
+            .transpose()?;
+        let group = options
+            .group
+            .as_deref()
+            .map(|spec| IdMatch::parse(spec, group_id))
+            .transpose()?;
+        let perm = options.perm.as_deref().map(PermMatch::parse).transpose()?;
+
+        Ok(Self {
+            extensions,
+            extension_inv: options.extension_inv,
+            regex,
+            regex_inv: options.regex_inv,
+            content_regex,
+            regex_mode: options.regex_mode,
+            scope: options.scope,
+            pattern,
+            exprs,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            newer_than: options.newer_than,
+            older_than: options.older_than,
+            owner,
+            group,
+            perm,
+        })
+    }
+
+    /// Evaluates if a path matches the criteria.
+    /// This is the "hot path" of the application, keep it allocation-free if possible.
+    pub(crate) fn should_process(&self, path: &Path, is_dir: bool) -> bool {
+        // 1. Extension Filter (O(1) lookup effectively for small lists)
+        if !is_dir && let Some(exts) = &self.extensions {
+            let file_ext = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_lowercase())
+                .unwrap_or_default();
+
+            let found = exts.contains(&file_ext);
+            if found == self.extension_inv {
+                return false;
+            }
+        }
+
+        // 2. Regex Filter (Expensive, do it last)
+        if !self.regex.is_empty() {
+            let text_to_match = match self.scope {
+                Scope::Name => path.file_name().and_then(|s| s.to_str()).unwrap_or(""),
+                Scope::Path => path.to_str().unwrap_or(""),
+                // Compiled into `content_regex` instead
+                Scope::Content => return true,
+            };
+
+            let found = match self.regex_mode {
+                RegexMode::Any => self.regex.iter().any(|re| re.is_match(text_to_match)),
+                RegexMode::All => self.regex.iter().all(|re| re.is_match(text_to_match)),
+            };
+            if found == self.regex_inv {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// True when a filter needs `stat` data (checked before the content search).
+    pub(crate) fn needs_metadata(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.newer_than.is_some()
+            || self.older_than.is_some()
+            || self.owner.is_some()
+            || self.group.is_some()
+            || self.perm.is_some()
+            || self.exprs.iter().any(Expr::needs_metadata)
+    }
+
+    /// Metadata-based filters (size and mtime bounds, owner, group, mode).
+    pub(crate) fn matches_metadata(&self, meta: &Metadata) -> bool {
+        let size = meta.len();
+        if self.min_size.is_some_and(|min| size < min) {
+            return false;
+        }
+        if self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            // Files without an mtime can't satisfy a time bound
+            let Ok(mtime) = meta.modified() else {
+                return false;
+            };
+            if self.newer_than.is_some_and(|t| mtime <= t) {
+                return false;
+            }
+            if self.older_than.is_some_and(|t| mtime >= t) {
+                return false;
+            }
+        }
+
+        if self.owner.is_some() || self.group.is_some() {
+            let Some((uid, gid)) = ownership(meta) else {
+                return false;
+            };
+            if self.owner.is_some_and(|owner| !owner.matches(uid)) {
+                return false;
+            }
+            if self.group.is_some_and(|group| !group.matches(gid)) {
+                return false;
+            }
+        }
+        if let Some(perm) = self.perm {
+            return mode(meta).is_some_and(|mode| perm.matches(mode));
+        }
+
+        true
+    }
+}
+
+/// `--owner` / `--group`: a user or group (name or id), or with a leading
+/// `!` anyone else.
+#[derive(Clone, Copy, Debug)]
+struct IdMatch {
+    id: u32,
+    negate: bool,
+}
+
+impl IdMatch {
+    fn parse(spec: &str, lookup: fn(&str) -> Result<u32>) -> Result<Self> {
+        let (negate, name) = match spec.strip_prefix('!') {
+            Some(name) => (true, name),
+            None => (false, spec),
+        };
+        Ok(Self {
+            id: lookup(name.trim())?,
+            negate,
+        })
+    }
+
+    fn matches(self, id: u32) -> bool {
+        (id == self.id) != self.negate
+    }
+}
+
+/// `--perm`, as in find: `MODE` exactly these bits, `-MODE` at least all of
+/// them, `/MODE` at least one of them (octal, e.g. `644`, `-4000`, `/022`).
+#[derive(Clone, Copy, Debug)]
+enum PermMatch {
+    Exact(u32),
+    All(u32),
+    Any(u32),
+}
+
+impl PermMatch {
+    fn parse(spec: &str) -> Result<Self> {
+        let (kind, digits): (fn(u32) -> Self, _) = if let Some(digits) = spec.strip_prefix('-') {
+            (Self::All, digits)
+        } else if let Some(digits) = spec.strip_prefix('/') {
+            (Self::Any, digits)
+        } else {
+            (Self::Exact, spec)
+        };
+        let bits = u32::from_str_radix(digits, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)
+            .with_context(|| {
+                format!("Invalid --perm '{spec}': expected octal bits such as 644, -002 or /111")
+            })?;
+        Ok(kind(bits))
+    }
+
+    fn matches(self, mode: u32) -> bool {
+        match self {
+            Self::Exact(bits) => mode == bits,
+            Self::All(bits) => mode & bits == bits,
+            // As in find, `/000` matches everything
+            Self::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+/// `fixed_strings` turns a pattern into an escaped literal.
+fn regex_source<'a>(pattern: &'a str, options: &CollectOptions) -> Cow<'a, str> {
+    if options.fixed_strings {
+        Cow::Owned(regex::escape(pattern))
+    } else {
+        Cow::Borrowed(pattern)
+    }
+}
+
+/// Content regexes (`--pattern`, `--scope content`) with the shared modifiers.
+fn bytes_regex(pattern: &str, options: &CollectOptions) -> Result<BytesRegex, regex::Error> {
+    BytesRegexBuilder::new(&regex_source(pattern, options))
+        .case_insensitive(options.ignore_case)
+        .build()
+}
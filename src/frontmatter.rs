@@ -0,0 +1,160 @@
+//! `--frontmatter`: the YAML (`---`) or TOML (`+++`) block at the top of
+//! Markdown files, as structured fields to emit and filter on.
+
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Front matter longer than this is not looked for.
+const READ_LIMIT: u64 = 64 * 1024;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "mdown", "mkd", "rmd"];
+
+/// The fields of a file's front matter.
+pub(crate) type Fields = Map<String, Value>;
+
+/// The front matter of the Markdown file at `path`: `Ok(None)` for other
+/// files and Markdown without one, an `InvalidData` error when the block
+/// does not parse.
+pub(crate) fn read_front_matter(path: &Path) -> io::Result<Option<Fields>> {
+    let is_markdown = path.extension().is_some_and(|e| {
+        MARKDOWN_EXTENSIONS
+            .iter()
+            .any(|m| e.eq_ignore_ascii_case(m))
+    });
+    if !is_markdown {
+        return Ok(None);
+    }
+    let mut head = Vec::new();
+    File::open(path)?.take(READ_LIMIT).read_to_end(&mut head)?;
+    let text = String::from_utf8_lossy(&head);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    parse(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse(text: &str) -> Result<Option<Fields>> {
+    let mut lines = text.split_inclusive('\n');
+    let Some(first) = lines.next() else {
+        return Ok(None);
+    };
+    let (closing, yaml): (&[&str], bool) = match first.trim_end() {
+        "---" => (&["---", "..."], true),
+        "+++" => (&["+++"], false),
+        _ => return Ok(None),
+    };
+
+    let mut block = String::new();
+    let mut closed = false;
+    for line in lines {
+        if closing.contains(&line.trim_end()) {
+            closed = true;
+            break;
+        }
+        block.push_str(line);
+    }
+    // A thematic break opening a document is no front matter
+    if !closed {
+        return Ok(None);
+    }
+
+    let value = if yaml {
+        if block.trim().is_empty() {
+            return Ok(Some(Fields::new()));
+        }
+        serde_yaml_ng::from_str::<Value>(&block)?
+    } else {
+        toml_to_json(toml::from_str::<toml::Value>(&block)?)
+    };
+    match value {
+        Value::Object(fields) => Ok(Some(fields)),
+        Value::Null => Ok(Some(Fields::new())),
+        _ => Err(anyhow!("front matter is not a mapping")),
+    }
+}
+
+/// TOML dates and times become their text form.
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::from(s),
+        toml::Value::Integer(n) => Value::from(n),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::from(b),
+        toml::Value::Datetime(d) => Value::from(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// A field as text: strings as they are, anything else as compact JSON.
+pub(crate) fn field_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// A parsed `--frontmatter-filter`: `KEY=VALUE`, `KEY!=VALUE` or a bare
+/// `KEY` (present and not `false` / null). Dots reach into nested fields
+/// (`params.author=ann`); a list matches when one of its items does.
+#[derive(Clone, Debug)]
+pub struct FrontMatterFilter {
+    key: Vec<String>,
+    test: Test,
+}
+
+#[derive(Clone, Debug)]
+enum Test {
+    Set,
+    Equals(String),
+    Differs(String),
+}
+
+impl FrontMatterFilter {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (key, test) = if let Some((key, value)) = spec.split_once("!=") {
+            (key, Test::Differs(value.trim().to_string()))
+        } else if let Some((key, value)) = spec.split_once('=') {
+            (key, Test::Equals(value.trim().to_string()))
+        } else {
+            (spec, Test::Set)
+        };
+        let key: Vec<String> = key.trim().split('.').map(str::to_string).collect();
+        if key.iter().any(String::is_empty) {
+            return Err(anyhow!(
+                "Invalid --frontmatter-filter '{spec}' (expected KEY, KEY=VALUE or KEY!=VALUE)"
+            ));
+        }
+        Ok(Self { key, test })
+    }
+
+    /// Whether a file with these fields (`None`: no front matter) passes.
+    pub(crate) fn matches(&self, fields: Option<&Fields>) -> bool {
+        let mut value = None;
+        if let Some((first, rest)) = self.key.split_first() {
+            value = fields.and_then(|f| f.get(first));
+            for key in rest {
+                value = value.and_then(|v| v.get(key));
+            }
+        }
+        match &self.test {
+            Test::Set => value.is_some_and(|v| !matches!(v, Value::Null | Value::Bool(false))),
+            Test::Equals(expected) => value.is_some_and(|v| equals(v, expected)),
+            Test::Differs(expected) => !value.is_some_and(|v| equals(v, expected)),
+        }
+    }
+}
+
+fn equals(value: &Value, expected: &str) -> bool {
+    match value {
+        Value::Array(items) => items.iter().any(|item| equals(item, expected)),
+        Value::Null => expected.is_empty() || expected == "null" || expected == "~",
+        other => field_text(other) == expected,
+    }
+}
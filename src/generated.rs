@@ -0,0 +1,129 @@
+//! `--skip-generated`: lockfiles, source maps, minified bundles and files
+//! that say they are machine written, whose content is noise in a bundle.
+//! They are still listed; only their content gives way to a marker.
+
+use crate::binary::{PROBE_LEN, looks_binary};
+use crate::options::BinaryCheck;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Package manager lockfiles, by exact name.
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "bun.lockb",
+    "deno.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "Pipfile.lock",
+    "poetry.lock",
+    "pdm.lock",
+    "uv.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+    "pubspec.lock",
+    "Podfile.lock",
+    "Package.resolved",
+    "Cartfile.resolved",
+    "packages.lock.json",
+    "gradle.lockfile",
+    "shard.lock",
+    "conan.lock",
+];
+
+/// Sources whose minified builds are recognized, by name or by content.
+const MINIFIABLE: &[&str] = &["js", "mjs", "cjs", "css"];
+
+/// Text that marks a file as written by a tool: Go's `Code generated ...
+/// DO NOT EDIT.`, `@generated` and .NET's `<auto-generated>` headers.
+const MARKERS: &[&str] = &["@generated", "DO NOT EDIT", "<auto-generated"];
+
+/// Markers only count in the first lines, so code mentioning them is kept.
+const MARKER_LINES: usize = 5;
+
+/// Average line length (in the first `PROBE_LEN` bytes) above which a
+/// script or stylesheet is taken as minified. Hand-written code stays far
+/// below it.
+const MINIFIED_LINE_LEN: usize = 250;
+
+/// Why a file counts as generated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Generated {
+    Lockfile,
+    SourceMap,
+    Minified,
+    /// Carries one of `MARKERS`.
+    Marked,
+}
+
+impl Generated {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Lockfile => "lockfile",
+            Self::SourceMap => "source map",
+            Self::Minified => "minified",
+            Self::Marked => "marked generated",
+        }
+    }
+
+    /// What content output shows instead of the file's text.
+    pub(crate) fn marker(self) -> String {
+        format!("<Generated file ({}), content skipped>", self.name())
+    }
+}
+
+/// Whether the file named `name`, with its content at `content`, is
+/// generated. The name decides first; only scripts, stylesheets and text
+/// files with a marker need their first `PROBE_LEN` bytes read.
+pub(crate) fn detect_generated(
+    name: &Path,
+    content: &Path,
+    check: BinaryCheck,
+) -> io::Result<Option<Generated>> {
+    let file_name = name
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    if LOCKFILES.contains(&file_name.as_ref()) {
+        return Ok(Some(Generated::Lockfile));
+    }
+    // app.js.map, but not a bare .map, which is also a game level or a
+    // linker map
+    let mut parts = file_name.rsplit('.');
+    let (last, inner) = (parts.next(), parts.next());
+    if last == Some("map") && inner.is_some_and(|ext| MINIFIABLE.contains(&ext) || ext == "ts") {
+        return Ok(Some(Generated::SourceMap));
+    }
+    let minifiable = last.is_some_and(|ext| MINIFIABLE.contains(&ext));
+    if minifiable && inner == Some("min") {
+        return Ok(Some(Generated::Minified));
+    }
+
+    let mut head = Vec::new();
+    File::open(content)?
+        .take(PROBE_LEN as u64)
+        .read_to_end(&mut head)?;
+    if looks_binary(&head, check) {
+        return Ok(None);
+    }
+    let text = String::from_utf8_lossy(&head);
+    if text
+        .lines()
+        .take(MARKER_LINES)
+        .any(|line| MARKERS.iter().any(|marker| line.contains(marker)))
+    {
+        return Ok(Some(Generated::Marked));
+    }
+    // A last line cut off by the probe counts as a line
+    let lines = text.lines().count().max(1);
+    if minifiable && text.len() / lines > MINIFIED_LINE_LEN {
+        return Ok(Some(Generated::Minified));
+    }
+    Ok(None)
+}
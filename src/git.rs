@@ -0,0 +1,315 @@
+//! Git-aware selection for `--git` and `--export-ignore`, and the
+//! `--git-meta` annotations, by shelling out to the `git` binary.
+
+use crate::options::GitSelection;
+use crate::paths;
+use anyhow::{Context, Result, bail};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Paths selected by git, stored absolute so walker and listed paths can be
+/// matched regardless of how `base_path` was spelled.
+#[derive(Debug)]
+pub(crate) struct GitFilter {
+    /// Canonical base path (walker paths are rooted at `base_path`).
+    root: PathBuf,
+    base_path: PathBuf,
+    paths: HashSet<PathBuf>,
+}
+
+impl GitFilter {
+    pub(crate) fn new(base_path: &Path, selections: &[GitSelection]) -> Result<Self> {
+        let root = paths::canonicalize(base_path)
+            .with_context(|| format!("Failed to resolve {}", base_path.display()))?;
+        let toplevel = PathBuf::from(
+            git(&root, &["rev-parse", "--show-toplevel"])?.trim_end_matches(['\r', '\n']),
+        );
+
+        let mut paths = HashSet::new();
+        for selection in selections {
+            let args: &[&str] = match selection {
+                GitSelection::Tracked => &["ls-files", "-z", "--full-name"],
+                GitSelection::Modified => &["diff", "--name-only", "-z", "HEAD"],
+                GitSelection::Staged => &["diff", "--name-only", "-z", "--cached"],
+                GitSelection::Untracked => &[
+                    "ls-files",
+                    "-z",
+                    "--full-name",
+                    "--others",
+                    "--exclude-standard",
+                ],
+            };
+            // Output is relative to the repository root
+            let output = git(&root, args)?;
+            paths.extend(
+                output
+                    .split('\0')
+                    .filter(|p| !p.is_empty())
+                    .map(|p| toplevel.join(p)),
+            );
+        }
+
+        Ok(Self {
+            root,
+            base_path: base_path.to_path_buf(),
+            paths,
+        })
+    }
+
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        let absolute = match path.strip_prefix(&self.base_path) {
+            Ok(relative) => self.root.join(relative),
+            // Listed paths may live outside the base path
+            Err(_) => match paths::canonicalize(path) {
+                Ok(p) => p,
+                Err(_) => return false,
+            },
+        };
+        self.paths.contains(&absolute)
+    }
+}
+
+/// Paths below `base_path` (relative to it) that differ between the merge
+/// base of `reference` and HEAD and the working tree: committed, staged or
+/// not. Deleted files are left out.
+pub(crate) fn changed_paths(base_path: &Path, reference: &str) -> Result<Vec<PathBuf>> {
+    let root = paths::canonicalize(base_path)
+        .with_context(|| format!("Failed to resolve {}", base_path.display()))?;
+    if reference.starts_with('-') {
+        bail!("Invalid git reference '{reference}'");
+    }
+    if git(&root, &["rev-parse", "--verify", "--quiet", reference]).is_err() {
+        bail!("Unknown git reference '{reference}'");
+    }
+    let output = git(
+        &root,
+        &[
+            "diff",
+            "--name-only",
+            "-z",
+            "--relative",
+            "--diff-filter=d",
+            "--merge-base",
+            reference,
+        ],
+    )?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Tracked paths with the `export-ignore` attribute set, which `git archive`
+/// leaves out of a release: files, and directories with everything below.
+#[derive(Debug)]
+pub(crate) struct ExportIgnore {
+    root: PathBuf,
+    base_path: PathBuf,
+    ignored: HashSet<PathBuf>,
+}
+
+impl ExportIgnore {
+    pub(crate) fn new(base_path: &Path) -> Result<Self> {
+        let root = paths::canonicalize(base_path)
+            .with_context(|| format!("Failed to resolve {}", base_path.display()))?;
+        let toplevel = PathBuf::from(
+            git(&root, &["rev-parse", "--show-toplevel"])?.trim_end_matches(['\r', '\n']),
+        );
+
+        // Attributes do not pass from a directory to its content, so the
+        // directories are asked about too, as git archive does
+        let files = git(&toplevel, &["ls-files", "-z"])?;
+        let mut candidates = BTreeSet::new();
+        for file in files.split('\0').filter(|p| !p.is_empty()) {
+            candidates.insert(file);
+            let mut dir = file;
+            while let Some((parent, _)) = dir.rsplit_once('/') {
+                if !candidates.insert(parent) {
+                    break;
+                }
+                dir = parent;
+            }
+        }
+        let input = candidates.into_iter().collect::<Vec<_>>().join("\0");
+        let output = git_with_input(
+            &toplevel,
+            &["check-attr", "-z", "--stdin", "export-ignore"],
+            input,
+        )?;
+
+        // NUL-separated triples: path, attribute, value
+        let fields: Vec<&str> = output.split('\0').collect();
+        let ignored = fields
+            .chunks_exact(3)
+            .filter_map(|triple| match triple {
+                [path, _, "set"] => Some(toplevel.join(path)),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            root,
+            base_path: base_path.to_path_buf(),
+            ignored,
+        })
+    }
+
+    /// Whether `path` or one of its directories is export-ignored.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        if self.ignored.is_empty() {
+            return false;
+        }
+        let absolute = match path.strip_prefix(&self.base_path) {
+            Ok(relative) => self.root.join(relative),
+            Err(_) => match paths::canonicalize(path) {
+                Ok(p) => p,
+                Err(_) => return false,
+            },
+        };
+        absolute.ancestors().any(|p| self.ignored.contains(p))
+    }
+}
+
+/// The last commit that touched a file (`--git-meta`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitCommit {
+    /// Full commit hash.
+    pub hash: String,
+    pub author: String,
+    /// Commit date.
+    pub time: SystemTime,
+}
+
+impl GitCommit {
+    /// The first 12 hex digits of the hash, as shown in text output.
+    pub fn short_hash(&self) -> &str {
+        self.hash.get(..12).unwrap_or(&self.hash)
+    }
+}
+
+/// The last commit of every file below the base path, read from a single
+/// `git log` walk (newest first, so the first commit naming a path wins).
+#[derive(Debug)]
+pub(crate) struct GitLog {
+    root: PathBuf,
+    base_path: PathBuf,
+    commits: HashMap<PathBuf, Arc<GitCommit>>,
+}
+
+impl GitLog {
+    pub(crate) fn new(base_path: &Path) -> Result<Self> {
+        let root = paths::canonicalize(base_path)
+            .with_context(|| format!("Failed to resolve {}", base_path.display()))?;
+        let toplevel = PathBuf::from(
+            git(&root, &["rev-parse", "--show-toplevel"])?.trim_end_matches(['\r', '\n']),
+        );
+        // A repository without commits has no history to read
+        if git(&root, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+            return Ok(Self {
+                root,
+                base_path: base_path.to_path_buf(),
+                commits: HashMap::new(),
+            });
+        }
+        // Names are relative to the repository root, even for a pathspec
+        // limited to the base path
+        let output = git(
+            &root,
+            &[
+                "log",
+                "-z",
+                "--name-only",
+                "--format=%x1e%H%x1f%an%x1f%ct",
+                "--",
+                ".",
+            ],
+        )?;
+
+        let mut commits = HashMap::new();
+        for record in output.split('\x1e').filter(|r| !r.is_empty()) {
+            let mut fields = record.split('\0');
+            let header = fields.next().unwrap_or_default();
+            let mut parts = header.splitn(3, '\x1f');
+            let (Some(hash), Some(author), Some(time)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let commit = Arc::new(GitCommit {
+                hash: hash.to_string(),
+                author: author.to_string(),
+                time: UNIX_EPOCH + Duration::from_secs(time.trim().parse().unwrap_or(0)),
+            });
+            for name in fields
+                .map(|f| f.trim_start_matches('\n'))
+                .filter(|f| !f.is_empty())
+            {
+                commits
+                    .entry(toplevel.join(name))
+                    .or_insert_with(|| Arc::clone(&commit));
+            }
+        }
+
+        Ok(Self {
+            root,
+            base_path: base_path.to_path_buf(),
+            commits,
+        })
+    }
+
+    /// The last commit of `path`; `None` for files git never saw.
+    pub(crate) fn commit(&self, path: &Path) -> Option<Arc<GitCommit>> {
+        let absolute = match path.strip_prefix(&self.base_path) {
+            Ok(relative) => self.root.join(relative),
+            Err(_) => paths::canonicalize(path).ok()?,
+        };
+        self.commits.get(&absolute).cloned()
+    }
+}
+
+/// Runs `git <args>` in `dir` and returns its stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args.iter().map(OsStr::new))
+        .output()
+        .context("Failed to run git (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `git` with `input` on its stdin, written from a thread so a large output
+/// cannot block the writer.
+fn git_with_input(dir: &Path, args: &[&str], input: String) -> Result<String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args.iter().map(OsStr::new))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run git (is it installed?)")?;
+    let mut stdin = child.stdin.take().context("git stdin is not piped")?;
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A write error means git exited early; its status tells why
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
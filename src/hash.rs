@@ -0,0 +1,63 @@
+//! Per-file content hashing for `--hash`.
+
+use crate::options::HashAlgorithm;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Streams the whole file through the hasher and returns the lowercase hex digest.
+/// Ignores `max_bytes`: a truncated hash would be useless for integrity checks.
+pub(crate) fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    let digest = match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            feed(&mut file, &mut buffer, |chunk| hasher.update(chunk))?;
+            hasher.finalize().to_vec()
+        }
+        HashAlgorithm::Md5 => {
+            let mut hasher = Md5::new();
+            feed(&mut file, &mut buffer, |chunk| hasher.update(chunk))?;
+            hasher.finalize().to_vec()
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            feed(&mut file, &mut buffer, |chunk| {
+                hasher.update(chunk);
+            })?;
+            hasher.finalize().as_bytes().to_vec()
+        }
+    };
+
+    Ok(to_hex(&digest))
+}
+
+fn feed(
+    reader: &mut impl Read,
+    buffer: &mut [u8],
+    mut update: impl FnMut(&[u8]),
+) -> io::Result<()> {
+    loop {
+        let n = match reader.read(buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        update(buffer.get(..n).unwrap_or_default());
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        // Writing to a String cannot fail
+        let _ = write!(out, "{b:02x}");
+    }
+    out
+}
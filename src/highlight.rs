@@ -0,0 +1,116 @@
+//! `--highlight`: ANSI syntax highlighting of text content (syntect).
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::as_24_bit_terminal_escaped;
+
+const THEME: &str = "base16-ocean.dark";
+const RESET: &[u8] = b"\x1b[0m";
+
+/// Built-in syntax definitions, loaded on first use.
+pub(crate) fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// The language for `path`, from its name or first line (plain text otherwise).
+pub(crate) fn syntax_for(path: &Path) -> &'static SyntaxReference {
+    let syntaxes = syntax_set();
+    syntaxes
+        .find_syntax_for_file(path)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+pub(crate) fn theme() -> &'static Theme {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES
+        .get_or_init(ThemeSet::load_defaults)
+        .themes
+        .get(THEME)
+        .expect("default theme set contains base16-ocean.dark")
+}
+
+/// Highlights whole lines written through it; the language comes from the
+/// file name (or its first line). Colors are reset before every newline so
+/// line number prefixes further down stay plain. Call `flush` at the end to
+/// emit a last line without newline.
+pub(crate) struct HighlightWriter<'a> {
+    inner: &'a mut dyn Write,
+    lines: HighlightLines<'static>,
+    pending: Vec<u8>,
+    /// Lines arrive already numbered (`prefix` included); keep that part plain.
+    prefix: Option<&'static [u8]>,
+}
+
+impl std::fmt::Debug for HighlightWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HighlightWriter").finish_non_exhaustive()
+    }
+}
+
+impl<'a> HighlightWriter<'a> {
+    pub(crate) fn new(
+        path: &Path,
+        inner: &'a mut dyn Write,
+        prefix: Option<&'static [u8]>,
+    ) -> Self {
+        Self {
+            inner,
+            lines: HighlightLines::new(syntax_for(path), theme()),
+            pending: Vec::new(),
+            prefix,
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let split = self.prefix.and_then(|prefix| {
+            line.windows(prefix.len())
+                .position(|w| w == prefix)
+                .map(|i| i + prefix.len())
+        });
+        let (number, line) = line.split_at(split.unwrap_or(0));
+        self.inner.write_all(number)?;
+
+        let text = String::from_utf8_lossy(line);
+        let ranges = self
+            .lines
+            .highlight_line(&text, syntax_set())
+            .map_err(io::Error::other)?;
+        let escaped = as_24_bit_terminal_escaped(&ranges, false);
+        let (body, newline) = match escaped.strip_suffix('\n') {
+            Some(body) => (body, true),
+            None => (escaped.as_str(), false),
+        };
+        self.inner.write_all(body.as_bytes())?;
+        self.inner.write_all(RESET)?;
+        if newline {
+            self.inner.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for HighlightWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = memchr::memchr(b'\n', &self.pending) {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.write_line(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.write_line(&line)?;
+        }
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,182 @@
+//! `--format html`: one self-contained page with a collapsible file tree and
+//! highlighted content sections.
+
+use crate::archive::archive_name;
+use crate::binary::write_binary;
+use crate::collector::{Entry, entry_size};
+use crate::highlight::{syntax_for, syntax_set, theme};
+use crate::options::{CollectOptions, EntryType, SortKey};
+use crate::output::{header_fields, read_shaped_text, truncation_note};
+use crate::redact::Redactor;
+use crate::tree::Node;
+use std::fs::File;
+use std::io::{self, Write};
+use syntect::html::highlighted_html_for_string;
+
+/// Inline so the page works offline and as a mail attachment.
+const STYLE: &str = "
+body { font: 14px/1.5 system-ui, sans-serif; margin: 0; display: flex; color: #222; }
+nav { position: sticky; top: 0; height: 100vh; overflow: auto; min-width: 16em;
+      max-width: 30%; padding: 1em; box-sizing: border-box; background: #f6f8fa;
+      border-right: 1px solid #ddd; font-family: ui-monospace, monospace; font-size: 13px; }
+nav ul { list-style: none; margin: 0; padding-left: 1.2em; }
+nav > ul { padding-left: 0; }
+nav summary { cursor: pointer; }
+nav a { color: #0550ae; text-decoration: none; }
+nav a:hover { text-decoration: underline; }
+main { flex: 1; min-width: 0; padding: 1em 2em; }
+h1 { font-size: 1.3em; }
+section { margin: 1em 0; border: 1px solid #ddd; border-radius: 6px; }
+section > details > summary { cursor: pointer; padding: .5em .8em; background: #f6f8fa;
+      font-family: ui-monospace, monospace; }
+.meta { color: #666; }
+pre { margin: 0; padding: .8em; overflow-x: auto; font-size: 13px; }
+";
+
+/// Renders the page: the tree in a side panel and, with `read_content`, one
+/// collapsible section per file. Returns the number of entries.
+pub(crate) fn write_html(
+    entries: impl Iterator<Item = Entry>,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+    writer: &mut dyn Write,
+) -> io::Result<usize> {
+    let entries: Vec<Entry> = entries.collect();
+    let mut root = Node::default();
+    let mut files = 0;
+    for (index, entry) in entries.iter().enumerate() {
+        let node = root.insert(&archive_name(entry.path(), options));
+        if options.types.contains(&EntryType::Dir) && entry.path().is_dir() {
+            node.is_dir = true;
+        } else {
+            node.index = Some(index);
+            files += 1;
+        }
+    }
+    if options.sort == SortKey::None {
+        root.sort();
+    }
+
+    let title = escape(&options.base_path.display().to_string());
+    writeln!(
+        writer,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>collect: {title}</title>\n<style>{STYLE}</style>\n</head>\n<body>"
+    )?;
+    writeln!(writer, "<nav>\n<ul>")?;
+    write_nodes(&root, options.read_content, writer)?;
+    writeln!(writer, "</ul>\n</nav>\n<main>")?;
+    writeln!(
+        writer,
+        "<h1>{title}</h1>\n<p class=\"meta\">{files} file{}</p>",
+        if files == 1 { "" } else { "s" }
+    )?;
+
+    if options.read_content {
+        for (index, entry) in entries.iter().enumerate() {
+            if entry.path().is_dir() {
+                continue;
+            }
+            write_section(entry, index, options, redactor, writer)?;
+        }
+    }
+    writeln!(writer, "</main>\n</body>\n</html>")?;
+    Ok(entries.len())
+}
+
+fn write_nodes(node: &Node, linked: bool, writer: &mut dyn Write) -> io::Result<()> {
+    for (name, child) in &node.children {
+        let name = escape(name);
+        if child.is_dir {
+            writeln!(writer, "<li><details open><summary>{name}/</summary><ul>")?;
+            write_nodes(child, linked, writer)?;
+            writeln!(writer, "</ul></details></li>")?;
+        } else {
+            match child.index.filter(|_| linked) {
+                Some(index) => writeln!(writer, "<li><a href=\"#f{index}\">{name}</a></li>")?,
+                None => writeln!(writer, "<li>{name}</li>")?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One file: a collapsible header with the path and requested fields, then
+/// the content (highlighted text, or the `--binary` rendering).
+fn write_section(
+    entry: &Entry,
+    index: usize,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let path = entry.path();
+    let fields = header_fields(entry, options);
+    let meta = if fields.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " <span class=\"meta\">[{}]</span>",
+            escape(&fields.join(", "))
+        )
+    };
+    writeln!(
+        writer,
+        "<section id=\"f{index}\"><details open><summary>{}{meta}</summary>",
+        escape(&archive_name(path, options))
+    )?;
+
+    let source = entry.content_path();
+    let body = match entry.content_marker() {
+        Some(marker) => Ok(Some(marker)),
+        None => read_shaped_text(source, options, redactor),
+    };
+    // Unreadable files, text or binary, get a note rather than ending the report
+    let body = body.and_then(|text| match text {
+        Some(text) => {
+            Ok(
+                highlighted_html_for_string(&text, syntax_set(), syntax_for(path), theme())
+                    .unwrap_or_else(|_| format!("<pre>{}</pre>", escape(&text))),
+            )
+        }
+        None => {
+            let mut dump = Vec::new();
+            write_binary(
+                File::open(source)?,
+                &mut dump,
+                options.binary,
+                options.max_bytes,
+                false,
+            )?;
+            Ok(format!(
+                "<pre>{}</pre>",
+                escape(&String::from_utf8_lossy(&dump))
+            ))
+        }
+    });
+    let body = body.unwrap_or_else(|e| {
+        format!(
+            "<pre class=\"meta\">Error reading file: {}</pre>",
+            escape(&e.to_string())
+        )
+    });
+    writer.write_all(body.as_bytes())?;
+    if let Some(note) = truncation_note(entry_size(entry), options) {
+        writeln!(writer, "<pre class=\"meta\">{}</pre>", escape(&note))?;
+    }
+    writeln!(writer, "</details></section>")
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,449 @@
+//! `--image-meta`: format, dimensions and the main EXIF fields (camera,
+//! capture time, GPS position) of image files, read from their headers
+//! without decoding any pixels.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Headers and EXIF blocks sit near the start of the file.
+const READ_LIMIT: u64 = 1 << 20;
+
+/// What `--image-meta` reports for one image.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ImageMeta {
+    /// `png`, `jpeg`, `gif`, `webp`, `bmp` or `tiff`.
+    pub(crate) format: &'static str,
+    /// Width and height in pixels, when the header was readable.
+    pub(crate) dimensions: Option<(u32, u32)>,
+    /// EXIF make and model (`Canon EOS 5D`).
+    pub(crate) camera: Option<String>,
+    /// EXIF capture time, `YYYY-MM-DDTHH:MM:SS` in the camera's local time.
+    pub(crate) taken: Option<String>,
+    /// Latitude and longitude in decimal degrees.
+    pub(crate) gps: Option<(f64, f64)>,
+}
+
+/// The metadata of the image at `path`; `None` when it is not an image in
+/// one of the known formats.
+pub(crate) fn image_meta(path: &Path) -> io::Result<Option<ImageMeta>> {
+    let mut data = Vec::new();
+    File::open(path)?.take(READ_LIMIT).read_to_end(&mut data)?;
+    Ok(parse(&data))
+}
+
+/// Whether `path` starts like an image `image_meta` can read.
+pub(crate) fn is_image(path: &Path) -> io::Result<bool> {
+    let mut head = [0u8; 18];
+    let n = File::open(path)?.read(&mut head)?;
+    Ok(format_of(head.get(..n).unwrap_or_default()).is_some())
+}
+
+fn format_of(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if head.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("jpeg")
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        Some("webp")
+    } else if head.starts_with(b"BM")
+        // "BM" alone is too common: the DIB header size must be a known one
+        && matches!(le32(head, 14), Some(12 | 40 | 52 | 56 | 64 | 108 | 124))
+    {
+        Some("bmp")
+    } else if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+        Some("tiff")
+    } else {
+        None
+    }
+}
+
+fn parse(data: &[u8]) -> Option<ImageMeta> {
+    let format = format_of(data)?;
+    let (dimensions, exif) = match format {
+        "png" => png(data),
+        "jpeg" => jpeg(data),
+        "gif" => (Some((le16(data, 6)?.into(), le16(data, 8)?.into())), None),
+        "webp" => webp(data),
+        "bmp" => (bmp(data), None),
+        _ => {
+            let exif = exif(data);
+            (exif.as_ref().and_then(|e| e.dimensions), exif)
+        }
+    };
+    let exif = exif.unwrap_or_default();
+    Some(ImageMeta {
+        format,
+        dimensions: dimensions.or(exif.dimensions),
+        camera: exif.camera(),
+        taken: exif.taken,
+        gps: exif.gps,
+    })
+}
+
+type Parsed = (Option<(u32, u32)>, Option<Exif>);
+
+/// IHDR comes first; an `eXIf` chunk, if any, before the image data.
+fn png(data: &[u8]) -> Parsed {
+    let dimensions = be32(data, 16).zip(be32(data, 20));
+    let mut exif = None;
+    let mut pos = 8;
+    while let (Some(len), Some(kind)) = (be32(data, pos), data.get(pos + 4..pos + 8)) {
+        let Ok(len) = usize::try_from(len) else {
+            break;
+        };
+        match kind {
+            b"eXIf" => {
+                exif = data.get(pos + 8..pos + 8 + len).and_then(self::exif);
+                break;
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => pos += 12 + len,
+        }
+    }
+    (dimensions, exif)
+}
+
+/// Walks the marker segments up to the scan: the APP1 `Exif` block and the
+/// start-of-frame header with the size.
+fn jpeg(data: &[u8]) -> Parsed {
+    let mut dimensions = None;
+    let mut exif = None;
+    let mut pos = 2;
+    while data.get(pos) == Some(&0xff) {
+        let Some(&marker) = data.get(pos + 1) else {
+            break;
+        };
+        match marker {
+            // Fill byte
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            // Markers without a length
+            0x01 | 0xd0..=0xd8 => {
+                pos += 2;
+                continue;
+            }
+            // End of image, start of scan
+            0xd9 | 0xda => break,
+            _ => {}
+        }
+        let Some(len) = be16(data, pos + 2).map(usize::from) else {
+            break;
+        };
+        let segment = data.get(pos + 4..pos + 2 + len).unwrap_or_default();
+        match marker {
+            0xe1 if exif.is_none() => {
+                exif = segment.strip_prefix(b"Exif\0\0").and_then(self::exif);
+            }
+            // SOF0..SOF15, except DHT (c4), JPG (c8) and DAC (cc)
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                dimensions = be16(segment, 3)
+                    .zip(be16(segment, 1))
+                    .map(|(w, h)| (w.into(), h.into()));
+            }
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+    (dimensions, exif)
+}
+
+/// RIFF chunks: the size from `VP8 ` (lossy), `VP8L` (lossless) or `VP8X`
+/// (extended, which may be followed by an `EXIF` chunk).
+fn webp(data: &[u8]) -> Parsed {
+    let mut dimensions = None;
+    let mut exif = None;
+    let mut pos = 12;
+    while let (Some(kind), Some(len)) = (data.get(pos..pos + 4), le32(data, pos + 4)) {
+        let Ok(len) = usize::try_from(len) else {
+            break;
+        };
+        let chunk = data.get(pos + 8..pos + 8 + len).unwrap_or_default();
+        match kind {
+            b"VP8 " if dimensions.is_none() => {
+                dimensions = le16(chunk, 6)
+                    .zip(le16(chunk, 8))
+                    .map(|(w, h)| (u32::from(w & 0x3fff), u32::from(h & 0x3fff)));
+            }
+            b"VP8L" if dimensions.is_none() && chunk.first() == Some(&0x2f) => {
+                dimensions =
+                    le32(chunk, 1).map(|bits| ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1));
+            }
+            b"VP8X" => {
+                dimensions = le24(chunk, 4)
+                    .zip(le24(chunk, 7))
+                    .map(|(w, h)| (w + 1, h + 1));
+            }
+            b"EXIF" => {
+                exif = self::exif(chunk.strip_prefix(b"Exif\0\0").unwrap_or(chunk));
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        pos += 8 + len + (len & 1);
+    }
+    (dimensions, exif)
+}
+
+/// `BITMAPINFOHEADER` and later store signed 32-bit sizes (a negative
+/// height is a top-down bitmap); the old OS/2 header 16-bit ones.
+fn bmp(data: &[u8]) -> Option<(u32, u32)> {
+    if le32(data, 14)? == 12 {
+        return Some((le16(data, 18)?.into(), le16(data, 20)?.into()));
+    }
+    let signed = |at| le32(data, at).map(|v| i32::from_le_bytes(v.to_le_bytes()).unsigned_abs());
+    Some((signed(18)?, signed(22)?))
+}
+
+/// The EXIF fields kept, from a TIFF structure (what JPEG APP1, PNG `eXIf`
+/// and WebP `EXIF` carry, and what a TIFF file is).
+#[derive(Debug, Default)]
+struct Exif {
+    make: Option<String>,
+    model: Option<String>,
+    taken: Option<String>,
+    gps: Option<(f64, f64)>,
+    dimensions: Option<(u32, u32)>,
+}
+
+impl Exif {
+    /// `Canon EOS 5D` rather than `Canon Canon EOS 5D`.
+    fn camera(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{make} {model}")),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+}
+
+const TAG_WIDTH: u16 = 0x0100;
+const TAG_HEIGHT: u16 = 0x0101;
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_GPS_LATITUDE_REF: u16 = 1;
+const TAG_GPS_LATITUDE: u16 = 2;
+const TAG_GPS_LONGITUDE_REF: u16 = 3;
+const TAG_GPS_LONGITUDE: u16 = 4;
+
+fn exif(data: &[u8]) -> Option<Exif> {
+    let tiff = Tiff {
+        data,
+        big_endian: match data.get(..4)? {
+            b"II*\0" => false,
+            b"MM\0*" => true,
+            _ => return None,
+        },
+    };
+    let ifd0 = tiff.ifd(tiff.u32(4)?);
+    let mut exif = Exif {
+        make: ifd0.find(TAG_MAKE).and_then(|e| tiff.ascii(e)),
+        model: ifd0.find(TAG_MODEL).and_then(|e| tiff.ascii(e)),
+        ..Exif::default()
+    };
+    exif.dimensions = ifd0
+        .find(TAG_WIDTH)
+        .and_then(|e| tiff.number(e))
+        .zip(ifd0.find(TAG_HEIGHT).and_then(|e| tiff.number(e)));
+
+    let original = ifd0
+        .find(TAG_EXIF_IFD)
+        .and_then(|e| tiff.number(e))
+        .map(|offset| tiff.ifd(offset))
+        .and_then(|ifd| tiff.ascii(ifd.find(TAG_DATE_TIME_ORIGINAL)?));
+    exif.taken = original
+        .or_else(|| ifd0.find(TAG_DATE_TIME).and_then(|e| tiff.ascii(e)))
+        .map(|time| iso_time(&time));
+
+    if let Some(gps) = ifd0
+        .find(TAG_GPS_IFD)
+        .and_then(|e| tiff.number(e))
+        .map(|offset| tiff.ifd(offset))
+    {
+        let coordinate = |value, reference, negative| {
+            let degrees = degrees(&tiff.rationals(gps.find(value)?))?;
+            let sign = match gps.find(reference).and_then(|e| tiff.ascii(e)) {
+                Some(r) if r == negative => -1.0,
+                _ => 1.0,
+            };
+            Some(sign * degrees)
+        };
+        exif.gps = coordinate(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S").zip(coordinate(
+            TAG_GPS_LONGITUDE,
+            TAG_GPS_LONGITUDE_REF,
+            "W",
+        ));
+    }
+    Some(exif)
+}
+
+/// Degrees, minutes and seconds as decimal degrees.
+fn degrees(dms: &[f64]) -> Option<f64> {
+    match dms {
+        [d, m, s, ..] => Some(d + m / 60.0 + s / 3600.0),
+        [d, m] => Some(d + m / 60.0),
+        [d] => Some(*d),
+        [] => None,
+    }
+}
+
+/// `2024:05:01 10:30:00` -> `2024-05-01T10:30:00`; anything else as is.
+fn iso_time(time: &str) -> String {
+    match time.split_once(' ') {
+        Some((date, clock)) if date.len() == 10 && date.matches(':').count() == 2 => {
+            format!("{}T{clock}", date.replace(':', "-"))
+        }
+        _ => time.to_string(),
+    }
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+/// One 12-byte IFD entry.
+struct Field {
+    kind: u16,
+    count: u32,
+    /// Where the value is: inline in the entry when it fits in 4 bytes.
+    at: usize,
+}
+
+/// The entries of one IFD.
+struct Ifd(Vec<(u16, Field)>);
+
+impl Ifd {
+    fn find(&self, tag: u16) -> Option<&Field> {
+        self.0
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, field)| field)
+    }
+}
+
+impl Tiff<'_> {
+    fn u16(&self, at: usize) -> Option<u16> {
+        if self.big_endian {
+            be16(self.data, at)
+        } else {
+            le16(self.data, at)
+        }
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        if self.big_endian {
+            be32(self.data, at)
+        } else {
+            le32(self.data, at)
+        }
+    }
+
+    fn ifd(&self, offset: u32) -> Ifd {
+        let mut fields = Vec::new();
+        let Ok(start) = usize::try_from(offset) else {
+            return Ifd(fields);
+        };
+        let count = self.u16(start).unwrap_or(0);
+        for i in 0..usize::from(count) {
+            let entry = start + 2 + i * 12;
+            let (Some(tag), Some(kind), Some(count)) =
+                (self.u16(entry), self.u16(entry + 2), self.u32(entry + 4))
+            else {
+                break;
+            };
+            let size = u64::from(type_size(kind)) * u64::from(count);
+            let at = if size <= 4 {
+                Some(entry + 8)
+            } else {
+                self.u32(entry + 8).and_then(|o| usize::try_from(o).ok())
+            };
+            if let Some(at) = at {
+                fields.push((tag, Field { kind, count, at }));
+            }
+        }
+        Ifd(fields)
+    }
+
+    fn ascii(&self, field: &Field) -> Option<String> {
+        if field.kind != 2 {
+            return None;
+        }
+        let len = usize::try_from(field.count).ok()?;
+        let bytes = self.data.get(field.at..field.at + len)?;
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// A SHORT or LONG value.
+    fn number(&self, field: &Field) -> Option<u32> {
+        match field.kind {
+            3 => self.u16(field.at).map(u32::from),
+            4 => self.u32(field.at),
+            _ => None,
+        }
+    }
+
+    fn rationals(&self, field: &Field) -> Vec<f64> {
+        if field.kind != 5 {
+            return Vec::new();
+        }
+        (0..usize::try_from(field.count).unwrap_or(0))
+            .map_while(|i| {
+                let at = field.at + i * 8;
+                let (n, d) = (self.u32(at)?, self.u32(at + 4)?);
+                Some(if d == 0 {
+                    0.0
+                } else {
+                    f64::from(n) / f64::from(d)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Bytes per value of a TIFF field type (BYTE, ASCII, SHORT, LONG,
+/// RATIONAL...).
+fn type_size(kind: u16) -> u32 {
+    match kind {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    }
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, &b| acc << 8 | u32::from(b)),
+    )
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
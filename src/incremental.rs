@@ -0,0 +1,90 @@
+//! `--incremental`: the size and mtime of every collected path, so the next
+//! run only emits what is new or changed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const STATE_VERSION: u32 = 1;
+
+/// Size and modification time of one path (nanoseconds since the epoch).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Stamp {
+    size: u64,
+    mtime: u64,
+}
+
+impl Stamp {
+    pub(crate) fn of(meta: &Metadata) -> Self {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .and_then(|d| u64::try_from(d.as_nanos()).ok())
+            .unwrap_or(0);
+        Self {
+            size: meta.len(),
+            mtime,
+        }
+    }
+}
+
+/// Stamps keyed by path relative to the base path, `/` separated.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct State {
+    version: u32,
+    pub(crate) files: BTreeMap<String, Stamp>,
+}
+
+impl State {
+    /// A missing file is an empty state: everything counts as new.
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open state file {}", path.display()));
+            }
+        };
+        let state: Self = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid state file {}", path.display()))?;
+        if state.version != STATE_VERSION {
+            anyhow::bail!(
+                "Unsupported state file version {} in {}",
+                state.version,
+                path.display()
+            );
+        }
+        Ok(state)
+    }
+
+    pub(crate) fn new(files: BTreeMap<String, Stamp>) -> Self {
+        Self {
+            version: STATE_VERSION,
+            files,
+        }
+    }
+
+    /// `true` when `name` was recorded with exactly this stamp.
+    pub(crate) fn unchanged(&self, name: &str, stamp: Stamp) -> bool {
+        self.files.get(name) == Some(&stamp)
+    }
+
+    /// Written next to `path` and renamed over it, so an interrupted save
+    /// keeps the previous state.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        fs::rename(&temp, path)
+    }
+}
@@ -0,0 +1,267 @@
+//! Lines of code per language for `--stats-by-language`.
+
+use crate::options::BinaryCheck;
+use crate::search::scan_file;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Comment and string syntax of a language, found by extension or file name.
+pub(crate) struct Language {
+    pub(crate) name: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    pub(crate) line: &'static [&'static str],
+    pub(crate) block: Option<(&'static str, &'static str)>,
+    /// String delimiters (`"` unless set with `quotes`).
+    pub(crate) quotes: &'static [u8],
+    /// Rust's `r#"..."#` raw strings and `'c'` char literals next to lifetimes.
+    pub(crate) raw_strings: bool,
+}
+
+const C_STYLE: Option<(&str, &str)> = Some(("/*", "*/"));
+const MARKUP: Option<(&str, &str)> = Some(("<!--", "-->"));
+
+const LANGUAGES: &[Language] = &[
+    lang("Rust", &["rs"], &[], &["//"], C_STYLE).raw_strings(),
+    lang("C", &["c", "h"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang(
+        "C++",
+        &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+        &[],
+        &["//"],
+        C_STYLE,
+    )
+    .quotes(b"\"'"),
+    lang("C#", &["cs", "csx"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Go", &["go"], &[], &["//"], C_STYLE).quotes(b"\"'`"),
+    lang("Java", &["java"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Kotlin", &["kt", "kts"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Scala", &["scala", "sc"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Swift", &["swift"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Dart", &["dart"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang(
+        "JavaScript",
+        &["js", "jsx", "mjs", "cjs"],
+        &[],
+        &["//"],
+        C_STYLE,
+    )
+    .quotes(b"\"'`"),
+    lang(
+        "TypeScript",
+        &["ts", "tsx", "mts", "cts"],
+        &[],
+        &["//"],
+        C_STYLE,
+    )
+    .quotes(b"\"'`"),
+    lang("PHP", &["php"], &[], &["//", "#"], C_STYLE).quotes(b"\"'"),
+    lang("CSS", &["css"], &[], &[], C_STYLE).quotes(b"\"'"),
+    lang("SCSS", &["scss", "sass", "less"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("Zig", &["zig"], &[], &["//"], None).quotes(b"\"'"),
+    lang("Python", &["py", "pyi"], &[], &["#"], None).quotes(b"\"'"),
+    lang(
+        "Ruby",
+        &["rb", "rake", "gemspec"],
+        &["Rakefile", "Gemfile"],
+        &["#"],
+        None,
+    )
+    .quotes(b"\"'"),
+    lang("Perl", &["pl", "pm"], &[], &["#"], None).quotes(b"\"'"),
+    lang(
+        "Shell",
+        &["sh", "bash", "zsh", "fish", "ksh"],
+        &[],
+        &["#"],
+        None,
+    )
+    .quotes(b"\"'"),
+    lang(
+        "PowerShell",
+        &["ps1", "psm1"],
+        &[],
+        &["#"],
+        Some(("<#", "#>")),
+    )
+    .quotes(b"\"'"),
+    lang(
+        "Makefile",
+        &["mk"],
+        &["Makefile", "makefile", "GNUmakefile"],
+        &["#"],
+        None,
+    )
+    .quotes(b"\"'"),
+    lang(
+        "Dockerfile",
+        &[],
+        &["Dockerfile", "Containerfile"],
+        &["#"],
+        None,
+    )
+    .quotes(b"\"'"),
+    lang("CMake", &["cmake"], &["CMakeLists.txt"], &["#"], None).quotes(b"\"'"),
+    lang("TOML", &["toml"], &[], &["#"], None).quotes(b"\"'"),
+    lang("YAML", &["yaml", "yml"], &[], &["#"], None).quotes(b"\"'"),
+    lang("INI", &["ini", "cfg", "conf"], &[], &["#", ";"], None).quotes(b"\"'"),
+    lang("R", &["r"], &[], &["#"], None).quotes(b"\"'"),
+    lang("Elixir", &["ex", "exs"], &[], &["#"], None).quotes(b"\"'"),
+    lang("Nix", &["nix"], &[], &["#"], C_STYLE).quotes(b"\"'"),
+    lang("Lua", &["lua"], &[], &["--"], Some(("--[[", "]]"))).quotes(b"\"'"),
+    lang("SQL", &["sql"], &[], &["--"], C_STYLE).quotes(b"\"'"),
+    lang("Haskell", &["hs"], &[], &["--"], Some(("{-", "-}"))).quotes(b"\"'"),
+    lang("Elm", &["elm"], &[], &["--"], Some(("{-", "-}"))).quotes(b"\"'"),
+    lang("OCaml", &["ml", "mli"], &[], &[], Some(("(*", "*)"))).quotes(b"\"'"),
+    lang("Erlang", &["erl", "hrl"], &[], &["%"], None).quotes(b"\"'"),
+    lang("TeX", &["tex", "sty"], &[], &["%"], None),
+    lang(
+        "Lisp",
+        &["lisp", "el", "clj", "cljs", "scm"],
+        &[],
+        &[";"],
+        None,
+    ),
+    lang("Vim script", &["vim"], &[".vimrc"], &["\""], None),
+    lang("HTML", &["html", "htm", "xhtml"], &[], &[], MARKUP).quotes(b"\"'"),
+    lang("XML", &["xml", "xsd", "xsl", "svg"], &[], &[], MARKUP).quotes(b"\"'"),
+    lang("Vue", &["vue"], &[], &["//"], MARKUP).quotes(b"\"'`"),
+    lang("Svelte", &["svelte"], &[], &["//"], MARKUP).quotes(b"\"'`"),
+    lang("Markdown", &["md", "mdx", "markdown"], &[], &[], MARKUP).quotes(b""),
+    lang("reStructuredText", &["rst"], &[], &[], None),
+    lang("JSON", &["json"], &[], &[], None),
+    lang("Protobuf", &["proto"], &[], &["//"], C_STYLE).quotes(b"\"'"),
+    lang("GraphQL", &["graphql", "gql"], &[], &["#"], None).quotes(b"\"'"),
+    lang("Text", &["txt"], &[], &[], None),
+];
+
+const fn lang(
+    name: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+) -> Language {
+    Language {
+        name,
+        extensions,
+        file_names,
+        line,
+        block,
+        quotes: b"\"",
+        raw_strings: false,
+    }
+}
+
+impl Language {
+    const fn quotes(mut self, quotes: &'static [u8]) -> Self {
+        self.quotes = quotes;
+        self
+    }
+
+    const fn raw_strings(mut self) -> Self {
+        self.raw_strings = true;
+        self
+    }
+}
+
+/// Files without a known language (no comment syntax: lines are code or blank).
+const OTHER: Language = lang("Other", &[], &[], &[], None);
+
+/// Totals for one language.
+#[derive(Clone, Debug, Default)]
+pub struct LanguageCount {
+    pub language: String,
+    pub files: usize,
+    pub lines: u64,
+    pub code: u64,
+    pub comments: u64,
+    pub blanks: u64,
+}
+
+impl LanguageCount {
+    pub(crate) fn add(&mut self, other: &LineCount) {
+        self.files += 1;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.lines += other.code + other.comments + other.blanks;
+    }
+}
+
+/// Line classes of one file.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LineCount {
+    code: u64,
+    comments: u64,
+    blanks: u64,
+}
+
+/// Language of a file name: exact names (`Makefile`) first, then the
+/// extension, case-insensitively. `Other` when neither is known.
+pub(crate) fn language_of(path: &Path) -> &'static Language {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    if let Some(language) = LANGUAGES
+        .iter()
+        .find(|l| l.file_names.contains(&name.as_ref()))
+    {
+        return language;
+    }
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    LANGUAGES
+        .iter()
+        .find(|l| l.extensions.contains(&extension.as_str()))
+        .unwrap_or(&OTHER)
+}
+
+/// Language name and line counts of a text file; `None` for binary files.
+///
+/// A line is a comment when it starts with a comment marker or lies inside
+/// a block comment; code that shares a line with a comment counts as code.
+/// String literals are not parsed, so a marker inside one can mislead it.
+pub(crate) fn count_lines(
+    path: &Path,
+    size: u64,
+    check: BinaryCheck,
+) -> io::Result<Option<(&'static str, LineCount)>> {
+    let language = language_of(path);
+    let mut count = LineCount::default();
+    let mut in_block = false;
+    let text = scan_file(File::open(path)?, size, check, |line| {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() {
+            count.blanks += 1;
+        } else if in_block {
+            count.comments += 1;
+            if let Some((_, close)) = language.block {
+                in_block = !line.contains(close);
+            }
+        } else if let Some((open, close)) = language.block
+            && let Some(rest) = line.strip_prefix(open)
+        {
+            count.comments += 1;
+            in_block = !rest.contains(close);
+        } else if language.line.iter().any(|marker| line.starts_with(marker)) {
+            count.comments += 1;
+        } else {
+            count.code += 1;
+            // A block opened after code continues on the next lines
+            if let Some((open, close)) = language.block
+                && let Some(start) = line.rfind(open)
+            {
+                let rest = line.get(start + open.len()..).unwrap_or_default();
+                in_block = !rest.contains(close);
+            }
+        }
+        true
+    })?;
+    Ok(text.then_some((language.name, count)))
+}
@@ -0,0 +1,124 @@
+//! Git LFS pointer files (`--lfs`): the three-line stand-ins git keeps in
+//! place of large files until `git lfs` replaces them with the content.
+
+use crate::units::format_size;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Pointer files are at most this long (git-lfs spec).
+const MAX_POINTER_LEN: u64 = 1024;
+
+const VERSION_LINE: &str = "version https://git-lfs.github.com/spec/v1\n";
+
+/// The object a pointer file stands for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LfsPointer {
+    /// `sha256:<hex>`.
+    pub(crate) oid: String,
+    /// Size of the real content in bytes.
+    pub(crate) size: u64,
+}
+
+impl LfsPointer {
+    /// What content output shows instead of the pointer text.
+    pub(crate) fn marker(&self) -> String {
+        format!(
+            "<Git LFS pointer: {}, {}>",
+            self.oid,
+            format_size(self.size)
+        )
+    }
+
+    /// The hex digest, without the `sha256:` prefix.
+    fn digest(&self) -> &str {
+        self.oid.strip_prefix("sha256:").unwrap_or(&self.oid)
+    }
+}
+
+/// Reads `path` as a pointer file; `None` for anything else. Only the first
+/// KiB is read, so large files cost one short read.
+pub(crate) fn read_pointer(path: &Path) -> io::Result<Option<LfsPointer>> {
+    let mut text = String::new();
+    let read = File::open(path)?
+        .take(MAX_POINTER_LEN + 1)
+        .read_to_string(&mut text);
+    // Not UTF-8, so not a pointer
+    if read.is_err() || u64::try_from(text.len()).map_or(true, |n| n > MAX_POINTER_LEN) {
+        return Ok(None);
+    }
+    Ok(parse_pointer(&text))
+}
+
+fn parse_pointer(text: &str) -> Option<LfsPointer> {
+    let rest = text.strip_prefix(VERSION_LINE)?;
+    let mut oid = None;
+    let mut size = None;
+    for line in rest.lines() {
+        let (key, value) = line.split_once(' ')?;
+        match key {
+            "oid" => oid = Some(value.to_string()),
+            "size" => size = Some(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    let oid = oid.filter(|oid| {
+        oid.strip_prefix("sha256:")
+            .is_some_and(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+    })?;
+    Some(LfsPointer { oid, size: size? })
+}
+
+/// The local copy of the object behind `pointer` (under
+/// `.git/lfs/objects`), fetched with `git lfs smudge` when it is not there
+/// yet. `None` when git-lfs is missing or cannot get it.
+pub(crate) fn smudge(path: &Path, pointer: &LfsPointer) -> Option<PathBuf> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let object = object_path(dir, pointer)?;
+    if object.is_file() {
+        return Some(object);
+    }
+
+    // Smudging stores the download in the object directory; the content it
+    // prints is read from there afterwards
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["lfs", "smudge", "--"])
+        .arg(path.file_name()?)
+        .stdin(File::open(path).ok()?)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?;
+    (status.success() && object.is_file()).then_some(object)
+}
+
+/// `<git dir>/lfs/objects/ab/cd/abcd...`, where git-lfs keeps the objects
+/// of the repository around `dir`.
+fn object_path(dir: &Path, pointer: &LfsPointer) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--path-format=absolute", "--git-common-dir"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let git_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end());
+    let digest = pointer.digest();
+    Some(
+        git_dir
+            .join("lfs")
+            .join("objects")
+            .join(digest.get(..2)?)
+            .join(digest.get(2..4)?)
+            .join(digest),
+    )
+}
@@ -0,0 +1,108 @@
+/*
+    Project: Retype15 Collect
+    Context: High-performance file traversal and filtering library.
+
+    Architecture:
+    1. Options (CollectOptions, plain data)
+    2. Filters (compiled once per Collector)
+    3. Traversal Engine (ignore crate wrapper, sequential or parallel)
+    4. Output (Entry -> text / JSON records)
+*/
+
+//! Embeddable traversal + filter pipeline behind the `collect` CLI.
+//!
+//! ```no_run
+//! use collect::{CollectOptions, Collector};
+//!
+//! let options = CollectOptions {
+//!     extensions: Some(vec!["rs".into()]),
+//!     ..CollectOptions::default()
+//! };
+//! let collector = Collector::new(options)?;
+//! for entry in collector.entries()? {
+//!     println!("{}", entry.path().display());
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+mod ads;
+mod archive;
+mod binary;
+#[cfg(feature = "tui")]
+mod browse;
+mod checkpoint;
+mod chunk;
+mod collector;
+mod color;
+mod documents;
+mod errors;
+mod exec;
+mod expr;
+mod filter;
+mod frontmatter;
+mod generated;
+mod git;
+mod hash;
+mod highlight;
+mod html;
+mod image;
+mod incremental;
+mod languages;
+mod lfs;
+mod manifest;
+#[cfg(feature = "serve")]
+mod mcp;
+mod metadata;
+mod mime;
+mod minify;
+mod notebook;
+mod options;
+mod output;
+mod owners;
+mod paths;
+mod redact;
+mod search;
+mod secrets;
+#[cfg(feature = "serve")]
+mod serve;
+mod template;
+mod todos;
+mod tokens;
+mod transfer;
+mod tree;
+mod units;
+mod unpack;
+mod uring;
+#[cfg(feature = "serve")]
+mod watch;
+mod xattrs;
+
+#[cfg(feature = "tui")]
+pub use browse::browse;
+pub use checkpoint::Checkpoint;
+pub use collector::{
+    Collector, Entries, Entry, ExtensionCount, Limit, Progress, SplitSummary, Summary, Usage,
+};
+pub use exec::{CommandTemplate, ExecSummary};
+pub use frontmatter::FrontMatterFilter;
+pub use git::GitCommit;
+pub use languages::LanguageCount;
+pub use manifest::{Manifest, ManifestDiff, ManifestEntry};
+#[cfg(feature = "serve")]
+pub use mcp::mcp;
+pub use metadata::format_time;
+pub use options::{
+    Backend, BinaryCheck, BinaryMode, BudgetStrategy, CollectOptions, ColorChoice, Column,
+    Compression, EntryType, GitSelection, HashAlgorithm, LfsMode, LineEnding, LineRange, MetaField,
+    NotebookMode, OutputFormat, PathStyle, RegexMode, Scope, SortKey, SymlinkMode,
+};
+pub use paths::PathRewrite;
+pub use search::LineMatch;
+pub use secrets::Finding;
+#[cfg(feature = "serve")]
+pub use serve::serve;
+pub use todos::{DEFAULT_TODO_MARKERS, Todo};
+pub use transfer::{TransferMode, TransferSummary};
+pub use units::{format_size, parse_line_range, parse_size, parse_time};
+#[cfg(feature = "serve")]
+pub use watch::on_changes;
@@ -4,21 +4,33 @@
 
     Architecture:
     1. CLI Parsing (Clap)
-    2. Configuration Builder (Domain Logic)
-    3. Traversal Engine (ignore crate wrapper)
-    4. Pipeline Processor (Filter -> Stream -> Output)
+    2. Config Files (.collect.toml -> default CLI arguments)
+    3. Configuration Builder (Cli -> CollectOptions)
+    4. Collector (library: traversal, filters, output)
 */
 
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
-use ignore::{WalkBuilder, overrides::OverrideBuilder};
-use memchr::memchr;
-use regex::Regex;
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use clap::builder::Resettable;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use collect::{
+    Backend, BinaryCheck, BinaryMode, BudgetStrategy, Checkpoint, CollectOptions, Collector,
+    ColorChoice, Column, CommandTemplate, Compression, DEFAULT_TODO_MARKERS, EntryType,
+    ExtensionCount, Finding, FrontMatterFilter, GitSelection, HashAlgorithm, LanguageCount,
+    LfsMode, LineEnding, LineRange, Manifest, ManifestDiff, MetaField, NotebookMode, OutputFormat,
+    PathRewrite, PathStyle, Progress, RegexMode, Scope, SortKey, Summary, SymlinkMode, Todo,
+    TransferMode, Usage, format_size, format_time, parse_line_range, parse_size, parse_time,
+};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{Level, LevelFilter};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::process::ExitCode;
+use std::sync::{Mutex, PoisonError, mpsc};
+use std::time::{Duration, SystemTime};
 
 // =============================================================================
 // MODULE: CLI DEFINITIONS
@@ -30,17 +42,137 @@ use std::time::Instant;
     author = "Retype15",
     version = "1.1.0",
     about = "Optimized file collector and filtering tool.",
-    long_about = "Traverses directory trees respecting gitignore, applies filters, and optionally captures content."
+    long_about = "Traverses directory trees respecting gitignore, applies filters, and optionally captures content.",
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    args: CollectArgs,
+
+    /// Show usage guide.
+    #[arg(long)]
+    guide: bool,
+}
+
+/// Everything that shapes a collection. `collect [OPTIONS]` takes all of it;
+/// the subcommands take the same set minus the flags they decide themselves.
+#[derive(clap::Args, Debug)]
+struct CollectArgs {
     /// Base directory to start searching from.
     #[arg(long, default_value = ".")]
     path: PathBuf,
 
+    /// Read the paths to process from FILE (one per line, - for stdin) instead of
+    /// walking --path. Filters still apply; ignore files and hidden rules do not.
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
     /// Include file content in the output.
     #[arg(long)]
     content: bool,
 
+    /// How binary files are handled: suppress (marker only), skip (leave them out),
+    /// hex (dump, up to --max-bytes or 4 KiB), base64, or raw bytes.
+    #[arg(long, value_enum, default_value_t = BinaryMode::Suppress)]
+    binary: BinaryMode,
+
+    /// How binary files are detected: loose (known signatures or mostly control
+    /// characters; UTF-16 with a BOM is decoded), strict (also any null byte),
+    /// or off (everything is text).
+    #[arg(long, value_enum, default_value_t = BinaryCheck::Loose)]
+    binary_check: BinaryCheck,
+
+    /// Git LFS pointer files in content output: flag (a marker with the
+    /// object id and size), skip (left out), smudge (the real content, via
+    /// git lfs) or keep (the pointer text).
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = LfsMode::Flag)]
+    lfs: LfsMode,
+
+    /// Jupyter notebooks (.ipynb) in content output: raw (the JSON as
+    /// stored), code (the code cells only) or all (code and markdown cells,
+    /// each after a "# %%" marker line). Outputs and metadata are dropped
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = NotebookMode::Raw)]
+    notebook: NotebookMode,
+
+    /// Syntax-highlight --content with ANSI colors. Only applies when colors
+    /// are enabled (see --color).
+    #[arg(long, requires = "content")]
+    highlight: bool,
+
+    /// Color listed paths by type and extension (LS_COLORS): auto (terminal only,
+    /// unless NO_COLOR is set), always or never. Also gates --highlight.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Prefix each line of --content with its (padded) line number.
+    #[arg(long, requires = "content")]
+    line_numbers: bool,
+
+    /// Mask matches of this regex in emitted content (repeatable), e.g. an email
+    /// or token pattern. Applied line by line; archives are left unchanged.
+    #[arg(long, value_name = "REGEX")]
+    redact: Vec<String>,
+
+    /// Replacement for --redact matches (default: ███).
+    #[arg(long, value_name = "TEXT", requires = "redact")]
+    redact_with: Option<String>,
+
+    /// Drop comments from emitted content, using each file's language syntax
+    /// (// and /* */ for Rust, # for Python, ...). Strings are left intact;
+    /// lines that held only a comment are removed.
+    #[arg(long, requires = "content")]
+    strip_comments: bool,
+
+    /// Collapse runs of blank lines in emitted content into a single one.
+    #[arg(long, requires = "content")]
+    squeeze_blank: bool,
+
+    /// Emit every line ending of text content as lf or crlf, whatever mix
+    /// the file has
+    #[arg(long, value_enum, value_name = "EOL", requires = "content")]
+    normalize_eol: Option<LineEnding>,
+
+    /// Drop the UTF-8 byte order mark at the start of text content
+    #[arg(long, requires = "content")]
+    strip_bom: bool,
+
+    /// Cut content lines longer than SIZE bytes (e.g. 4K), such as minified
+    /// code or base64 blobs; a "<line truncated: N of M bytes>" marker
+    /// replaces the rest of the line
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "content")]
+    max_line_bytes: Option<u64>,
+
+    /// Show a "<Generated file (...), content skipped>" marker instead of the
+    /// content of lockfiles, source maps, minified scripts and stylesheets,
+    /// and files with an @generated or DO NOT EDIT header; they are still
+    /// listed
+    #[arg(long)]
+    skip_generated: bool,
+
+    /// Only emit the first N lines of each file's content (a marker notes the rest).
+    #[arg(long, value_name = "N", requires = "content")]
+    head_lines: Option<usize>,
+
+    /// Only emit the last N lines of each file's content. With --head-lines,
+    /// both ends are kept and the middle is replaced by a marker.
+    #[arg(long, value_name = "N", requires = "content")]
+    tail_lines: Option<usize>,
+
+    /// Only emit these lines of each file's content: START:END (1-based,
+    /// inclusive; 100: or :50 are open-ended), or PATH:START:END for one file.
+    /// Repeatable; gaps are replaced by markers.
+    #[arg(
+        long,
+        value_name = "RANGE",
+        value_parser = parse_line_range,
+        requires = "content",
+        conflicts_with_all = ["head_lines", "tail_lines"]
+    )]
+    lines: Vec<LineRange>,
+
     /// Filter by file extensions (comma separated, e.g., rs,toml).
     #[arg(long, value_delimiter = ',', group = "extension_filter")]
     extension: Option<Vec<String>>,
@@ -50,40 +182,215 @@ struct Cli {
     #[arg(long, value_delimiter = ',', group = "extension_filter")]
     no_extension: Option<Vec<String>>,
 
-    /// Regex pattern to apply.
+    /// Filter by named extension sets (comma separated, e.g. rust or web,docs),
+    /// added to --extension. See --type-list; define more with --type-add.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "NAME",
+        conflicts_with = "no_extension"
+    )]
+    type_alias: Vec<String>,
+
+    /// Define or extend a type alias: NAME:*.EXT,*.EXT (repeatable), e.g.
+    /// "web:*.html,*.css,*.js". Usually set as type_add in the config file.
+    #[arg(long, value_name = "DEFINITION")]
+    type_add: Vec<String>,
+
+    /// Print the type aliases (built-in and --type-add) and exit.
     #[arg(long)]
-    regex: Option<String>,
+    type_list: bool,
+
+    /// Regex pattern to apply (repeatable; see --regex-mode).
+    #[arg(long)]
+    regex: Vec<String>,
+
+    /// How repeated --regex patterns combine: any (at least one matches) or all.
+    #[arg(long, value_enum, default_value_t = RegexMode::Any)]
+    regex_mode: RegexMode,
 
-    /// Scope of the regex/pattern application.
+    /// What --regex matches: the file name, the relative path, or the file content
+    /// (text files only, up to --max-bytes).
     #[arg(long, value_enum, default_value_t = Scope::Name)]
     scope: Scope,
 
+    /// Match --regex and --pattern case-insensitively.
+    #[arg(long, short = 'i')]
+    ignore_case: bool,
+
+    /// Treat --regex and --pattern as literal text instead of regular expressions.
+    #[arg(long, short = 'F')]
+    fixed_strings: bool,
+
     /// Invert regex filter.
     #[arg(long)]
     regex_inv: bool,
 
-    // TODO Features
+    /// Only emit files whose content matches this pattern (literal text or regex).
     #[arg(long)]
     pattern: Option<String>,
+
+    /// Print the matching lines (with line numbers) of each --pattern match.
+    #[arg(long, requires = "pattern")]
+    show_matches: bool,
+
+    /// Only include files of at least this size (e.g. 512, 1K, 10M, 1.5G).
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// Only include files of at most this size (e.g. 512, 1K, 10M, 1.5G).
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only include files modified after this date or duration ago (e.g. 2024-01-01, 2d).
+    #[arg(long, value_parser = parse_time)]
+    newer_than: Option<SystemTime>,
+
+    /// Only include files modified before this date or duration ago (e.g. 2024-01-01, 4w).
+    #[arg(long, value_parser = parse_time)]
+    older_than: Option<SystemTime>,
+
+    /// Only include files modified within this duration (e.g. 2d, 3h30m). Same as --newer-than.
+    #[arg(long, value_parser = parse_time, conflicts_with = "newer_than")]
+    changed_within: Option<SystemTime>,
+
+    /// Only include files owned by this user (name or uid; "!deploy" for
+    /// everyone else). Unix only.
+    #[arg(long, value_name = "USER")]
+    owner: Option<String>,
+
+    /// Only include files of this group (name or gid; "!staff" for any other).
+    /// Unix only.
+    #[arg(long, value_name = "GROUP")]
+    group: Option<String>,
+
+    /// Only include files with these permission bits, as in find: 644 exactly,
+    /// -002 all of them, /111 any of them (octal). Unix only.
+    #[arg(long, value_name = "MODE", allow_hyphen_values = true)]
+    perm: Option<String>,
+
+    /// Filter expression, e.g. "ext == 'rs' && size > 10KB && path !~ 'tests/'"
+    /// (repeatable; all must match). See --guide for the syntax.
+    #[arg(long, value_name = "EXPR")]
+    filter: Vec<String>,
+
+    /// Only include files in these git sets (comma separated: tracked, modified,
+    /// staged, untracked). Needs git and a repository around --path.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    git: Option<Vec<GitSelection>>,
+
+    /// Only files changed since REF (e.g. origin/main): against the merge
+    /// base with HEAD, committed or not. Replaces the walk; other filters apply.
+    #[arg(long, value_name = "REF", conflicts_with_all = ["files_from", "checkpoint"])]
+    since_ref: Option<String>,
+
+    /// Leave out tracked paths marked export-ignore in .gitattributes, as
+    /// git archive does. Needs git and a repository around --path.
     #[arg(long)]
-    metadata: Option<String>,
+    export_ignore: bool,
+
+    /// Only emit these entry kinds (comma separated: f file, d directory, l symlink,
+    /// s special: FIFOs, sockets, devices). Default: everything but directories
+    /// and special files.
+    #[arg(long = "type", short = 't', value_enum, value_delimiter = ',')]
+    types: Option<Vec<EntryType>>,
+
+    /// Only include files whose MIME type, detected from their content, matches
+    /// (comma separated, e.g. image/*,application/pdf,text/x-python).
+    #[arg(long, value_delimiter = ',', value_name = "TYPES")]
+    mime: Vec<String>,
+
+    /// Only include Markdown files whose front matter passes: KEY=VALUE,
+    /// KEY!=VALUE or KEY (set and not false); dots reach nested keys, lists
+    /// match on any item (repeatable, all must pass; e.g. draft=false)
+    #[arg(long, value_name = "FILTER", value_parser = FrontMatterFilter::parse)]
+    frontmatter_filter: Vec<FrontMatterFilter>,
+
+    /// Print file metadata alongside each path (comma separated: size,mtime,ctime,
+    /// perms,owner,group,acl,context,type,mime).
+    #[arg(long, value_enum, value_delimiter = ',')]
+    metadata: Option<Vec<MetaField>>,
 
     /// Maximum search depth (0 = base only).
     #[arg(long)]
     depth: Option<usize>,
 
+    /// Minimum depth of emitted entries (1 = direct children of --path).
+    #[arg(long, value_name = "N")]
+    min_depth: Option<usize>,
+
     /// Explicitly exclude files/folders patterns (e.g., "target", "*.log").
     #[arg(long, value_delimiter = ',')]
     exclude: Option<Vec<String>>,
 
+    /// Read gitignore-style exclude patterns from FILE (repeatable, like rsync's --exclude-from).
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+
+    /// Include only paths matching this gitignore-style glob, relative to --path
+    /// (repeatable; prefix with ! to exclude, e.g. -g 'src/**/*.rs' -g '!**/tests/**').
+    #[arg(long, short = 'g')]
+    glob: Vec<String>,
+
     /// Disable default excludes (gitignore, hidden, etc).
     #[arg(long)]
     no_default_excludes: bool,
 
-    /// Follow symbolic links.
+    /// Follow symbolic links. Links back into a directory already walked
+    /// (cycles included) are skipped with a warning.
     #[arg(long)]
     follow_symlinks: bool,
 
+    /// Walk into zip, tar and tar.gz files: their members are filtered like
+    /// files and shown as archive.zip!/path/inside.
+    #[arg(long)]
+    archives: bool,
+
+    /// How symlinks appear: report (as the link, warning about broken ones),
+    /// skip (left out and never followed) or target (as the resolved path).
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = SymlinkMode::Report)]
+    symlinks: SymlinkMode,
+
+    /// Path separators in the output: native, forward (/) or backslash (\).
+    /// Canonical Windows paths are shown without their \\?\ prefix.
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = PathStyle::Native)]
+    windows_path_style: PathStyle,
+
+    /// Rewrite displayed paths with a sed-style expression, e.g.
+    /// 's/^src/lib/' or 's|^|vendor/|'; repeatable, applied in order.
+    #[arg(long, value_name = "EXPR", value_parser = PathRewrite::parse)]
+    path_rewrite: Vec<PathRewrite>,
+
+    /// Emit a file reached through several hardlinks once, with the other
+    /// paths listed as aliases (aliases=... in text, "aliases" in JSON).
+    #[arg(long)]
+    dedupe_hardlinks: bool,
+
+    /// Print the extended attributes of each file (name=value fields in text,
+    /// an "xattrs" object in JSON); binary values as base64:...
+    #[arg(long)]
+    xattrs: bool,
+
+    /// Print the format, dimensions and EXIF fields (camera, capture time,
+    /// GPS position) of image files (format=..., dimensions=WxH... fields in
+    /// text, an "image" object in JSON). With --binary skip, images are kept
+    #[arg(long)]
+    image_meta: bool,
+
+    /// List the NTFS alternate data streams of each file (ads=name(size) in
+    /// text, "streams" in JSON). Windows only.
+    #[arg(long)]
+    ads: bool,
+
+    /// Also print each alternate data stream's content after the file's own
+    /// (implies --ads).
+    #[arg(long, requires = "content")]
+    ads_content: bool,
+
+    /// Do not descend into other file systems (stay on the one holding --path).
+    #[arg(long, visible_alias = "one-file-system")]
+    same_file_system: bool,
+
     /// Include hidden files.
     #[arg(long)]
     include_hidden: bool,
@@ -92,245 +399,1497 @@ struct Cli {
     #[arg(long)]
     output: Option<PathBuf>,
 
-    /// Max bytes to read per file when using --content.
+    /// Append to the --output file instead of replacing it.
+    #[arg(long, requires = "output", conflicts_with = "atomic")]
+    append: bool,
+
+    /// Write --output to a temporary file next to it and rename it into place
+    /// on success, so readers never see a partial result.
+    #[arg(long, requires = "output")]
+    atomic: bool,
+
+    /// Record progress in FILE while writing --output. If FILE exists, resume:
+    /// the output is cut back to the last checkpointed record and the walk
+    /// continues after it. FILE is removed once the run completes.
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "output",
+        conflicts_with_all = ["atomic", "watch"]
+    )]
+    checkpoint: Option<PathBuf>,
+
+    /// Only emit files that are new or changed (size or mtime) since the last run
+    /// with the same STATE_FILE, which is created or updated after each run.
+    #[arg(long, value_name = "STATE_FILE")]
+    incremental: Option<PathBuf>,
+
+    /// Max bytes to read per file when using --content. Cut files end with a
+    /// "<truncated: N of M bytes>" marker ("truncated": true in JSON).
     #[arg(long)]
     max_bytes: Option<u64>,
 
+    /// Emit a content hash per file (sha256, blake3 or md5).
+    #[arg(long, value_enum)]
+    hash: Option<HashAlgorithm>,
+
+    /// Annotate each file with an estimated token count (~4 bytes per token).
+    #[arg(long)]
+    count_tokens: bool,
+
+    /// Annotate each file with its last commit: hash, author and commit date
+    /// (one git log for the whole run). Needs git and a repository around --path.
+    #[arg(long)]
+    git_meta: bool,
+
+    /// Emit the YAML (---) or TOML (+++) front matter of Markdown files
+    /// (one key=value field per top-level key in text, a "frontmatter" object
+    /// in JSON)
+    #[arg(long)]
+    frontmatter: bool,
+
+    /// Stop once the estimated token total would exceed N (implies --count-tokens).
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<u64>,
+
+    /// Stop after N matched files (the summary notes the early stop).
+    #[arg(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// Stop once the output reaches this size (e.g. 10M); records are never cut,
+    /// so the last one may cross it. Pair with --max-bytes for a hard bound.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_output_bytes: Option<u64>,
+
+    /// Write --output as numbered parts (OUTPUT.001, OUTPUT.002, ...) of at
+    /// most SIZE each (e.g. 100K), never cutting a file; text parts start with
+    /// a "part N of M" line.
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        requires = "output",
+        conflicts_with_all = [
+            "append", "atomic", "checkpoint", "compress", "summary_mode", "stats_by_language",
+            "report", "group_by", "scan_secrets", "todos", "snapshot", "diff", "exec_action",
+            "transfer"
+        ]
+    )]
+    split_bytes: Option<u64>,
+
+    /// Like --split-bytes, with at most N files per part (both can be given).
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "output",
+        conflicts_with_all = [
+            "append", "atomic", "checkpoint", "compress", "summary_mode", "stats_by_language",
+            "report", "group_by", "scan_secrets", "todos", "snapshot", "diff", "exec_action",
+            "transfer"
+        ]
+    )]
+    split_files: Option<usize>,
+
+    /// Emit each file's content as chunks of at most SIZE bytes (e.g. 2K),
+    /// cut at line breaks, one record each: path, chunk index, byte and line
+    /// range, text. For embedding / vector database ingestion; implies
+    /// --content and needs text, json or ndjson output
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    chunk_size: Option<u64>,
+
+    /// Bytes of whole lines each chunk repeats from the end of the previous
+    /// one, so context is not lost at the cut (must be below --chunk-size)
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        default_value = "0",
+        requires = "chunk_size"
+    )]
+    chunk_overlap: u64,
+
+    /// How --max-tokens enforces the budget: stop at the first file that doesn't
+    /// fit, or drop the largest files until the rest fits.
+    #[arg(long, value_enum, default_value_t = BudgetStrategy::Stop)]
+    budget_strategy: BudgetStrategy,
+
+    /// Output format: plain text blocks, a JSON array, one JSON record per line,
+    /// a CSV / TSV listing, a directory tree, or a tar / zip archive of the matched files.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Tar archives: store owners as uid / gid only, without user and group names.
+    #[arg(long)]
+    numeric_owner: bool,
+
+    /// Archives: store 0755 (owner-executable) or 0644 modes and root ownership
+    /// instead of the files' own, e.g. for bundles built from a restrictive umask.
+    #[arg(long, conflicts_with = "numeric_owner")]
+    no_perms: bool,
+
+    /// Columns for --format csv / tsv (comma separated: path, size, mtime, ext, hash, tokens, mime,
+    /// commit, author, committed).
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "path,size,mtime"
+    )]
+    columns: Vec<Column>,
+
+    /// Per-file text layout, e.g. "## {path} ({size} bytes)\n```{ext}\n{content}```\n".
+    /// Placeholders: path, name, ext, lang, size, mtime, hash, tokens, commit,
+    /// author, committed, depth, index, content. `{{` / `}}` are literal braces; \n and \t are escapes.
+    #[arg(long, value_name = "TEMPLATE")]
+    template: Option<String>,
+
+    /// Replace the "=== path ===" line of each content block, e.g.
+    /// '<file path="{path}" lang="{lang}">'. Same placeholders as --template
+    /// but content; the file content follows as usual.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        requires = "content",
+        conflicts_with = "template"
+    )]
+    header_template: Option<String>,
+
+    /// Text written once before all records of text output (\n and \t escapes).
+    #[arg(long, visible_alias = "preamble", value_name = "TEXT")]
+    template_header: Option<String>,
+
+    /// Text written once after all records of text output.
+    #[arg(long, visible_alias = "epilogue", value_name = "TEXT")]
+    template_footer: Option<String>,
+
+    /// Compress the output stream (any --format): gzip or zstd. Meant for
+    /// --output or redirection; --max-output-bytes counts uncompressed bytes.
+    #[arg(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Worker threads for traversal and content reading (0 = all cores).
+    /// With more than one thread, output is sorted by path to stay deterministic.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// I/O backend of the walk: std, or io-uring (experimental, Linux only)
+    /// to read the metadata and first bytes of each directory's entries
+    /// ahead in batches, for cold-cache scans of huge trees. Needs a build
+    /// with the io-uring feature; falls back to std where io_uring is
+    /// unavailable
+    #[arg(long, value_enum, value_name = "BACKEND", default_value_t = Backend::Std)]
+    backend: Backend,
+
+    /// Sort output by name (path), size or mtime. Default keeps walker order.
+    #[arg(long, value_enum, default_value_t = SortKey::None)]
+    sort: SortKey,
+
+    /// Reverse the output order.
+    #[arg(long)]
+    reverse: bool,
+
+    /// Print totals (files, directories, bytes, elapsed time) instead of the listing.
+    #[arg(long, group = "summary_mode")]
+    count: bool,
+
+    /// Dry run: like --count, plus how much content --content would emit
+    /// (after --max-bytes, binary files excluded) and its estimated tokens.
+    #[arg(long, group = "summary_mode")]
+    stats: bool,
+
+    /// Count files and code / comment / blank lines per language (by extension
+    /// or file name) over the matched files, instead of the listing.
+    #[arg(long, conflicts_with_all = ["count", "stats"])]
+    stats_by_language: bool,
+
+    /// Size report instead of the listing: du (matched bytes per directory,
+    /// largest first) or top-n (the --top largest files).
+    #[arg(long, value_enum, conflicts_with_all = ["count", "stats", "stats_by_language"])]
+    report: Option<Report>,
+
+    /// Rows of --report: the N largest (default: 10 for top-n, all for du).
+    #[arg(long, value_name = "N", requires = "report")]
+    top: Option<usize>,
+
+    /// Files and total size per group instead of the listing, most files first.
+    /// A JSON array with --format json, one object per line with ndjson.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KEY",
+        conflicts_with_all = ["count", "stats", "stats_by_language", "report"]
+    )]
+    group_by: Option<GroupBy>,
+
+    /// Report likely secrets (AWS, GitHub, Slack, Stripe... tokens, private
+    /// keys, high-entropy assignments) in the matched files as path:line
+    /// instead of the listing. Exits 0 when something was found, 1 when clean.
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "stats", "stats_by_language", "report", "group_by"]
+    )]
+    scan_secrets: bool,
+
+    /// List TODO / FIXME / HACK / XXX lines with their line numbers, grouped by
+    /// file, instead of the listing. Markers are whole, case-sensitive words.
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "stats", "stats_by_language", "report", "group_by", "scan_secrets"]
+    )]
+    todos: bool,
+
+    /// Markers for --todos (comma separated, default TODO,FIXME,HACK,XXX).
+    #[arg(long, value_delimiter = ',', value_name = "MARKERS")]
+    todo_markers: Vec<String>,
+
+    /// Break the --count / --stats totals down per extension.
+    #[arg(long, requires = "summary_mode")]
+    by_extension: bool,
+
+    /// Write a manifest (path, size, mtime, hash) of the matched files to FILE
+    /// instead of the listing. Uses --hash (default sha256).
+    #[arg(long, value_name = "FILE")]
+    snapshot: Option<PathBuf>,
+
+    /// Compare the matched files with a manifest from --snapshot and report
+    /// added (+), removed (-) and modified (M) files. May name the --snapshot file
+    /// to compare and update in one run.
+    #[arg(long, value_name = "FILE")]
+    diff: Option<PathBuf>,
+
+    /// Run CMD for every matched file instead of listing it. Placeholders: {} path,
+    /// {/} basename, {//} parent, {.} path without extension, {/.} basename without
+    /// extension; without any, the path is appended. No shell is involved.
+    #[arg(long, value_name = "CMD", group = "exec_action", conflicts_with_all = ["summary_mode", "snapshot", "diff", "archives"])]
+    exec: Option<String>,
+
+    /// Like --exec, but run CMD once with all matched files (split into batches
+    /// that fit the OS argument limit), like xargs.
+    #[arg(long, value_name = "CMD", group = "exec_action", conflicts_with_all = ["summary_mode", "snapshot", "diff", "archives"])]
+    exec_batch: Option<String>,
+
+    /// Copy the matched files into DIR, keeping their paths relative to --path.
+    #[arg(long, value_name = "DIR", group = "transfer", conflicts_with_all = ["exec_action", "summary_mode", "snapshot", "diff", "archives"])]
+    copy_to: Option<PathBuf>,
+
+    /// Move the matched files into DIR, keeping their paths relative to --path.
+    #[arg(long, value_name = "DIR", group = "transfer", conflicts_with_all = ["exec_action", "summary_mode", "snapshot", "diff", "archives"])]
+    move_to: Option<PathBuf>,
+
+    /// Commands to run in parallel with --exec / --exec-batch (0 = all cores).
+    /// Output of parallel commands is captured and printed per command.
+    #[arg(long, short = 'j', default_value_t = 1, requires = "exec_action")]
+    jobs: usize,
+
+    /// Keep running and regenerate the output whenever a matching file under --path
+    /// is created, changed or removed.
+    #[arg(long, conflicts_with = "files_from")]
+    watch: bool,
+
+    /// Browse the matches in a full-screen terminal view first: fuzzy search,
+    /// preview, Tab to mark files. Enter runs the output on the marked files
+    /// (or the one under the cursor) in any --format; Esc emits nothing.
+    #[arg(long, conflicts_with_all = ["watch", "checkpoint"])]
+    interactive: bool,
+
+    /// Pick the files to process from the matched paths in a fuzzy finder
+    /// (like fzf, without a preview), starting from QUERY if given
+    /// (--pick=QUERY); a single match for it is taken without asking.
+    #[arg(
+        long,
+        value_name = "QUERY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        conflicts_with_all = ["watch", "checkpoint", "interactive"]
+    )]
+    pick: Option<String>,
+
+    /// Keep an index of the matched paths in memory and answer JSON queries on
+    /// the Unix socket PATH (one request per line, see --guide); with --watch
+    /// the index follows file system events.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "output", "checkpoint", "interactive", "pick", "files_from", "snapshot", "diff",
+            "summary_mode", "stats_by_language", "report", "group_by", "scan_secrets", "todos",
+            "exec_action", "transfer"
+        ]
+    )]
+    serve: Option<PathBuf>,
+
+    /// Like --serve, over HTTP on ADDR (e.g. 127.0.0.1:8080): GET /list and
+    /// /content take the query fields as URL parameters and stream JSON, or
+    /// ND-JSON with format=ndjson.
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = [
+            "output", "checkpoint", "interactive", "pick", "files_from", "snapshot", "diff",
+            "summary_mode", "stats_by_language", "report", "group_by", "scan_secrets", "todos",
+            "exec_action", "transfer"
+        ]
+    )]
+    http: Option<String>,
+
+    /// Like --serve, as a Model Context Protocol server on stdin/stdout: the
+    /// list_files, read_files and pack_context tools for LLM agents. Ends when
+    /// the client closes stdin.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "serve", "http", "output", "checkpoint", "interactive", "pick", "files_from",
+            "snapshot", "diff", "summary_mode", "stats_by_language", "report", "group_by",
+            "scan_secrets", "todos", "exec_action", "transfer"
+        ]
+    )]
+    mcp: bool,
+
+    /// Separate listed paths with NUL instead of newlines (for xargs -0).
+    #[arg(long, short = '0', conflicts_with = "content")]
+    print0: bool,
+
     /// Use absolute paths in output header.
     #[arg(long)]
     absolute: bool,
 
+    /// Show paths relative to DIR instead of --path ('..' steps for paths
+    /// outside of it).
+    #[arg(long, value_name = "DIR", conflicts_with = "absolute")]
+    relative_to: Option<PathBuf>,
+
+    /// Byte-identical output on every OS for the same tree: sorted by name
+    /// (unless --sort is given), '/' separators, LF line endings in content,
+    /// plain modes without owners in archives. Timestamps are always UTC.
+    #[arg(long, conflicts_with = "absolute")]
+    stable: bool,
+
     /// Reduce warnings and metadata info.
     #[arg(long, short = 'q')]
     quiet: bool,
 
-    /// Show usage guide.
+    /// More detail on stderr: -v adds debug messages, -vv traces every match
+    /// and the walker's own decisions.
+    #[arg(long, short = 'v', action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Format of the messages on stderr: text, or json (one object per line
+    /// with time, level, target and message).
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Also write every traversal and I/O error as a JSON line (time, path,
+    /// operation, kind, message) to FILE, for auditing big runs.
+    #[arg(long, value_name = "FILE")]
+    errors: Option<PathBuf>,
+
+    /// Exit with code 2 when any file could not be read, walked or processed,
+    /// instead of only warning (the output is still complete otherwise).
     #[arg(long)]
-    guide: bool,
+    strict: bool,
+
+    /// Show a live counter (scanned, matched, bytes written, elapsed, ETA when
+    /// known) on stderr. Only drawn when stderr is a terminal that is not also
+    /// receiving the output; --quiet turns it off.
+    #[arg(long)]
+    progress: bool,
+
+    /// Apply a named bundle of options (comma separated, e.g. rust or python,docs).
+    /// Built-in: rust, python, node, go, java, web, docs; more via [presets.NAME] in the config.
+    #[arg(long, value_delimiter = ',', value_name = "NAME")]
+    preset: Vec<String>,
+
+    /// Read defaults from FILE instead of the global and project config files.
+    #[arg(long, value_name = "FILE", conflicts_with = "no_config")]
+    config: Option<PathBuf>,
+
+    /// Ignore all config files.
+    #[arg(long)]
+    no_config: bool,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
-enum Scope {
-    Name,
-    Path,
+/// Task-shaped entry points. Each one is shorthand for the flat command line
+/// (`collect pack` is `collect --content`), so the two can be mixed freely.
+#[derive(clap::Subcommand, Debug)]
+enum Commands {
+    /// List the matched paths (no content).
+    List(CollectArgs),
+
+    /// Bundle the matched files with their content (`--content`).
+    Pack(CollectArgs),
+
+    /// Hash the matched files: a listing with --hash (default sha256), or a
+    /// manifest written to MANIFEST (`--snapshot`).
+    Hash {
+        /// Write a manifest (path, size, mtime, hash) here instead of the listing.
+        manifest: Option<PathBuf>,
+
+        #[command(flatten)]
+        args: CollectArgs,
+    },
+
+    /// Compare the matched files with a manifest from `collect hash` (`--diff`).
+    Diff {
+        /// The manifest to compare with.
+        manifest: PathBuf,
+
+        #[command(flatten)]
+        args: CollectArgs,
+    },
+
+    /// Totals and estimated content size instead of the listing (`--stats`).
+    Stats(CollectArgs),
+
+    /// Answer filter queries from a warm index over a Unix socket (`--serve`),
+    /// HTTP (`--http`) or as an MCP server on stdio (`--mcp`).
+    Serve {
+        /// The socket to listen on, e.g. /tmp/collect.sock.
+        #[arg(long, value_name = "PATH", required_unless_present_any = ["http", "mcp"])]
+        socket: Option<PathBuf>,
+
+        #[command(flatten)]
+        args: CollectArgs,
+    },
+
+    /// Print a shell completion script to stdout.
+    ///
+    /// e.g. `collect completions bash > /etc/bash_completion.d/collect`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+/// Keys for `--group-by`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupBy {
+    /// Lowercase extension ("" for none).
+    Ext,
+}
+
+/// Size reports for `--report`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Report {
+    /// Bytes per directory, descendants included (like `du`), largest first.
+    Du,
+    /// The largest matched files.
+    TopN,
 }
 
 // =============================================================================
-// MODULE: CORE LOGIC & CONFIG
+// MODULE: CONFIG FILES
 // =============================================================================
 
-/// Runtime configuration optimized for lookup speed.
-/// Now includes all fields required by the walker and processor.
-struct AppConfig {
-    // Filters
-    extensions: Option<Vec<String>>,
-    extension_inv: bool,
-    regex: Option<Regex>,
-    regex_inv: bool,
-    scope: Scope,
+/// Project config, looked up from the working directory upwards.
+const PROJECT_CONFIG: &str = ".collect.toml";
+
+/// Presets shipped with the binary (`--preset rust`, ...).
+const BUILTIN_PRESETS: &str = include_str!("presets.toml");
+
+/// Flags that only make sense on the command line, and those a config file
+/// must not set: a `.collect.toml` is picked up from any parent directory,
+/// so a cloned repository could otherwise run commands, move files, redirect
+/// the output or open listeners with a plain `collect`.
+const CLI_ONLY: &[&str] = &[
+    "config",
+    "no_config",
+    "preset",
+    "type_list",
+    "guide",
+    "help",
+    "version",
+    "exec",
+    "exec_batch",
+    "copy_to",
+    "move_to",
+    "output",
+    "serve",
+    "http",
+    "mcp",
+];
+
+/// Flags each subcommand decides itself: hidden from its help, rejected on
+/// its command line and ignored in config files.
+const SUBCOMMAND_FLAGS: &[(&str, &[&str])] = &[
+    (
+        "list",
+        &[
+            "content",
+            "snapshot",
+            "diff",
+            "count",
+            "stats",
+            "stats_by_language",
+        ],
+    ),
+    (
+        "pack",
+        &[
+            "content",
+            "snapshot",
+            "diff",
+            "stats_by_language",
+            "report",
+            "group_by",
+        ],
+    ),
+    (
+        "hash",
+        &[
+            "snapshot",
+            "diff",
+            "stats_by_language",
+            "report",
+            "group_by",
+        ],
+    ),
+    (
+        "diff",
+        &[
+            "diff",
+            "stats_by_language",
+            "report",
+            "group_by",
+            "scan_secrets",
+        ],
+    ),
+    (
+        "stats",
+        &[
+            "stats",
+            "count",
+            "snapshot",
+            "diff",
+            "stats_by_language",
+            "report",
+        ],
+    ),
+    ("serve", &["serve"]),
+];
+
+/// The clap definition with the per-subcommand flags hidden.
+/// `requires` is checked on the rewritten command line instead, where the
+/// implied flags (`--content` for `pack`, ...) are present.
+fn cli_command() -> Command {
+    SUBCOMMAND_FLAGS
+        .iter()
+        .fold(Cli::command(), |cmd, (name, decided)| {
+            cmd.mut_subcommand(*name, |sub| {
+                decided
+                    .iter()
+                    .fold(sub, |sub, id| sub.mut_arg(*id, |arg| arg.hide(true)))
+                    .mut_args(|arg| arg.requires(Resettable::Reset))
+            })
+        })
+}
 
-    // Walker Config
-    base_path: PathBuf,
-    depth: Option<usize>,
-    exclude: Option<Vec<String>>,
-    no_default_excludes: bool,
-    include_hidden: bool,
-    follow_symlinks: bool,
+/// Parses the command line, using config files and presets for every option it leaves unset.
+/// Precedence: CLI flags > presets > project `.collect.toml` > global config.
+fn parse_cli() -> Result<Cli> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let mut cmd = cli_command();
+    let matches = cmd
+        .try_get_matches_from_mut(&args)
+        .unwrap_or_else(|e| e.exit());
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // A subcommand is rewritten into the flat command line it stands for
+    let (cmd, matches, decided, rewritten) = match (&cli.command, matches.subcommand()) {
+        // Completions do not collect anything, so config files do not apply
+        (Some(Commands::Completions { .. }), _) => return Ok(cli),
+        (_, Some((name, sub_matches))) => {
+            let sub = cmd
+                .find_subcommand(name)
+                .context("Unknown subcommand")?
+                .clone();
+            let decided = SUBCOMMAND_FLAGS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map_or(&[][..], |(_, decided)| *decided);
+            if let Some(id) = decided
+                .iter()
+                .find(|id| sub_matches.value_source(id) == Some(ValueSource::CommandLine))
+            {
+                let long = sub
+                    .get_arguments()
+                    .find(|a| a.get_id() == *id)
+                    .and_then(Arg::get_long)
+                    .unwrap_or(id);
+                anyhow::bail!("--{long} does not apply to `collect {name}`");
+            }
+            let tokens = matched_tokens(&sub, sub_matches, &cmd);
+            (sub, sub_matches.clone(), decided, Some(tokens))
+        }
+        _ => (cmd, matches, &[][..], None),
+    };
+    let args_cli = &cli.args;
+
+    let files = match &args_cli.config {
+        _ if args_cli.no_config => Vec::new(),
+        Some(path) => vec![path.clone()],
+        None => [global_config_path(), project_config_path()]
+            .into_iter()
+            .flatten()
+            .filter(|p| p.is_file())
+            .collect(),
+    };
 
-    // Output Config
-    output: Option<PathBuf>,
-    absolute_path: bool,
-    max_bytes: Option<u64>,
-    read_content: bool,
-    quiet: bool,
+    let mut presets: toml::Table = BUILTIN_PRESETS
+        .parse()
+        .context("Invalid built-in presets")?;
+
+    // Later files replace earlier ones key by key (presets by name)
+    let mut values = BTreeMap::new();
+    for path in &files {
+        for (key, value) in load_config(path)? {
+            if key == "presets" {
+                let toml::Value::Table(table) = value else {
+                    anyhow::bail!("'presets' must be a table in {}", path.display());
+                };
+                presets.extend(table);
+            } else {
+                let source = path.display().to_string();
+                values.insert(key.replace('-', "_"), (source, key, value));
+            }
+        }
+    }
+
+    // --preset on the CLI, else a `preset` key in the config
+    let names = match values.remove("preset") {
+        _ if !args_cli.preset.is_empty() => args_cli.preset.clone(),
+        Some((source, key, value)) => preset_names(&value)
+            .with_context(|| format!("Invalid value for '{key}' in {source}"))?,
+        None => Vec::new(),
+    };
+    for name in names {
+        let Some(toml::Value::Table(preset)) = presets.get(&name) else {
+            let available: Vec<_> = presets.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Unknown preset '{name}' (available: {})",
+                available.join(", ")
+            );
+        };
+        for (key, value) in preset {
+            let id = key.replace('-', "_");
+            let mut value = value.clone();
+            // Combined presets add up their lists (`--preset rust,docs`)
+            if let Some((source, _, toml::Value::Array(prev))) = values.get(&id)
+                && source.starts_with("preset ")
+                && let toml::Value::Array(items) = &mut value
+            {
+                items.splice(0..0, prev.iter().cloned());
+            }
+            values.insert(id, (format!("preset '{name}'"), key.clone(), value));
+        }
+    }
+    if values.is_empty() && rewritten.is_none() {
+        return Ok(cli);
+    }
+
+    let mut tokens = Vec::new();
+    for (id, (source, key, value)) in &values {
+        if decided.contains(&id.as_str()) {
+            continue;
+        }
+        let arg = cmd
+            .get_arguments()
+            .find(|a| a.get_id() == id.as_str() && a.get_long().is_some())
+            .with_context(|| format!("Unknown option '{key}' in {source}"))?;
+        if CLI_ONLY.contains(&id.as_str()) {
+            anyhow::bail!("'{key}' can only be given on the command line, not in {source}");
+        }
+
+        if !set_on_cli(&cmd, &matches, arg) {
+            config_tokens(arg, value, &mut tokens)
+                .with_context(|| format!("Invalid value for '{key}' in {source}"))?;
+        }
+    }
+
+    let rest: Vec<OsString> = match (rewritten, &cli.command) {
+        (Some(matched), Some(command)) => {
+            let hash_set = matches.value_source("hash") == Some(ValueSource::CommandLine)
+                || values.contains_key("hash");
+            implied_tokens(command, hash_set)
+                .into_iter()
+                .chain(matched)
+                .collect()
+        }
+        _ => args.iter().skip(1).cloned().collect(),
+    };
+
+    // Config tokens go first so clap reports CLI mistakes as usual
+    let mut merged = Vec::with_capacity(rest.len() + tokens.len() + 1);
+    merged.extend(args.first().cloned());
+    merged.extend(tokens);
+    merged.extend(rest);
+    Ok(Cli::try_parse_from(merged).unwrap_or_else(|e| e.exit()))
 }
 
-impl AppConfig {
-    fn from_cli(cli: Cli) -> Result<Self> {
-        let regex = if let Some(re_str) = cli.regex {
-            Some(Regex::new(&re_str).context("Invalid Regex format")?)
-        } else {
-            None
+/// The flat flags a subcommand stands for.
+fn implied_tokens(command: &Commands, hash_set: bool) -> Vec<OsString> {
+    let flag = |long: &str, value: &Path| {
+        let mut token = OsString::from(format!("--{long}="));
+        token.push(value);
+        token
+    };
+    match command {
+        Commands::Pack(_) => vec!["--content".into()],
+        Commands::Hash {
+            manifest: Some(manifest),
+            ..
+        } => vec![flag("snapshot", manifest)],
+        Commands::Hash { manifest: None, .. } if !hash_set => vec!["--hash=sha256".into()],
+        Commands::Diff { manifest, .. } => vec![flag("diff", manifest)],
+        Commands::Stats(_) => vec!["--stats".into()],
+        Commands::Serve {
+            socket: Some(socket),
+            ..
+        } => vec![flag("serve", socket)],
+        _ => Vec::new(),
+    }
+}
+
+/// Replays the options given to a subcommand as `--flag[=value]` tokens.
+/// Its own arguments (`serve --socket`) come back through `implied_tokens`.
+fn matched_tokens(cmd: &Command, matches: &ArgMatches, flat: &Command) -> Vec<OsString> {
+    let mut tokens = Vec::new();
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        if !flat.get_arguments().any(|a| a.get_id() == id) {
+            continue;
+        }
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        match arg.get_action() {
+            clap::ArgAction::Count => {
+                let count = matches.get_count(id);
+                tokens.extend((0..count).map(|_| OsString::from(format!("--{long}"))));
+            }
+            action if action.takes_values() => {
+                for value in matches.get_raw(id).into_iter().flatten() {
+                    let mut token = OsString::from(format!("--{long}="));
+                    token.push(value);
+                    tokens.push(token);
+                }
+            }
+            _ => tokens.push(format!("--{long}").into()),
+        }
+    }
+    tokens
+}
+
+/// `preset = "rust"` or `preset = ["rust", "docs"]`.
+fn preset_names(value: &toml::Value) -> Result<Vec<String>> {
+    match value {
+        toml::Value::String(name) => Ok(vec![name.clone()]),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .context("expected a preset name")
+            })
+            .collect(),
+        _ => anyhow::bail!("expected a preset name or a list of them"),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/collect/config.toml` (or `~/.config/...`), `%APPDATA%` on Windows.
+fn global_config_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+    };
+    dir.map(|d| d.join("collect").join("config.toml"))
+}
+
+/// Nearest `.collect.toml` in the working directory or one of its parents.
+fn project_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .find(|p| p.is_file())
+}
+
+fn load_config(path: &Path) -> Result<toml::Table> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    text.parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// True if the CLI already set this option, or one that excludes it.
+fn set_on_cli(cmd: &Command, matches: &ArgMatches, arg: &Arg) -> bool {
+    let on_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if on_cli(arg.get_id().as_str()) {
+        return true;
+    }
+
+    let conflicts = cmd
+        .get_arg_conflicts_with(arg)
+        .into_iter()
+        .any(|other| on_cli(other.get_id().as_str()));
+
+    let exclusive_group = cmd.get_groups().any(|group| {
+        let members: Vec<_> = group.get_args().collect();
+        !group.clone().is_multiple()
+            && members.contains(&arg.get_id())
+            && members.iter().any(|id| on_cli(id.as_str()))
+    });
+
+    conflicts || exclusive_group
+}
+
+/// Converts one config value into `--flag[=value]` tokens.
+fn config_tokens(arg: &Arg, value: &toml::Value, tokens: &mut Vec<OsString>) -> Result<()> {
+    let long = arg.get_long().unwrap_or_default();
+
+    if !arg.get_action().takes_values() {
+        let toml::Value::Boolean(enabled) = value else {
+            anyhow::bail!("expected true or false");
         };
+        if *enabled {
+            tokens.push(format!("--{long}").into());
+        }
+        return Ok(());
+    }
+
+    let items = match value {
+        toml::Value::Array(items) => items.as_slice(),
+        single => std::slice::from_ref(single),
+    };
+    for item in items {
+        let text = match item {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Datetime(_) => {
+                item.to_string()
+            }
+            _ => anyhow::bail!("expected a string, a number or a list of them"),
+        };
+        tokens.push(format!("--{long}={text}").into());
+    }
+    Ok(())
+}
+
+// =============================================================================
+// MODULE: CONFIGURATION BUILDER
+// =============================================================================
 
+impl CollectArgs {
+    fn into_options(self) -> CollectOptions {
+        // Escape codes only make sense on a terminal, unless forced
+        let color = match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                self.output.is_none()
+                    && io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        };
         // Determine if we are allowing or excluding extensions
         // Since they are in a Clap group, only one (or none) can be present.
-        let (raw_extensions, extension_inv) = if let Some(exts) = cli.extension {
+        let (extensions, extension_inv) = if let Some(exts) = self.extension {
             (Some(exts), false) // Whitelist mode
-        } else if let Some(exts) = cli.no_extension {
+        } else if let Some(exts) = self.no_extension {
             (Some(exts), true) // Blacklist mode
         } else {
             (None, false)
         };
 
-        // Normalize extensions to lowercase for case-insensitive comparison
-        let extensions = raw_extensions.map(|exts| {
-            exts.into_iter()
-                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
-                .collect()
-        });
-
-        Ok(Self {
+        CollectOptions {
             extensions,
             extension_inv,
-            regex,
-            regex_inv: cli.regex_inv,
-            scope: cli.scope,
-            base_path: cli.path,
-            depth: cli.depth,
-            exclude: cli.exclude,
-            no_default_excludes: cli.no_default_excludes,
-            include_hidden: cli.include_hidden,
-            follow_symlinks: cli.follow_symlinks,
-            output: cli.output,
-            absolute_path: cli.absolute,
-            max_bytes: cli.max_bytes,
-            read_content: cli.content,
-            quiet: cli.quiet,
-        })
+            regex: self.regex,
+            regex_mode: self.regex_mode,
+            regex_inv: self.regex_inv,
+            scope: self.scope,
+            ignore_case: self.ignore_case,
+            fixed_strings: self.fixed_strings,
+            pattern: self.pattern,
+            show_matches: self.show_matches,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            newer_than: self.newer_than.or(self.changed_within),
+            older_than: self.older_than,
+            owner: self.owner,
+            group: self.group,
+            perm: self.perm,
+            filter: self.filter,
+            git: self.git.unwrap_or_default(),
+            since_ref: self.since_ref,
+            export_ignore: self.export_ignore,
+            types: self.types.unwrap_or_default(),
+            mime: self.mime,
+            frontmatter_filters: self.frontmatter_filter,
+            base_path: self.path,
+            files_from: self.files_from,
+            selected: None,
+            depth: self.depth,
+            min_depth: self.min_depth,
+            exclude: self.exclude,
+            exclude_from: self.exclude_from,
+            // Including the --atomic temporary file
+            skip_files: self
+                .output
+                .iter()
+                .flat_map(|path| [path.clone(), atomic_temp_path(path)])
+                .collect(),
+            globs: self.glob,
+            no_default_excludes: self.no_default_excludes,
+            include_hidden: self.include_hidden,
+            follow_symlinks: self.follow_symlinks,
+            archives: self.archives,
+            symlinks: self.symlinks,
+            path_style: self.windows_path_style,
+            path_rewrite: self.path_rewrite,
+            dedupe_hardlinks: self.dedupe_hardlinks,
+            xattrs: self.xattrs,
+            image_meta: self.image_meta,
+            ads: self.ads,
+            ads_content: self.ads_content,
+            same_file_system: self.same_file_system,
+            threads: self.threads,
+            backend: self.backend,
+            sort: self.sort,
+            reverse: self.reverse,
+            absolute_path: self.absolute,
+            relative_to: self.relative_to,
+            max_bytes: self.max_bytes,
+            read_content: self.content,
+            binary: self.binary,
+            binary_check: self.binary_check,
+            lfs: self.lfs,
+            notebook: self.notebook,
+            highlight: self.highlight && color,
+            color,
+            line_numbers: self.line_numbers,
+            redact: self.redact,
+            redact_with: self.redact_with,
+            strip_comments: self.strip_comments,
+            squeeze_blank: self.squeeze_blank,
+            normalize_eol: self.normalize_eol,
+            strip_bom: self.strip_bom,
+            max_line_bytes: self.max_line_bytes,
+            skip_generated: self.skip_generated,
+            stable: self.stable,
+            head_lines: self.head_lines,
+            tail_lines: self.tail_lines,
+            lines: self.lines,
+            print0: self.print0,
+            format: self.format,
+            numeric_owner: self.numeric_owner,
+            no_perms: self.no_perms,
+            compress: self.compress,
+            metadata: self.metadata.unwrap_or_default(),
+            columns: self.columns,
+            template: self.template,
+            header_template: self.header_template,
+            template_header: self.template_header,
+            template_footer: self.template_footer,
+            hash: self.hash,
+            git_meta: self.git_meta,
+            frontmatter: self.frontmatter,
+            count_tokens: self.count_tokens,
+            max_tokens: self.max_tokens,
+            max_files: self.max_files,
+            max_output_bytes: self.max_output_bytes,
+            split_bytes: self.split_bytes,
+            split_files: self.split_files,
+            chunk_size: self.chunk_size,
+            chunk_overlap: self.chunk_overlap,
+            checkpoint: self.checkpoint,
+            incremental: self.incremental,
+            budget_strategy: self.budget_strategy,
+            errors: self.errors,
+            quiet: self.quiet,
+        }
     }
 }
 
 // =============================================================================
-// MODULE: FILTER PIPELINE
+// MODULE: TYPE ALIASES
 // =============================================================================
 
-/// Evaluates if a path matches the criteria.
-/// This is the "hot path" of the application, keep it allocation-free if possible.
-fn should_process(path: &Path, config: &AppConfig, is_dir: bool) -> bool {
-    // 1. Extension Filter (O(1) lookup effectively for small lists)
-    if !is_dir && let Some(exts) = &config.extensions {
-        let file_ext = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_lowercase())
-            .unwrap_or_default();
-
-        let found = exts.contains(&file_ext);
-        if found == config.extension_inv {
-            return false;
+/// Extension sets for `--type-alias`, extended by `--type-add`.
+const TYPE_ALIASES: &[(&str, &[&str])] = &[
+    ("c", &["c", "h"]),
+    (
+        "config",
+        &["toml", "yaml", "yml", "json", "ini", "cfg", "conf"],
+    ),
+    ("cpp", &["cpp", "cc", "cxx", "hpp", "hh", "hxx", "h"]),
+    ("csharp", &["cs", "csx"]),
+    ("css", &["css", "scss", "sass", "less"]),
+    ("docs", &["md", "mdx", "rst", "txt", "adoc"]),
+    ("go", &["go"]),
+    ("html", &["html", "htm", "xhtml"]),
+    ("java", &["java"]),
+    ("js", &["js", "jsx", "mjs", "cjs"]),
+    ("kotlin", &["kt", "kts"]),
+    ("php", &["php"]),
+    ("python", &["py", "pyi"]),
+    ("ruby", &["rb", "gemspec", "rake"]),
+    ("rust", &["rs"]),
+    ("shell", &["sh", "bash", "zsh", "fish"]),
+    ("sql", &["sql"]),
+    ("swift", &["swift"]),
+    ("ts", &["ts", "tsx", "mts", "cts"]),
+    (
+        "web",
+        &[
+            "html", "htm", "css", "scss", "js", "jsx", "mjs", "ts", "tsx", "vue", "svelte",
+        ],
+    ),
+];
+
+/// Built-in aliases plus `--type-add` definitions (`web:*.html,*.css`), which
+/// add to an existing alias of the same name or define a new one.
+fn type_aliases(added: &[String]) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut aliases: BTreeMap<String, Vec<String>> = TYPE_ALIASES
+        .iter()
+        .map(|(name, exts)| {
+            (
+                (*name).to_string(),
+                exts.iter().map(|e| (*e).to_string()).collect(),
+            )
+        })
+        .collect();
+    for definition in added {
+        let (name, globs) = definition
+            .split_once(':')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .with_context(|| {
+                format!("Invalid --type-add '{definition}' (expected NAME:*.EXT,*.EXT)")
+            })?;
+        let exts = aliases.entry(name.trim().to_string()).or_default();
+        for glob in globs.split(',').map(str::trim).filter(|g| !g.is_empty()) {
+            let ext = glob.strip_prefix("*.").unwrap_or(glob);
+            if ext.is_empty() || ext.contains(['*', '?', '[', '/', '.']) {
+                anyhow::bail!(
+                    "Invalid --type-add pattern '{glob}' in '{definition}' (expected *.EXT or EXT)"
+                );
+            }
+            if !exts.iter().any(|e| e == ext) {
+                exts.push(ext.to_string());
+            }
         }
     }
+    Ok(aliases)
+}
 
-    // 2. Regex Filter (Expensive, do it last)
-    if let Some(re) = &config.regex {
-        let text_to_match = match config.scope {
-            Scope::Name => path.file_name().and_then(|s| s.to_str()).unwrap_or(""),
-            Scope::Path => path.to_str().unwrap_or(""),
+/// Extensions of the `--type-alias` names, merged into `--extension`.
+fn resolve_type_aliases(cli: &mut CollectArgs) -> Result<()> {
+    if cli.type_alias.is_empty() {
+        return Ok(());
+    }
+    let aliases = type_aliases(&cli.type_add)?;
+    let extensions = cli.extension.get_or_insert_with(Vec::new);
+    for name in &cli.type_alias {
+        let Some(exts) = aliases.get(name) else {
+            let available: Vec<_> = aliases.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Unknown type alias '{name}' (available: {})",
+                available.join(", ")
+            );
         };
-
-        let found = re.is_match(text_to_match);
-        if found == config.regex_inv {
-            return false;
+        for ext in exts {
+            if !extensions.contains(ext) {
+                extensions.push(ext.clone());
+            }
         }
     }
+    Ok(())
+}
 
-    true
+/// `--type-list`: one `name: *.ext, *.ext` line per alias.
+fn write_type_list(
+    aliases: &BTreeMap<String, Vec<String>>,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    for (name, exts) in aliases {
+        let globs: Vec<_> = exts.iter().map(|e| format!("*.{e}")).collect();
+        writeln!(writer, "{name}: {}", globs.join(", "))?;
+    }
+    Ok(())
 }
 
 // =============================================================================
-// MODULE: I/O PROCESSOR (Optimized)
+// MODULE: OUTPUT FILE
 // =============================================================================
 
-/// Handles file reading and writing with buffering.
-/// Returns io::Result to allow easier BrokenPipe handling in main.
-fn process_file(
-    path: &Path,
-    config: &AppConfig,
-    writer: &mut BufWriter<Box<dyn Write + Send>>,
-) -> io::Result<()> {
-    // 1. Path Formatting
-    let path_display = if config.absolute_path {
-        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
-    } else {
-        path.strip_prefix(&config.base_path)
-            .unwrap_or(path)
-            .to_path_buf()
-    };
+/// Where a run writes: stdout, or --output (replaced, appended, or written
+/// atomically through a temporary file).
+enum Output {
+    Stdout(io::Stdout),
+    File(File),
+    /// Renamed to `path` by `commit`; removed on drop otherwise.
+    Atomic {
+        file: File,
+        temp: PathBuf,
+        path: PathBuf,
+        committed: bool,
+    },
+}
 
-    // 2. Write Header
-    if config.read_content {
-        writeln!(writer, "=== {} ===", path_display.display())?;
-    } else {
-        writeln!(writer, "{}", path_display.display())?;
+impl Output {
+    fn open(mode: &RunMode) -> Result<Self> {
+        let Some(path) = &mode.output else {
+            return Ok(Self::stdout());
+        };
+        if mode.atomic {
+            let temp = atomic_temp_path(path);
+            let file = File::create(&temp)
+                .with_context(|| format!("Failed to create temporary file {}", temp.display()))?;
+            return Ok(Self::Atomic {
+                file,
+                temp,
+                path: path.clone(),
+                committed: false,
+            });
+        }
+        let resume = match &mode.checkpoint {
+            Some(checkpoint) => Checkpoint::load(checkpoint)?,
+            None => None,
+        };
+        if let Some(resume) = resume {
+            // Drop whatever was written after the last checkpoint
+            let file = fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open {} to resume", path.display()))?;
+            if file.metadata()?.len() < resume.offset {
+                anyhow::bail!(
+                    "{} is shorter than its checkpoint ({} bytes); remove the checkpoint to start over",
+                    path.display(),
+                    resume.offset
+                );
+            }
+            file.set_len(resume.offset)?;
+            return Ok(Self::File(file));
+        }
+        let file = if mode.append {
+            fs::OpenOptions::new().create(true).append(true).open(path)
+        } else {
+            File::create(path)
+        };
+        Ok(Self::File(file.context("Failed to create output file")?))
     }
 
-    // 3. Content Streaming (The optimization core)
-    if config.read_content {
-        stream_file_content(path, writer, config.max_bytes)?;
+    /// Stdout redirected to a regular file (`collect > out.txt`) is written
+    /// through a duplicate of the descriptor, so it gets the file fast path.
+    fn stdout() -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsFd;
+            let stdout = io::stdout();
+            if let Ok(fd) = stdout.as_fd().try_clone_to_owned() {
+                let file = File::from(fd);
+                if file.metadata().is_ok_and(|m| m.is_file()) {
+                    return Self::File(file);
+                }
+            }
+        }
+        Self::Stdout(io::stdout())
     }
 
-    Ok(())
+    /// The file behind this target, for `Collector::write_file`.
+    fn file(&self) -> Option<&File> {
+        match self {
+            Self::Stdout(_) => None,
+            Self::File(file) | Self::Atomic { file, .. } => Some(file),
+        }
+    }
+
+    /// Makes an atomic write visible; a no-op for the other targets.
+    fn commit(mut self) -> Result<()> {
+        if let Self::Atomic {
+            file,
+            temp,
+            path,
+            committed,
+        } = &mut self
+        {
+            file.sync_all().context("Failed to sync output file")?;
+            fs::rename(&*temp, &*path)
+                .with_context(|| format!("Failed to move output into {}", path.display()))?;
+            *committed = true;
+        }
+        Ok(())
+    }
 }
 
-/// Reads file with binary detection and streams to output.
-/// Uses a 8KB buffer to detect binary files (null bytes) and respects max_bytes immediately.
-fn stream_file_content(
-    path: &Path,
-    writer: &mut BufWriter<Box<dyn Write + Send>>,
-    max_bytes: Option<u64>,
-) -> io::Result<()> {
-    let file = match File::open(path) {
-        Ok(f) => f,
-        Err(e) => {
-            writeln!(writer, "\n<Error opening file: {}>\n", e)?;
-            return Ok(());
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Self::Atomic {
+            temp,
+            committed: false,
+            ..
+        } = self
+        {
+            let _ = fs::remove_file(temp);
         }
-    };
+    }
+}
 
-    let mut reader = BufReader::new(file);
-    // 8KB buffer for heuristic binary check
-    let mut buffer = [0u8; 8192];
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(out) => out.write(buf),
+            Self::File(file) | Self::Atomic { file, .. } => file.write(buf),
+        }
+    }
 
-    // Read first chunk
-    let n = reader.read(&mut buffer)?;
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(out) => out.flush(),
+            Self::File(file) | Self::Atomic { file, .. } => file.flush(),
+        }
+    }
+}
 
-    if n == 0 {
-        writeln!(writer, "\n<Empty File>\n")?;
-        return Ok(());
+/// Hidden sibling of `path`, on the same file system so the rename is atomic.
+fn atomic_temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.collect-tmp"))
+}
+
+// =============================================================================
+// MODULE: LOGGING
+// =============================================================================
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum LogFormat {
+    /// Plain messages (warnings and debug output prefixed with their level).
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Writes `log` records (from the library, the CLI and, with -vv, the walker)
+/// to stderr, keeping stdout for data.
+#[derive(Debug)]
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+impl Logger {
+    /// --quiet keeps errors only; -v / -vv raise the level from `info`.
+    fn install(verbose: u8, quiet: bool, format: LogFormat) -> Result<()> {
+        let level = match (quiet, verbose) {
+            (true, _) => LevelFilter::Error,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        };
+        log::set_boxed_logger(Box::new(Self { level, format }))
+            .context("Failed to install the logger")?;
+        log::set_max_level(level);
+        Ok(())
     }
+}
 
-    // SIMD Optimized search for null byte to detect binary
-    if memchr(0, buffer.get(..n).expect("Failed to read file")).is_some() {
-        writeln!(writer, "\n<Binary content suppressed>\n")?;
-        return Ok(());
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        // Dependencies only speak up at -vv
+        metadata.level() <= self.level
+            && (metadata.target().starts_with("collect") || self.level == LevelFilter::Trace)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Text => match record.level() {
+                Level::Info => record.args().to_string(),
+                Level::Warn => format!("warning: {}", record.args()),
+                level => format!("{}: {}", level.as_str().to_lowercase(), record.args()),
+            },
+            LogFormat::Json => serde_json::json!({
+                "time": format_time(Some(SystemTime::now())),
+                "level": record.level().as_str().to_lowercase(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        };
+        // Printed above a --progress spinner, which is redrawn below
+        match PROGRESS_BAR
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            Some(bar) => bar.suspend(|| eprintln!("{line}")),
+            None => eprintln!("{line}"),
+        }
     }
 
-    // Determine the absolute limit logic
-    let limit = max_bytes.unwrap_or(u64::MAX);
+    fn flush(&self) {}
+}
 
-    // Calculate how many bytes from the INITIAL buffer we are allowed to write.
-    // If limit is 100 but we read 8192, we only write 100.
-    // If limit is 1GB and we read 8192, we write 8192.
-    let bytes_to_write_from_buffer = usize::try_from(std::cmp::min(n as u64, limit))
-        .expect("Unexpected error trying to convert limit to usize.");
+// =============================================================================
+// MODULE: PROGRESS
+// =============================================================================
 
-    writer.write_all(b"\n")?;
-    writer.write_all(
-        buffer
-            .get(..bytes_to_write_from_buffer)
-            .expect("Failed to read file"),
-    )?;
+/// Refresh interval of the --progress spinner and its counters.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
-    // If we haven't reached the limit yet AND there might be more file content
-    if limit > bytes_to_write_from_buffer as u64 {
-        let remaining_allowance = limit - bytes_to_write_from_buffer as u64;
+/// The spinner of a running --progress, which log lines are printed above.
+static PROGRESS_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
 
-        // Use 'take' to wrap the reader, ensuring we never cross the boundary
-        // during the streaming copy.
-        let mut limited_reader = reader.take(remaining_allowance);
+/// Runs `work` under a spinner on stderr (hidden when stderr is not a
+/// terminal) whose counters a second thread refreshes, then clears it.
+fn with_progress<T>(collector: &Collector, work: impl FnOnce() -> T) -> T {
+    let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+    bar.set_style(
+        ProgressStyle::with_template("{spinner} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    bar.enable_steady_tick(PROGRESS_INTERVAL);
+    *PROGRESS_BAR.lock().unwrap_or_else(PoisonError::into_inner) = Some(bar.clone());
+
+    let (done, finished) = mpsc::channel::<()>();
+    let result = std::thread::scope(|scope| {
+        let bar = &bar;
+        scope.spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                finished.recv_timeout(PROGRESS_INTERVAL)
+            {
+                bar.set_message(progress_line(&collector.progress(), bar.elapsed()));
+            }
+        });
+        let result = work();
+        drop(done);
+        result
+    });
+
+    *PROGRESS_BAR.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    bar.finish_and_clear();
+    result
+}
+
+fn progress_line(progress: &Progress, elapsed: Duration) -> String {
+    let mut line = format!(
+        "Scanned {} · matched {} · {} written · {}",
+        progress.scanned,
+        progress.matched,
+        format_size(progress.written),
+        format_clock(elapsed)
+    );
+    // Only buffered runs know how many matches there are
+    if let Some(total) = progress.total
+        && progress.matched > 0
+        && progress.matched < total
+    {
+        let remaining =
+            elapsed.mul_f64((total - progress.matched) as f64 / progress.matched as f64);
+        line.push_str(&format!(" · ETA {}", format_clock(remaining)));
+    }
+    line
+}
 
-        // Zero-copy stream (kernel space copy where supported)
-        io::copy(&mut limited_reader, writer)?;
+/// `m:ss`, or `h:mm:ss` past an hour.
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
     }
+}
 
-    // Optional: Indicate if truncated?
-    // Usually CLI tools just stop, but for debugging valid to know.
-    // We stick to simple output for now.
+// =============================================================================
+// MODULE: PICKER
+// =============================================================================
 
-    writer.write_all(b"\n\n")?;
+/// `--interactive` / `--pick`: the files chosen in the browser, `None` when
+/// it was left without a choice.
+#[cfg(feature = "tui")]
+fn pick(options: &CollectOptions, query: String, picker: bool) -> Result<Option<Vec<PathBuf>>> {
+    collect::browse(&Collector::new(options.clone())?, query, picker)
+}
 
-    Ok(())
+#[cfg(not(feature = "tui"))]
+fn pick(_options: &CollectOptions, _query: String, _picker: bool) -> Result<Option<Vec<PathBuf>>> {
+    anyhow::bail!("--interactive and --pick need collect built with the `tui` feature")
+}
+
+// =============================================================================
+// MODULE: SERVER
+// =============================================================================
+
+/// `--mcp`: answers MCP requests on stdin/stdout until it closes.
+#[cfg(feature = "serve")]
+fn serve_mcp(options: CollectOptions, watch_mode: bool) -> Result<()> {
+    collect::mcp(options, watch_mode)
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve_mcp(_options: CollectOptions, _watch_mode: bool) -> Result<()> {
+    anyhow::bail!("--mcp needs collect built with the `serve` feature")
+}
+
+/// `--serve` / `--http`: answers queries on the socket and/or address.
+#[cfg(feature = "serve")]
+fn serve_index(
+    options: CollectOptions,
+    socket: Option<&Path>,
+    http: Option<&str>,
+    watch_mode: bool,
+) -> Result<()> {
+    collect::serve(options, socket, http, watch_mode)
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve_index(
+    _options: CollectOptions,
+    _socket: Option<&Path>,
+    _http: Option<&str>,
+    _watch_mode: bool,
+) -> Result<()> {
+    anyhow::bail!("--serve and --http need collect built with the `serve` feature")
+}
+
+// =============================================================================
+// MODULE: WATCH MODE
+// =============================================================================
+
+/// Re-runs the collection whenever a file that passes the path filters changes.
+#[cfg(feature = "serve")]
+fn watch(collector: &Collector, mode: &RunMode) -> Result<()> {
+    // Never react to our own writes (including the --atomic temporary file)
+    let output = mode.output.as_ref().and_then(|p| fs::canonicalize(p).ok());
+    let temp = output.as_deref().map(atomic_temp_path);
+    let own: Vec<PathBuf> = output.into_iter().chain(temp).collect();
+
+    collect::on_changes(collector, &own, || {
+        if run(collector, mode)? == Outcome::Closed {
+            return Ok(false);
+        }
+        if let Some(path) = &mode.output {
+            log::info!("Updated {}", path.display());
+        }
+        Ok(true)
+    })
+}
+
+#[cfg(not(feature = "serve"))]
+fn watch(_collector: &Collector, _mode: &RunMode) -> Result<()> {
+    anyhow::bail!("--watch needs collect built with the `serve` feature")
 }
 
 // =============================================================================
@@ -347,141 +1906,912 @@ fn print_guide() {
       --extension rs,toml    : Only allow .rs and .toml files.
       --no-extension py,js   : Allow everything EXCEPT .py and .js files.
       --regex "Test.*"       : Allow files matching regex.
+      --regex-mode all --scope content --regex "unsafe" --regex "extern"
+                             : Files containing both patterns (default: any).
       --scope path           : Regex applies to full relative path.
+      --scope content        : Regex applies to file content (text files only).
+      --scope content --regex-inv --regex "Copyright"
+                             : Files NOT containing "Copyright".
+      --pattern "fn main"    : Only files whose content matches (regex).
+      -i, --ignore-case      : Case-insensitive --regex / --pattern.
+      -F --regex "v1.2[3]"   : Literal text, no regex escaping needed.
+      --show-matches         : Print matching lines with line numbers.
+      --min-size 1K          : Only files of at least 1 KiB (K, M, G, T).
+      --max-size 10M         : Only files of at most 10 MiB.
+      --changed-within 2d    : Only files modified in the last 2 days.
+      --newer-than 2024-01-01: Modified after a date (UTC) or duration ago.
+      --older-than 4w        : Modified before a date (UTC) or duration ago.
+      --perm -002 --owner '!deploy'
+                             : World-writable files not owned by deploy (Unix).
+      --perm /111            : Executable by anyone (644 exact, -MODE all bits, /MODE any).
+      --git modified         : Only files changed vs HEAD (staged or not).
+      --git staged,untracked : Union of git sets (tracked, modified, staged, untracked).
+      --type d --regex "^test"
+                             : Directories named test* (f file, d dir, l symlink,
+                               s FIFO/socket/device).
+      --symlinks target      : Links as their resolved path (report, skip, target).
+      --dedupe-hardlinks     : Hardlinked files once, other paths as aliases.
+      --xattrs               : Extended attributes (security.selinux, com.apple.quarantine...).
+      --image-meta --mime image/*
+                             : Images with their format, size in pixels, camera, date and GPS.
+      --ads-content          : NTFS alternate data streams listed and printed (Windows).
+      --windows-path-style forward
+                             : src/main.rs separators everywhere (native, forward, backslash).
+      --mime image/*         : Files whose content is an image, whatever their name.
+      --mime text/x-python   : Python sources, including extensionless scripts (#!).
+      --type-alias rust,docs : Named extension sets (see --type-list), added to --extension.
+      --type-add "web:*.astro" --type-alias web
+                             : Extend or define an alias (type_add = [...] in the config).
+      --type-list            : Print every alias and its extensions.
       
     (Note: --extension and --no-extension are mutually exclusive)
 
+    FILTER EXPRESSIONS:
+      --filter "ext == 'rs' && size > 10KB && path !~ 'tests/'"
+      Fields : path, name, ext, size, mtime, depth,
+               type (file, dir, symlink, special)
+      Compare: == != < <= > >= on all fields but type (== != only);
+               =~ !~ (regex) on path, name and ext.
+      Combine: && || ! and parentheses. Quote values with spaces or symbols.
+      Values : sizes with units (10KB, 1.5M), times as dates or ages
+               (mtime > '2024-01-01', mtime > 2d = changed in the last 2 days).
+
     CONTENT & LIMITS:
       --content              : Read and print file content.
-      --max-bytes 1000       : Truncate reading after 1000 bytes.
+      --max-bytes 1000       : Truncate reading after 1000 bytes (with a <truncated> marker).
+      --line-numbers         : Prefix content lines with their line number.
+      --redact '[\w.+-]+@[\w-]+\.[\w.]+' --redact-with '<email>'
+                             : Mask matches in the content (repeatable, default ███).
+      --strip-comments --squeeze-blank
+                             : Smaller bundles: no comments, no runs of blank lines.
+      --highlight            : Syntax-colored content on a terminal (plain when piped).
+      --color always         : LS_COLORS-colored listing even when piped (auto, always, never).
+      --binary hex           : Binary files as a hex dump (suppress, skip, hex, base64, raw).
+      --binary-check strict  : Any null byte marks a file binary (loose default, off).
+      --head-lines 50        : Only the first 50 lines of each file.
+      --lines 100:200        : Only lines 100 to 200 of each file (100: or :50 open-ended).
+      --lines src/main.rs:40:80 --lines src/lib.rs:1:20
+                             : Per-file ranges; files without one are emitted whole.
+      --head-lines 20 --tail-lines 20
+                             : First and last 20 lines, "<truncated: N lines>" between.
       --depth 2              : Only go 2 folders deep.
+      --min-depth 2          : Skip entries directly under --path.
       --output file.txt      : Save result to file.
+      --output out.txt --atomic
+                             : Write a temp file, rename on success (never half-written).
+      --output log.txt --append
+                             : Add to the end of an existing file.
+      --incremental .collect-state --content
+                             : Only files added or changed since the previous run.
+      --progress --output dump.txt
+                             : Live scanned / matched / written counter on stderr.
+      -v, -vv                : Debug details; -vv also traces every match and the walker.
+      --log-format json      : Warnings and status on stderr as JSON lines.
+      --errors errors.ndjson : Every error as {{path, operation, kind, message}}, one per line.
+      --strict               : Exit 2 if any file could not be read (default: warn, exit 0).
+      --output dump.txt --checkpoint dump.ckpt
+                             : Resumable run: rerun the same command after an interruption.
+      -0, --print0           : NUL-separated paths: collect -0 | xargs -0 wc -l
+      --metadata size,mtime  : Print size, mtime, ctime, perms, owner, group, type or mime per file.
+      --metadata perms,acl,context --format json
+                             : Security audit: mode bits, POSIX ACLs, SELinux labels.
+      --hash blake3          : Content hash per file (sha256, blake3, md5).
+      --format ndjson        : One JSON record per file (text, json, ndjson).
+      --format csv --columns path,size,ext,hash
+                             : Spreadsheet-ready listing (csv or tsv).
+      --format tree          : Project structure overview, like `tree`.
+      --format html --content --output report.html
+                             : One shareable page: collapsible tree + highlighted files.
+      --template '## {{path}} ({{size}} bytes)\n```{{ext}}\n{{content}}```\n\n'
+                             : Custom per-file layout. Placeholders: path, name, ext,
+                               lang, size, mtime, hash, tokens, commit, author,
+                               committed, depth, index, content.
+      --content --header-template '<file path="{{path}}" lang="{{lang}}" n="{{index}}">'
+                             : Own header line per file instead of "=== path ===".
+      --preamble '# Bundle\n\n' --epilogue '--- end ---\n'
+                             : Text before / after all records (text output).
+      --format tar --compress gzip --output out.tgz
+      --content --compress zstd --output dump.txt.zst
+                             : Any format can be compressed (gzip, zstd).
+                             : Archive the matched files (tar or zip).
+      --format tar --no-perms: Release bundle with 0755 / 0644 modes, root owned
+                               (--numeric-owner: keep owners, ids only).
+      --sort size --reverse  : Order by name, size or mtime (largest first).
+
+    INPUT:
+      --files-from list.txt  : Process the listed paths instead of walking.
+      git ls-files | collect --files-from - --content
+
+    LLM PACKING:
+      --count-tokens         : Estimated tokens per file (~4 bytes per token).
+      --max-tokens 100000    : Stop once the token budget would be exceeded.
+      --budget-strategy drop-largest
+                             : Drop the largest files until the rest fits instead.
+      --max-files 500        : Stop after 500 files.
+      --max-output-bytes 50M : Stop once 50 MiB were written (records are kept whole).
+      --content --split-bytes 100K --output ctx.txt
+                             : Write ctx.txt.001, ctx.txt.002, ... under 100 KiB each,
+                               never cutting a file ("--- part 2 of 5 ---" on top).
+      --split-files 50       : At most 50 files per part (with or without --split-bytes).
 
     EXCLUDES:
-      Default: Ignores .git, target/, node_modules/ and hidden files.
-      --no-default-excludes  : Scan everything.
+      Default: Ignores .git, target/, node_modules/ and hidden files,
+               plus .gitignore / .collectignore entries.
+      --no-default-excludes  : Scan everything (.collectignore included).
       --include-hidden       : Include hidden files.
+      --same-file-system     : Stay on one file system (skip mount points).
       --exclude "log,tmp"    : Add custom exclusion patterns.
+      --exclude-from .collect-exclude
+                             : Exclude patterns from a file (gitignore syntax).
+      -g 'src/**/*.rs'       : Only paths matching a glob (repeatable).
+      -g '!**/tests/**'      : Exclude paths matching a glob.
+
+    CONFIG FILES:
+      Defaults are read from ~/.config/collect/config.toml and the nearest
+      .collect.toml (project keys win); flags on the command line always win.
+        extension = ["rs", "toml"]
+        exclude = ["target", "*.log"]
+        format = "ndjson"
+        content = true
+      --config team.toml     : Use this file instead.
+      --no-config            : Ignore all config files.
+
+    PRESETS:
+      --preset rust          : Apply a bundle of options (here: rs,toml, excluding target).
+      --preset python,docs   : Combine presets (their lists are merged).
+      Built-in: rust, python, node, go, java, web, docs. Define more in a config file:
+        [presets.backend]
+        extension = ["go", "sql"]
+        exclude = ["vendor"]
+
+    SUMMARY:
+      --count                : Print totals (files, dirs, bytes, time) instead of paths.
+      --count --by-extension : Add a per-extension breakdown.
+      --group-by ext --format json
+                             : Files and bytes per extension, as JSON records.
+      --stats-by-language    : Files and code / comment / blank lines per language.
+      --todos                : TODO / FIXME / HACK / XXX lines per file, with line numbers.
+      --todos --todo-markers TODO,SAFETY
+                             : Other markers (whole, case-sensitive words).
+      --scan-secrets --git staged
+                             : Leaked keys and tokens as path:line (exit 0 = found).
+      --report du --top 20   : The 20 directories holding the most matched bytes.
+      --report top-n         : The 10 largest matched files (--top N for more).
+      --stats --max-bytes 4096
+                             : Dry run: content bytes and tokens --content would emit.
+
+    ACTIONS:
+      --exec "wc -l {{}}"      : Run a command per file ({{}}, {{/}}, {{//}}, {{.}}, {{/.}}).
+      --exec-batch "wc -l"   : Run it once with all files appended (xargs-style).
+      -j 8                   : Run up to 8 commands at a time (0 = all cores).
+      --copy-to out/         : Copy matches into out/, keeping relative paths.
+      --move-to out/         : Move matches into out/ (copy + delete across disks).
+
+    CHANGE DETECTION:
+      --snapshot manifest.json
+                             : Save path, size, mtime and hash of every match.
+      --diff manifest.json   : Report added (+), removed (-), modified (M) files.
+      --diff m.json --snapshot m.json
+                             : Compare with the last run, then update the manifest.
+
+    WATCH MODE:
+      --watch --output ctx.txt
+                             : Regenerate ctx.txt whenever a matching file changes.
+
+    INTERACTIVE:
+      --interactive --content --output prompt.txt
+                             : Browse the matches (type to fuzzy search, Tab marks,
+                               ^A marks all, ^F/^B scroll the preview), Enter writes
+                               the marked files in any --format, Esc writes nothing.
+      --pick --content | pbcopy
+                             : Choose files in a fuzzy finder, copy them as a prompt.
+      --pick=readme --content: Starts from a query; a single match is taken directly.
+
+    SERVER:
+      collect serve --socket /tmp/collect.sock --preset rust --watch
+                             : Index the matches once, keep them fresh, and answer
+                               one JSON request per line on the socket:
+      {{"extension": ["rs"], "pattern": "TODO", "max_files": 20}}
+                             : -> {{"ok": true, "count": 3, "files": [{{"path": ...}}]}}
+      {{"glob": ["src/**"], "content": true, "format": "text"}}
+                             : -> {{"ok": true, "count": 9, "output": "..."}}
+      {{"op": "refresh"}} / {{"op": "ping"}}
+                             : Re-walk the tree now / report the indexed paths.
+      collect serve --http 127.0.0.1:8080
+                             : The same queries as URL parameters, streamed back:
+      curl '127.0.0.1:8080/list?extension=rs&glob=src/**'
+      curl '127.0.0.1:8080/content?pattern=TODO&format=ndjson'
+      collect serve --mcp --path ~/project --preset rust
+                             : MCP server on stdio for LLM agents: list_files,
+                               read_files and pack_context tools over the index.
+
+    COMMANDS (shorthand for the flat options; the rest apply unchanged):
+      collect list --extension rs
+                             : Paths only (same as no command, --content rejected).
+      collect pack --preset rust
+                             : Same as --content.
+      collect hash [MANIFEST]
+                             : Listing with --hash (default sha256), or --snapshot MANIFEST.
+      collect diff MANIFEST  : Same as --diff MANIFEST.
+      collect stats          : Same as --stats.
+      collect serve --socket PATH | --http ADDR | --mcp
+                             : Same as --serve PATH / --http ADDR / --mcp.
+
+    SHELL COMPLETIONS:
+      collect completions bash > ~/.local/share/bash-completion/completions/collect
+      collect completions zsh|fish|powershell|elvish
+                             : Print the script for that shell to stdout.
+
+    EXIT CODES:
+      0  At least one entry matched (or the reader closed the pipe early).
+      1  Nothing matched.
+      2  Fatal error, or any per-file error with --strict.
 
     PERFORMANCE TIPS:
       - Use --output for large datasets.
+      - Use --threads 0 to walk and read on all cores (output sorted by path).
       - Binary files are automatically detected and skipped.
     "#
     );
 }
 
+/// Renders the `--count` / `--stats` report.
+fn write_count(summary: &Summary, by_extension: bool, writer: &mut dyn Write) -> io::Result<()> {
+    let bytes = summary.bytes.unwrap_or(0);
+    writeln!(writer, "Files:       {}", summary.files)?;
+    writeln!(writer, "Directories: {}", summary.dirs)?;
+    if summary.loops > 0 {
+        writeln!(
+            writer,
+            "Loops:       {} symlink loop(s) skipped",
+            summary.loops
+        )?;
+    }
+    writeln!(
+        writer,
+        "Total size:  {} ({bytes} bytes)",
+        format_size(bytes)
+    )?;
+    if let Some(content) = summary.content_bytes {
+        writeln!(
+            writer,
+            "Content:     {} ({content} bytes) would be emitted",
+            format_size(content)
+        )?;
+        writeln!(
+            writer,
+            "Binary:      {} files (content suppressed)",
+            summary.binary_files.unwrap_or(0)
+        )?;
+    }
+    if let Some(tokens) = summary.tokens {
+        writeln!(writer, "Tokens:      ~{tokens}")?;
+    }
+    writeln!(writer, "Elapsed:     {:.2?}", summary.elapsed)?;
+    if let Some(limit) = summary.stopped {
+        writeln!(writer, "Stopped:     {limit}")?;
+    }
+
+    if by_extension && !summary.extensions.is_empty() {
+        writeln!(writer)?;
+        write_extension_table(&summary.extensions, writer)?;
+    }
+    Ok(())
+}
+
+/// Extensions by file count, most common first.
+fn extension_rows(extensions: &BTreeMap<String, ExtensionCount>) -> Vec<(&str, ExtensionCount)> {
+    let mut rows: Vec<_> = extensions
+        .iter()
+        .map(|(ext, totals)| (ext.as_str(), *totals))
+        .collect();
+    rows.sort_by(|a, b| b.1.files.cmp(&a.1.files).then(a.0.cmp(b.0)));
+    rows
+}
+
+fn write_extension_table(
+    extensions: &BTreeMap<String, ExtensionCount>,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(writer, "{:<16} {:>8} {:>12}", "Extension", "Files", "Size")?;
+    for (ext, totals) in extension_rows(extensions) {
+        let name = if ext.is_empty() { "(none)" } else { ext };
+        writeln!(
+            writer,
+            "{name:<16} {:>8} {:>12}",
+            totals.files,
+            format_size(totals.bytes)
+        )?;
+    }
+    Ok(())
+}
+
+/// `--group-by ext`: the extension table, or JSON records with exact byte
+/// counts for structured formats.
+fn write_groups(
+    extensions: &BTreeMap<String, ExtensionCount>,
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let records = extension_rows(extensions).into_iter().map(|(ext, totals)| {
+        serde_json::json!({ "extension": ext, "files": totals.files, "bytes": totals.bytes })
+    });
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut *writer, &records.collect::<Vec<_>>())?;
+            writeln!(writer)
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writeln!(writer)?;
+            }
+            Ok(())
+        }
+        _ => write_extension_table(extensions, writer),
+    }
+}
+
+/// Renders a `--report` table: size (human and exact) and path, plus the
+/// file count for directories.
+fn write_usage(rows: &[Usage], report: Report, writer: &mut dyn Write) -> io::Result<()> {
+    for row in rows {
+        let size = format_size(row.bytes);
+        match report {
+            Report::Du => writeln!(
+                writer,
+                "{size:>10} {:>14} {:>8} files  {}",
+                row.bytes, row.files, row.path
+            )?,
+            Report::TopN => writeln!(writer, "{size:>10} {:>14}  {}", row.bytes, row.path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Renders the `--stats-by-language` table, with a total row.
+fn write_languages(rows: &[LanguageCount], writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{:<18} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "Language", "Files", "Lines", "Code", "Comments", "Blanks"
+    )?;
+    let mut total = LanguageCount {
+        language: "Total".to_string(),
+        ..LanguageCount::default()
+    };
+    for row in rows {
+        write_language_row(row, writer)?;
+        total.files += row.files;
+        total.lines += row.lines;
+        total.code += row.code;
+        total.comments += row.comments;
+        total.blanks += row.blanks;
+    }
+    writeln!(writer, "{}", "-".repeat(71))?;
+    write_language_row(&total, writer)
+}
+
+fn write_language_row(row: &LanguageCount, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{:<18} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        row.language, row.files, row.lines, row.code, row.comments, row.blanks
+    )
+}
+
 // =============================================================================
 // MAIN ENTRY POINT
 // =============================================================================
 
-fn main() -> Result<()> {
+/// Exit codes: something matched, nothing matched, or the run failed
+/// (including per-file errors under --strict).
+const EXIT_MATCHED: u8 = 0;
+const EXIT_EMPTY: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
     // Initialize CLI
-    let cli = Cli::parse();
+    let cli = match parse_cli() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    let json_errors = cli.args.log_format == LogFormat::Json;
+    let result = Logger::install(cli.args.verbose, cli.args.quiet, cli.args.log_format)
+        .and_then(|()| run_cli(cli));
 
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            // Fatal errors become a log record too, so stderr stays machine-readable
+            if json_errors {
+                log::error!("{e:#}");
+            } else {
+                eprintln!("Error: {e:?}");
+            }
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run_cli(cli: Cli) -> Result<u8> {
+    if let Some(Commands::Completions { shell }) = cli.command {
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut cli_command(), "collect", &mut script);
+        match io::stdout().write_all(&script) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(e).context("Failed to write completion script");
+            }
+            _ => return Ok(EXIT_MATCHED),
+        }
+    }
     if cli.guide {
         print_guide();
-        return Ok(());
+        return Ok(EXIT_MATCHED);
+    }
+    let mut cli = cli.args;
+    if cli.type_list {
+        let aliases = type_aliases(&cli.type_add)?;
+        match write_type_list(&aliases, &mut io::stdout().lock()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(e).context("Failed to write the type list");
+            }
+            _ => return Ok(EXIT_MATCHED),
+        }
     }
+    resolve_type_aliases(&mut cli)?;
+
+    let mode = RunMode {
+        output: cli.output.clone(),
+        append: cli.append,
+        checkpoint: cli.checkpoint.clone(),
+        atomic: cli.atomic,
+        progress: cli.progress
+            && !cli.quiet
+            && io::stderr().is_terminal()
+            && (cli.output.is_some() || !io::stdout().is_terminal()),
+        count: cli.count,
+        stats: cli.stats,
+        languages: cli.stats_by_language,
+        report: cli.report.map(|report| (report, cli.top)),
+        group_by: cli.group_by,
+        secrets: cli.scan_secrets,
+        todos: cli.todos.then(|| {
+            if cli.todo_markers.is_empty() {
+                DEFAULT_TODO_MARKERS
+                    .iter()
+                    .map(|m| (*m).to_string())
+                    .collect()
+            } else {
+                cli.todo_markers.clone()
+            }
+        }),
+        format: cli.format,
+        by_extension: cli.by_extension,
+        snapshot: cli.snapshot.clone(),
+        diff: cli.diff.clone(),
+        exec: match (&cli.exec, &cli.exec_batch) {
+            (Some(cmd), _) => Some((CommandTemplate::parse(cmd)?, false)),
+            (None, Some(cmd)) => Some((CommandTemplate::parse(cmd)?, true)),
+            (None, None) => None,
+        },
+        transfer: match (&cli.copy_to, &cli.move_to) {
+            (Some(dir), _) => Some((dir.clone(), TransferMode::Copy)),
+            (None, Some(dir)) => Some((dir.clone(), TransferMode::Move)),
+            (None, None) => None,
+        },
+        jobs: match cli.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        },
+    };
+    let watch_mode = cli.watch;
+    let strict = cli.strict;
+    let socket = cli.serve.clone();
+    let http = cli.http.clone();
+    let mcp_mode = cli.mcp;
+    let picker = if cli.interactive {
+        Some((String::new(), false))
+    } else {
+        cli.pick.clone().map(|query| (query, true))
+    };
 
-    if cli.pattern.is_some() {
-        eprintln!("Info: --pattern is currently in TODO status. Ignoring.");
+    // Build Configuration
+    let mut options = cli.into_options();
+    if let Some((query, picker)) = picker {
+        let Some(selected) = pick(&options, query, picker)? else {
+            return Ok(EXIT_EMPTY);
+        };
+        options.selected = Some(selected);
+    }
+    if mcp_mode {
+        serve_mcp(options, watch_mode)?;
+        return Ok(EXIT_MATCHED);
     }
-    if cli.metadata.is_some() {
-        eprintln!("Info: --metadata is currently in TODO status. Ignoring.");
+    if socket.is_some() || http.is_some() {
+        serve_index(options, socket.as_deref(), http.as_deref(), watch_mode)?;
+        return Ok(EXIT_MATCHED);
     }
+    let collector = Collector::new(options)?;
 
-    // Build Configuration
-    let config = Arc::new(AppConfig::from_cli(cli)?);
+    let outcome = run(&collector, &mode)?;
+    if outcome != Outcome::Closed && watch_mode {
+        watch(&collector, &mode)?;
+    }
 
-    // Setup Output Strategy
-    let raw_writer: Box<dyn Write + Send> = match &config.output {
-        Some(path) => Box::new(File::create(path).context("Failed to create output file")?),
-        None => Box::new(io::stdout()),
-    };
+    let errors = collector.error_count();
+    if strict && errors > 0 {
+        log::error!(
+            "{errors} error{} during the run (--strict)",
+            if errors == 1 { "" } else { "s" }
+        );
+        return Ok(EXIT_ERROR);
+    }
+    Ok(match outcome {
+        Outcome::Empty => EXIT_EMPTY,
+        Outcome::Matched | Outcome::Closed => EXIT_MATCHED,
+    })
+}
+
+/// How a pass ended, for the exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Matched,
+    Empty,
+    /// The reader went away (BrokenPipe): stop quietly.
+    Closed,
+}
 
+impl Outcome {
+    fn from_count(count: usize) -> Self {
+        if count > 0 {
+            Self::Matched
+        } else {
+            Self::Empty
+        }
+    }
+}
+
+/// CLI switches that shape a run (everything else lives in `CollectOptions`).
+#[derive(Debug)]
+struct RunMode {
+    output: Option<PathBuf>,
+    /// --progress, already resolved against the terminal.
+    progress: bool,
+    append: bool,
+    atomic: bool,
+    checkpoint: Option<PathBuf>,
+    count: bool,
+    stats: bool,
+    /// `--stats-by-language` report instead of the listing.
+    languages: bool,
+    /// `--report` and its `--top` row limit.
+    report: Option<(Report, Option<usize>)>,
+    group_by: Option<GroupBy>,
+    /// `--scan-secrets` findings instead of the listing.
+    secrets: bool,
+    /// `--todos` with its markers.
+    todos: Option<Vec<String>>,
+    /// Decides between the text and JSON renderings of `--group-by`.
+    format: OutputFormat,
+    by_extension: bool,
+    snapshot: Option<PathBuf>,
+    diff: Option<PathBuf>,
+    /// Command template and whether to batch.
+    exec: Option<(CommandTemplate, bool)>,
+    transfer: Option<(PathBuf, TransferMode)>,
+    jobs: usize,
+}
+
+/// One collection pass into stdout or --output.
+fn run(collector: &Collector, mode: &RunMode) -> Result<Outcome> {
+    let options = collector.options();
+    if let Some(output) = &mode.output
+        && (options.split_bytes.is_some() || options.split_files.is_some())
+    {
+        return run_split(collector, output, mode);
+    }
     // Large buffer (64KB) for fewer syscalls
-    let writer = Arc::new(Mutex::new(BufWriter::with_capacity(64 * 1024, raw_writer)));
-
-    // Setup Walker (The Traversal Engine)
-    let mut builder = WalkBuilder::new(&config.base_path);
-    builder
-        .standard_filters(!config.no_default_excludes)
-        .hidden(!config.include_hidden)
-        .follow_links(config.follow_symlinks)
-        .max_depth(config.depth)
-        .threads(1); // Force single thread for deterministic output order
-
-    if let Some(excludes) = &config.exclude {
-        let mut override_builder = OverrideBuilder::new(&config.base_path);
-        for exc in excludes {
-            // ! negates the ignore, meaning "include", but in .gitignore syntax
-            // ! matches mean exclude if using ignore builder carefully.
-            // But here standard convention for cli override is just passed patterns.
-            // Let's assume standard gitignore logic: "foo" ignores foo.
-            override_builder.add(&format!("!{}", exc))?;
-        }
-        builder.overrides(override_builder.build()?);
-    }
-
-    let walker = builder.build();
-    let start = Instant::now();
-    let mut count = 0;
-
-    // Execution
-    for result in walker {
-        match result {
-            Ok(entry) => {
-                let path = entry.path();
-
-                // Skip root itself
-                if entry.depth() == 0 {
-                    continue;
-                }
+    let mut writer = BufWriter::with_capacity(64 * 1024, Output::open(mode)?);
+    let outcome = if mode.progress {
+        with_progress(collector, || run_into(collector, mode, &mut writer))?
+    } else {
+        run_into(collector, mode, &mut writer)?
+    };
+    // Errors return early and leave an --atomic target untouched
+    if outcome == Outcome::Closed {
+        return Ok(outcome);
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context("Failed to write output")?
+        .commit()?;
+    Ok(outcome)
+}
 
-                let is_dir = entry.file_type().map(|f| f.is_dir()).unwrap_or(false);
-
-                // Apply Filters
-                if should_process(path, &config, is_dir) && !is_dir {
-                    let mut w_guard = writer
-                        .lock()
-                        .expect("Unexpected error trying lock writter.");
-
-                    // Handle IO errors directly
-                    if let Err(e) = process_file(path, &config, &mut w_guard) {
-                        // Gracefully exit on BrokenPipe (e.g., piped to `head`)
-                        if e.kind() == io::ErrorKind::BrokenPipe {
-                            return Ok(());
-                        }
-                        if !config.quiet {
-                            eprintln!("Error processing {}: {}", path.display(), e);
-                        }
-                    }
-                    count += 1;
-                }
+fn run_into(
+    collector: &Collector,
+    mode: &RunMode,
+    writer: &mut BufWriter<Output>,
+) -> Result<Outcome> {
+    if let Some((template, batch)) = &mode.exec {
+        let summary = collector.exec(template, *batch, mode.jobs)?;
+        if summary.failures > 0 {
+            anyhow::bail!("{} of {} commands failed", summary.failures, summary.runs);
+        }
+        return Ok(Outcome::from_count(summary.runs));
+    }
+
+    if let Some((dest, transfer_mode)) = &mode.transfer {
+        let summary = collector.transfer(dest, *transfer_mode)?;
+        let verb = match transfer_mode {
+            TransferMode::Copy => "Copied",
+            TransferMode::Move => "Moved",
+        };
+        log::info!(
+            "{verb} {} files ({}) to {}",
+            summary.files,
+            format_size(summary.bytes),
+            dest.display()
+        );
+        if summary.failures > 0 {
+            anyhow::bail!("{} files could not be transferred", summary.failures);
+        }
+        return Ok(Outcome::from_count(summary.files));
+    }
+
+    if mode.snapshot.is_some() || mode.diff.is_some() {
+        return run_manifest(collector, mode, writer);
+    }
+
+    if let Some(markers) = &mode.todos {
+        let todos = collector.todos(markers)?;
+        return match write_todos(&todos, mode.format, writer).and_then(|()| writer.flush()) {
+            Ok(()) => Ok(Outcome::from_count(todos.len())),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if mode.secrets {
+        let findings = collector.scan_secrets()?;
+        return match write_findings(&findings, mode.format, writer).and_then(|()| writer.flush()) {
+            Ok(()) => Ok(Outcome::from_count(findings.len())),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if let Some(GroupBy::Ext) = mode.group_by {
+        let summary = collector.count()?;
+        return match write_groups(&summary.extensions, mode.format, writer)
+            .and_then(|()| writer.flush())
+        {
+            Ok(()) => Ok(Outcome::from_count(summary.files)),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if let Some((report, top)) = mode.report {
+        let rows = match report {
+            Report::Du => {
+                let mut rows = collector.disk_usage()?;
+                rows.truncate(top.unwrap_or(usize::MAX));
+                rows
+            }
+            Report::TopN => collector.largest(top.unwrap_or(10))?,
+        };
+        return match write_usage(&rows, report, writer).and_then(|()| writer.flush()) {
+            Ok(()) => Ok(Outcome::from_count(rows.len())),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if mode.languages {
+        let rows = collector.languages()?;
+        return match write_languages(&rows, writer).and_then(|()| writer.flush()) {
+            Ok(()) => Ok(Outcome::from_count(rows.len())),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    if mode.count || mode.stats {
+        let summary = if mode.stats {
+            collector.stats()?
+        } else {
+            collector.count()?
+        };
+        return match write_count(&summary, mode.by_extension, writer).and_then(|()| writer.flush())
+        {
+            Ok(()) => Ok(Outcome::from_count(summary.files)),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(Outcome::Closed),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    // Execution (files take the kernel copy path for content)
+    let result = match writer.get_ref().file() {
+        Some(file) => collector.write_file(file),
+        None => collector.write(writer),
+    };
+    let summary = match result.and_then(|s| writer.flush().map(|()| s)) {
+        Ok(summary) => summary,
+        // Gracefully exit on BrokenPipe (e.g., piped to `head`)
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(Outcome::Closed),
+        Err(e) => return Err(e.into()),
+    };
+
+    let tokens = summary
+        .tokens
+        .map(|t| format!(" (~{t} tokens)"))
+        .unwrap_or_default();
+    let message = format!(
+        "Done. Processed {} files{} in {:.2?}",
+        summary.files, tokens, summary.elapsed
+    );
+    if mode.output.is_none() {
+        log::info!("{message}");
+    } else {
+        log::debug!("{message}");
+    }
+    if summary.loops > 0 {
+        log::warn!("Skipped {} symlink loop(s)", summary.loops);
+    }
+    if let Some(limit) = summary.stopped {
+        log::warn!("Stopped early: {limit}");
+    }
+
+    Ok(Outcome::from_count(summary.files))
+}
+
+/// `--split-bytes` / `--split-files`: numbered parts instead of --output itself.
+fn run_split(collector: &Collector, output: &Path, mode: &RunMode) -> Result<Outcome> {
+    let split = if mode.progress {
+        with_progress(collector, || collector.write_split(output))
+    } else {
+        collector.write_split(output)
+    }
+    .with_context(|| format!("Failed to write the parts of {}", output.display()))?;
+
+    let summary = &split.summary;
+    if let (Some(first), Some(last)) = (split.parts.first(), split.parts.last()) {
+        log::info!(
+            "Wrote {} files into {} part{} ({} to {}) in {:.2?}",
+            summary.files,
+            split.parts.len(),
+            if split.parts.len() == 1 { "" } else { "s" },
+            first.display(),
+            last.display(),
+            summary.elapsed
+        );
+    }
+    if summary.loops > 0 {
+        log::warn!("Skipped {} symlink loop(s)", summary.loops);
+    }
+    if let Some(limit) = summary.stopped {
+        log::warn!("Stopped early: {limit}");
+    }
+    Ok(Outcome::from_count(summary.files))
+}
+
+/// `--snapshot` / `--diff`: hash the matches, compare and/or save the manifest.
+fn run_manifest(collector: &Collector, mode: &RunMode, writer: &mut dyn Write) -> Result<Outcome> {
+    // The old manifest decides the algorithm so digests stay comparable
+    let previous = mode.diff.as_deref().map(Manifest::load).transpose()?;
+    let algorithm = match &previous {
+        Some(manifest) => manifest.algorithm()?,
+        None => collector.options().hash.unwrap_or(HashAlgorithm::Sha256),
+    };
+    let current = collector.snapshot(algorithm)?;
+
+    if let Some(previous) = &previous {
+        let diff = previous.diff(&current);
+        match write_diff(&diff, collector.options().format, writer).and_then(|()| writer.flush()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(Outcome::Closed),
+            Err(e) => return Err(e.into()),
+        }
+        log::info!(
+            "{} added, {} removed, {} modified",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.modified.len()
+        );
+    }
+
+    if let Some(path) = &mode.snapshot {
+        current.save(path)?;
+        log::info!(
+            "Snapshot of {} files written to {}",
+            current.files.len(),
+            path.display()
+        );
+    }
+    Ok(Outcome::from_count(current.files.len()))
+}
+
+/// The path, then `  line: text` for each of its markers (like
+/// `--show-matches`), or JSON records for structured formats.
+fn write_todos(todos: &[Todo], format: OutputFormat, writer: &mut dyn Write) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut *writer, todos)?;
+            writeln!(writer)
+        }
+        OutputFormat::Ndjson => {
+            for todo in todos {
+                serde_json::to_writer(&mut *writer, todo)?;
+                writeln!(writer)?;
             }
-            Err(err) => {
-                if !config.quiet {
-                    eprintln!("Traversal Error: {}", err);
+            Ok(())
+        }
+        _ => {
+            let mut current = None;
+            for todo in todos {
+                if current != Some(&todo.path) {
+                    writeln!(writer, "{}", todo.path)?;
+                    current = Some(&todo.path);
                 }
+                writeln!(writer, "  {}: {}", todo.line, todo.text)?;
             }
+            Ok(())
         }
     }
+}
 
-    // Flush remaining buffer
-    {
-        let mut w = writer
-            .lock()
-            .expect("Unexpected error trying lock writter.");
-        if let Err(e) = w.flush()
-            && e.kind() != io::ErrorKind::BrokenPipe
-        {
-            return Err(e.into());
+/// `path:line: rule: secret` lines, or JSON records for structured formats.
+fn write_findings(
+    findings: &[Finding],
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut *writer, findings)?;
+            writeln!(writer)
+        }
+        OutputFormat::Ndjson => {
+            for finding in findings {
+                serde_json::to_writer(&mut *writer, finding)?;
+                writeln!(writer)?;
+            }
+            Ok(())
+        }
+        _ => {
+            for finding in findings {
+                writeln!(
+                    writer,
+                    "{}:{}: {}: {}",
+                    finding.path, finding.line, finding.rule, finding.secret
+                )?;
+            }
+            Ok(())
         }
     }
+}
 
-    if !config.quiet && config.output.is_none() {
-        eprintln!("Done. Processed {} files in {:.2?}", count, start.elapsed());
+/// `+ path` / `- path` / `M path` lines, or one JSON object for structured formats.
+fn write_diff(diff: &ManifestDiff, format: OutputFormat, writer: &mut dyn Write) -> io::Result<()> {
+    if matches!(format, OutputFormat::Json | OutputFormat::Ndjson) {
+        serde_json::to_writer(&mut *writer, diff)?;
+        return writeln!(writer);
     }
 
+    for (marker, paths) in [
+        ("+", &diff.added),
+        ("-", &diff.removed),
+        ("M", &diff.modified),
+    ] {
+        for path in paths {
+            writeln!(writer, "{marker} {path}")?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,112 @@
+//! File manifests for `--snapshot` / `--diff` change detection.
+
+use crate::options::HashAlgorithm;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Path, size, mtime and content hash of every collected file, sorted by path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Hash algorithm name (`sha256`, `blake3`, `md5`).
+    pub hash: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the base path, `/` separated.
+    pub path: String,
+    pub size: u64,
+    /// RFC 3339 UTC; informational only, ignored by `diff`.
+    pub mtime: String,
+    pub digest: String,
+}
+
+/// Changes between two manifests, each list sorted by path.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Same path, different size or digest.
+    pub modified: Vec<String>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Manifest {
+    pub(crate) fn new(algorithm: HashAlgorithm, mut files: Vec<ManifestEntry>) -> Self {
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            version: MANIFEST_VERSION,
+            hash: algorithm.name().to_string(),
+            files,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open manifest {}", path.display()))?;
+        let manifest: Self = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Invalid manifest {}", path.display()))?;
+        if manifest.version != MANIFEST_VERSION {
+            anyhow::bail!(
+                "Unsupported manifest version {} in {}",
+                manifest.version,
+                path.display()
+            );
+        }
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create manifest {}", path.display()))?,
+        );
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Algorithm the digests were computed with.
+    pub fn algorithm(&self) -> Result<HashAlgorithm> {
+        HashAlgorithm::from_str(&self.hash, true)
+            .map_err(|_| anyhow::anyhow!("Unknown hash algorithm '{}' in manifest", self.hash))
+    }
+
+    /// What changed from `self` (the old run) to `current`.
+    pub fn diff(&self, current: &Self) -> ManifestDiff {
+        let old: BTreeMap<_, _> = self.files.iter().map(|f| (f.path.as_str(), f)).collect();
+        let new: BTreeMap<_, _> = current.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+        let mut diff = ManifestDiff::default();
+        for (path, file) in &new {
+            match old.get(path) {
+                None => diff.added.push((*path).to_string()),
+                Some(prev) if prev.size != file.size || prev.digest != file.digest => {
+                    diff.modified.push((*path).to_string());
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|path| !new.contains_key(*path))
+            .map(|path| (*path).to_string())
+            .collect();
+        diff
+    }
+}
@@ -0,0 +1,274 @@
+//! `--mcp`: a Model Context Protocol server on stdin/stdout whose tools
+//! query the index of `serve`.
+
+use crate::collector::Collector;
+use crate::options::{CollectOptions, OutputFormat};
+use crate::serve::{Request, Server, follow_changes, parse_value};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, PoisonError};
+
+/// MCP revisions spoken, newest first; a client asking for another one gets
+/// the newest.
+const MCP_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// `--mcp`: a Model Context Protocol server on stdin/stdout (JSON-RPC, one
+/// message per line) whose tools run over the index. Returns when the client
+/// closes stdin; a --watch thread goes down with the process.
+pub fn mcp(options: CollectOptions, watch_mode: bool) -> Result<()> {
+    let server = Arc::new(Server::new(options)?);
+    server.refresh()?;
+    if watch_mode {
+        let watched = Arc::clone(&server);
+        std::thread::spawn(move || follow_changes(&watched, &[]));
+    }
+    log::info!("Serving MCP on stdin/stdout");
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Failed to read from stdin")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.mcp_message(&message),
+            Err(e) => Some(rpc_error(
+                &Value::Null,
+                -32700,
+                &format!("Parse error: {e}"),
+            )),
+        };
+        let Some(reply) = reply else {
+            continue;
+        };
+        match writeln!(stdout, "{reply}").and_then(|()| stdout.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            result => result.context("Failed to write to stdout")?,
+        }
+    }
+    Ok(())
+}
+
+fn rpc_error(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Arguments of the read_files tool.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ReadFiles {
+    /// As list_files prints them (relative to --path).
+    paths: Vec<String>,
+    #[serde(default)]
+    max_bytes: Option<u64>,
+}
+
+impl Server {
+    /// The reply to one JSON-RPC message; `None` for notifications and for
+    /// responses (this server sends no requests).
+    fn mcp_message(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?;
+        let method = message.get("method")?.as_str().unwrap_or_default();
+        let params = message.get("params").unwrap_or(&Value::Null);
+        log::debug!("MCP {method}");
+        let result = match method {
+            "initialize" => {
+                let asked = params.get("protocolVersion").and_then(Value::as_str);
+                let version = MCP_VERSIONS
+                    .iter()
+                    .find(|v| Some(**v) == asked)
+                    .or(MCP_VERSIONS.first());
+                json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "collect", "version": env!("CARGO_PKG_VERSION") },
+                    "instructions": "Files under the served directory, already filtered by the \
+                        server's options. Call list_files to narrow them down, then read_files \
+                        for a few of them or pack_context for a whole set as one bundle.",
+                })
+            }
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": mcp_tools() }),
+            "tools/call" => {
+                let Some(name) = params.get("name").and_then(Value::as_str) else {
+                    return Some(rpc_error(id, -32602, "Missing tool name"));
+                };
+                let arguments = params
+                    .get("arguments")
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                let output = match name {
+                    "list_files" => self.list_files(arguments),
+                    "read_files" => self.read_files(arguments),
+                    "pack_context" => self.pack_context(arguments),
+                    _ => return Some(rpc_error(id, -32602, &format!("Unknown tool: {name}"))),
+                };
+                // Failures are results the model can read and correct
+                let (text, is_error) = match output {
+                    Ok(text) => (text, false),
+                    Err(e) => (format!("{e:#}"), true),
+                };
+                json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+            }
+            _ => {
+                return Some(rpc_error(
+                    id,
+                    -32601,
+                    &format!("Method not found: {method}"),
+                ));
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    fn list_files(&self, arguments: Value) -> Result<String> {
+        let mut options = self.tool_options(arguments)?;
+        options.read_content = false;
+        tool_text(options)
+    }
+
+    fn pack_context(&self, arguments: Value) -> Result<String> {
+        let mut options = self.tool_options(arguments)?;
+        options.read_content = true;
+        tool_text(options)
+    }
+
+    /// The given files, which must be in the index, with their content.
+    fn read_files(&self, arguments: Value) -> Result<String> {
+        let request: ReadFiles = serde_json::from_value(arguments).context("Invalid arguments")?;
+        let index = self.index.read().unwrap_or_else(PoisonError::into_inner);
+        let base = &self.base.base_path;
+        let selected = request
+            .paths
+            .iter()
+            .map(|path| {
+                let wanted = base.join(path);
+                index
+                    .iter()
+                    .find(|indexed| **indexed == wanted || indexed.as_path() == Path::new(path))
+                    .cloned()
+                    .with_context(|| format!("{path} is not among the files (see list_files)"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        drop(index);
+
+        let mut options = self.base.clone();
+        options.selected = Some(selected);
+        options.read_content = true;
+        options.format = OutputFormat::Text;
+        if request.max_bytes.is_some() {
+            options.max_bytes = request.max_bytes;
+        }
+        tool_text(options)
+    }
+
+    /// A query's options from tool arguments, rendered as text unless they
+    /// ask for another `format`.
+    fn tool_options(&self, arguments: Value) -> Result<CollectOptions> {
+        let request: Request = serde_json::from_value(arguments).context("Invalid arguments")?;
+        if request.op.is_some() || request.content {
+            anyhow::bail!("Unknown argument (see the tool's input schema)");
+        }
+        let format = request
+            .format
+            .as_deref()
+            .map(|format| parse_value::<OutputFormat>(format, "format"))
+            .transpose()?
+            .unwrap_or(OutputFormat::Text);
+        if format.is_archive() {
+            anyhow::bail!("Archive formats are not served");
+        }
+        let mut options = self.options(request)?;
+        options.format = format;
+        Ok(options)
+    }
+}
+
+fn tool_text(options: CollectOptions) -> Result<String> {
+    let collector = Collector::new(options)?;
+    let mut buffer = Vec::new();
+    let summary = collector.write(&mut buffer)?;
+    if summary.files == 0 {
+        return Ok("No files matched.".to_string());
+    }
+    let mut text = String::from_utf8_lossy(&buffer).into_owned();
+    if let Some(limit) = summary.stopped {
+        text.push_str(&format!("\n[Stopped early: {limit}]\n"));
+    }
+    Ok(text)
+}
+
+/// The tools with their JSON Schemas; the filters mirror the CLI flags.
+fn mcp_tools() -> Value {
+    let string_list = |description: &str| json!({ "type": "array", "items": { "type": "string" }, "description": description });
+    let filters = json!({
+        "extension": string_list("Only these extensions, e.g. [\"rs\", \"toml\"]."),
+        "no_extension": string_list("Leave out these extensions."),
+        "regex": string_list("Regexes on the file name (or path, see scope)."),
+        "scope": {
+            "type": "string",
+            "enum": ["name", "path", "content"],
+            "description": "What regex matches against (default name)."
+        },
+        "ignore_case": { "type": "boolean", "description": "Case-insensitive regex and pattern." },
+        "fixed_strings": { "type": "boolean", "description": "regex and pattern are literal text." },
+        "glob": string_list("Gitignore-style globs relative to the root, e.g. [\"src/**\", \"!**/tests/**\"]."),
+        "filter": string_list("Filter expressions, e.g. [\"size > 10k && ext == 'rs'\"]."),
+        "pattern": { "type": "string", "description": "Only files with a line matching this regex." },
+        "min_size": { "type": "string", "description": "e.g. \"1K\"." },
+        "max_size": { "type": "string", "description": "e.g. \"100K\"." },
+        "newer_than": { "type": "string", "description": "A date or duration ago, e.g. \"2d\"." },
+        "older_than": { "type": "string", "description": "A date or duration ago, e.g. \"2024-01-01\"." },
+        "max_files": { "type": "integer", "minimum": 0, "description": "Stop after this many files." },
+        "format": {
+            "type": "string",
+            "enum": ["text", "json", "ndjson", "csv", "tsv", "tree", "html"],
+            "description": "Rendering (default text)."
+        },
+    });
+    let mut pack = filters.clone();
+    if let Value::Object(properties) = &mut pack {
+        properties.insert(
+            "max_bytes".into(),
+            json!({ "type": "integer", "minimum": 0, "description": "Cut each file after this many bytes." }),
+        );
+        properties.insert(
+            "max_tokens".into(),
+            json!({ "type": "integer", "minimum": 0, "description": "Token budget for the whole bundle." }),
+        );
+    }
+    json!([
+        {
+            "name": "list_files",
+            "description": "List the paths of the files matching the filters (no content). \
+                Without arguments, every file the server serves.",
+            "inputSchema": { "type": "object", "properties": filters, "additionalProperties": false },
+            "annotations": { "readOnlyHint": true },
+        },
+        {
+            "name": "read_files",
+            "description": "Read the content of the given files, paths as list_files prints them.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "paths": string_list("Files to read."),
+                    "max_bytes": { "type": "integer", "minimum": 0, "description": "Cut each file after this many bytes." },
+                },
+                "required": ["paths"],
+                "additionalProperties": false,
+            },
+            "annotations": { "readOnlyHint": true },
+        },
+        {
+            "name": "pack_context",
+            "description": "Bundle the files matching the filters with their content into one \
+                document, within an optional token budget: the quickest way to put a part of \
+                the tree into context.",
+            "inputSchema": { "type": "object", "properties": pack, "additionalProperties": false },
+            "annotations": { "readOnlyHint": true },
+        },
+    ])
+}
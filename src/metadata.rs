@@ -0,0 +1,184 @@
+//! Metadata fields for `--metadata` and structured records.
+
+use crate::mime::detect_mime;
+use crate::options::{BinaryCheck, MetaField};
+use crate::owners::{group_name, ownership, user_name};
+use crate::xattrs::{acl, security_context};
+use serde_json::Value;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Collects the requested metadata fields as (name, value) pairs.
+/// Sizes stay numeric so structured formats keep their type.
+pub(crate) fn metadata_values(
+    path: &Path,
+    meta: &Metadata,
+    fields: &[MetaField],
+    check: BinaryCheck,
+) -> Vec<(&'static str, Value)> {
+    fields
+        .iter()
+        .map(|field| match field {
+            MetaField::Size => ("size", Value::from(meta.len())),
+            MetaField::Mtime => ("mtime", Value::from(format_time(meta.modified().ok()))),
+            MetaField::Ctime => ("ctime", Value::from(format_time(change_time(meta)))),
+            MetaField::Perms => ("perms", Value::from(format_perms(meta))),
+            MetaField::Owner => ("owner", Value::from(owner(meta, |(uid, _)| user_name(uid)))),
+            MetaField::Group => (
+                "group",
+                Value::from(owner(meta, |(_, gid)| group_name(gid))),
+            ),
+            MetaField::Acl => ("acl", Value::from(acl(path).unwrap_or_else(|| "-".into()))),
+            MetaField::Context => (
+                "context",
+                Value::from(security_context(path).unwrap_or_else(|| "-".into())),
+            ),
+            MetaField::Type => ("type", Value::from(file_type_name(meta))),
+            MetaField::Mime => ("mime", Value::from(file_mime(path, meta, check))),
+        })
+        .collect()
+}
+
+/// Renders the requested metadata fields as `key=value` pairs.
+pub(crate) fn format_metadata(
+    path: &Path,
+    meta: &Metadata,
+    fields: &[MetaField],
+    check: BinaryCheck,
+) -> String {
+    metadata_values(path, meta, fields, check)
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(s) => format!("{key}={s}"),
+            other => format!("{key}={other}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Detected MIME type of a regular file; `inode/directory` and friends
+/// otherwise, `-` when unreadable.
+pub(crate) fn file_mime(path: &Path, meta: &Metadata, check: BinaryCheck) -> &'static str {
+    if meta.is_dir() {
+        "inode/directory"
+    } else if meta.is_symlink() {
+        "inode/symlink"
+    } else {
+        detect_mime(path, check).unwrap_or("-")
+    }
+}
+
+/// Status change time on Unix, creation time elsewhere.
+#[cfg(unix)]
+fn change_time(meta: &Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let secs = u64::try_from(meta.ctime()).ok()?;
+    UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs))
+}
+
+#[cfg(not(unix))]
+fn change_time(meta: &Metadata) -> Option<SystemTime> {
+    meta.created().ok()
+}
+
+#[cfg(unix)]
+fn format_perms(meta: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    format!("{:04o}", meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn format_perms(meta: &Metadata) -> String {
+    if meta.permissions().readonly() {
+        "ro"
+    } else {
+        "rw"
+    }
+    .to_string()
+}
+
+/// A name from the file's `(uid, gid)`; `-` where files have no Unix owner.
+fn owner(meta: &Metadata, name: impl FnOnce((u32, u32)) -> String) -> String {
+    ownership(meta).map_or_else(|| "-".to_string(), name)
+}
+
+fn file_type_name(meta: &Metadata) -> &'static str {
+    let ft = meta.file_type();
+    if ft.is_symlink() {
+        "symlink"
+    } else if ft.is_dir() {
+        "dir"
+    } else if ft.is_file() {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// Formats a timestamp as UTC RFC 3339 (`2024-01-31T12:00:00Z`) without pulling in a date crate.
+pub fn format_time(time: Option<SystemTime>) -> String {
+    let Some(t) = time.map(UtcTime::from) else {
+        return "-".to_string();
+    };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+/// Broken-down UTC calendar time.
+pub(crate) struct UtcTime {
+    pub(crate) year: i64,
+    pub(crate) month: i64,
+    pub(crate) day: i64,
+    pub(crate) hour: i64,
+    pub(crate) minute: i64,
+    pub(crate) second: i64,
+}
+
+impl From<SystemTime> for UtcTime {
+    fn from(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => i64::try_from(d.as_secs()).unwrap_or(i64::MAX),
+            Err(e) => -i64::try_from(e.duration().as_secs()).unwrap_or(i64::MAX),
+        };
+
+        let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: rem / 3600,
+            minute: (rem % 3600) / 60,
+            second: rem % 60,
+        }
+    }
+}
+
+/// (year, month, day) to days since 1970-01-01. Inverse of `civil_from_days`.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Days since 1970-01-01 to (year, month, day). Algorithm by Howard Hinnant.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
@@ -0,0 +1,116 @@
+//! Content-based file type detection (`--mime`, the `mime` metadata field).
+
+use crate::binary::{PROBE_LEN, looks_binary, utf16_bom};
+use crate::options::BinaryCheck;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Signatures of two printable bytes (`MZ`, `BM`), which also start plain
+/// words; they only count for content that looks binary.
+const WEAK_SIGNATURES: &[&str] = &["application/vnd.microsoft.portable-executable", "image/bmp"];
+
+/// Interpreters named by a `#!` line.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("sh", "text/x-shellscript"),
+    ("bash", "text/x-shellscript"),
+    ("zsh", "text/x-shellscript"),
+    ("dash", "text/x-shellscript"),
+    ("ksh", "text/x-shellscript"),
+    ("fish", "text/x-shellscript"),
+    ("python", "text/x-python"),
+    ("python3", "text/x-python"),
+    ("python2", "text/x-python"),
+    ("perl", "text/x-perl"),
+    ("ruby", "text/x-ruby"),
+    ("node", "text/javascript"),
+    ("deno", "text/javascript"),
+    ("php", "text/x-php"),
+    ("lua", "text/x-lua"),
+    ("awk", "text/x-awk"),
+    ("tclsh", "text/x-tcl"),
+];
+
+/// MIME type of a file from its first bytes, with no regard to its name:
+/// the signatures `infer` knows, then `#!` interpreters and a few markup
+/// prologues, then `text/plain` or `application/octet-stream` (as `check`
+/// decides).
+pub(crate) fn detect_mime(path: &Path, check: BinaryCheck) -> io::Result<&'static str> {
+    let mut head = Vec::with_capacity(PROBE_LEN);
+    File::open(path)?
+        .take(PROBE_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(mime_of(&head, check))
+}
+
+fn mime_of(head: &[u8], check: BinaryCheck) -> &'static str {
+    if head.is_empty() {
+        return "application/x-empty";
+    }
+    // `infer` knows the binary formats; its few text ones (any `#!` is a
+    // shell script to it) are told apart more finely below
+    if let Some(kind) = infer::get(head)
+        && kind.matcher_type() != infer::MatcherType::Text
+        && (!WEAK_SIGNATURES.contains(&kind.mime_type()) || looks_binary(head, BinaryCheck::Loose))
+    {
+        return kind.mime_type();
+    }
+    if looks_binary(head, check) {
+        return "application/octet-stream";
+    }
+    if utf16_bom(head, check).is_some() {
+        return "text/plain";
+    }
+    if let Some(mime) = shebang(head) {
+        return mime;
+    }
+    let start = head.trim_ascii_start();
+    let lower = start
+        .get(..start.len().min(64))
+        .unwrap_or(start)
+        .to_ascii_lowercase();
+    if lower.starts_with(b"<?xml") {
+        if head.windows(4).any(|w| w == b"<svg") {
+            return "image/svg+xml";
+        }
+        return "application/xml";
+    }
+    if lower.starts_with(b"<svg") {
+        return "image/svg+xml";
+    }
+    if lower.starts_with(b"<!doctype html") || lower.starts_with(b"<html") {
+        return "text/html";
+    }
+    "text/plain"
+}
+
+/// `#!/usr/bin/env python3 -u` and `#!/bin/sh` name their interpreter.
+fn shebang(head: &[u8]) -> Option<&'static str> {
+    let line = head.strip_prefix(b"#!")?;
+    let line = line.split(|&b| b == b'\n').next().unwrap_or(line);
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    // python3.12, perl5.36...
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| *interpreter == program || *interpreter == name)
+        .map(|&(_, mime)| mime)
+}
+
+/// `image/*`, `*/*`, `*` or an exact type, case-insensitive.
+pub(crate) fn mime_matches(pattern: &str, mime: &str) -> bool {
+    if pattern == "*" || pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime
+            .split_once('/')
+            .is_some_and(|(top, _)| top.eq_ignore_ascii_case(kind)),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
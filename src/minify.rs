@@ -0,0 +1,179 @@
+//! `--strip-comments` / `--squeeze-blank`: shrinking text content.
+
+use crate::languages::{Language, language_of};
+use std::path::Path;
+
+/// Drops comments (as the file's language writes them) and/or collapses
+/// runs of blank lines into one. Lines left empty by a removed comment go
+/// too; a leading `#!` line is kept. String literals are skipped over, so
+/// `"// not a comment"` survives, but the scanner is no parser: an unusual
+/// literal can hide a comment from it.
+pub(crate) fn minify(path: &Path, text: &str, strip_comments: bool, squeeze_blank: bool) -> String {
+    let mut scanner = strip_comments.then(|| Scanner::new(language_of(path)));
+    let mut out = String::with_capacity(text.len());
+    let mut last_blank = false;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let body = line.trim_end_matches(['\n', '\r']);
+        let newline = line.get(body.len()..).unwrap_or_default();
+        let kept = match scanner.as_mut() {
+            Some(_) if index == 0 && body.starts_with("#!") => body.to_string(),
+            Some(scanner) => match scanner.line(body) {
+                Some(kept) => kept,
+                None => continue,
+            },
+            None => body.to_string(),
+        };
+        let blank = kept.trim().is_empty();
+        if squeeze_blank && blank && last_blank {
+            continue;
+        }
+        last_blank = blank;
+        out.push_str(&kept);
+        out.push_str(newline);
+    }
+    out
+}
+
+/// Where a line ends up in the language's syntax, carried across lines.
+#[derive(Clone, Copy)]
+enum State {
+    Code,
+    Block,
+    /// Inside a string closed by this quote.
+    Quoted(char),
+    /// Inside a Rust raw string closed by `"` and this many `#`.
+    Raw(usize),
+}
+
+struct Scanner {
+    language: &'static Language,
+    state: State,
+}
+
+impl Scanner {
+    fn new(language: &'static Language) -> Self {
+        Self {
+            language,
+            state: State::Code,
+        }
+    }
+
+    /// The line without its comments, trailing space trimmed when one was
+    /// removed; `None` when nothing but comments and space was on it.
+    fn line(&mut self, line: &str) -> Option<String> {
+        let mut out = String::with_capacity(line.len());
+        let mut stripped = matches!(self.state, State::Block);
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            match self.state {
+                State::Block => {
+                    let close = self.language.block.map_or("", |(_, close)| close);
+                    match rest.find(close).filter(|_| !close.is_empty()) {
+                        Some(end) => {
+                            rest = rest.get(end + close.len()..).unwrap_or_default();
+                            self.state = State::Code;
+                        }
+                        None => rest = "",
+                    }
+                }
+                State::Quoted(quote) => {
+                    let len = if c == '\\' {
+                        rest.chars().take(2).map(char::len_utf8).sum()
+                    } else {
+                        c.len_utf8()
+                    };
+                    if c == quote {
+                        self.state = State::Code;
+                    }
+                    out.push_str(rest.get(..len).unwrap_or(rest));
+                    rest = rest.get(len..).unwrap_or_default();
+                }
+                State::Raw(hashes) => {
+                    let close = format!("\"{}", "#".repeat(hashes));
+                    match rest.find(&close) {
+                        Some(end) => {
+                            let end = end + close.len();
+                            out.push_str(rest.get(..end).unwrap_or(rest));
+                            rest = rest.get(end..).unwrap_or_default();
+                            self.state = State::Code;
+                        }
+                        None => {
+                            out.push_str(rest);
+                            rest = "";
+                        }
+                    }
+                }
+                State::Code => {
+                    if let Some((open, _)) = self.language.block
+                        && rest.starts_with(open)
+                    {
+                        rest = rest.get(open.len()..).unwrap_or_default();
+                        self.state = State::Block;
+                        stripped = true;
+                    } else if self
+                        .language
+                        .line
+                        .iter()
+                        .any(|marker| starts_comment(&out, rest, marker))
+                    {
+                        stripped = true;
+                        rest = "";
+                    } else if let Some(len) = self.literal(&out, rest) {
+                        out.push_str(rest.get(..len).unwrap_or(rest));
+                        rest = rest.get(len..).unwrap_or_default();
+                    } else {
+                        if u8::try_from(c).is_ok_and(|b| self.language.quotes.contains(&b)) {
+                            self.state = State::Quoted(c);
+                        }
+                        out.push(c);
+                        rest = rest.get(c.len_utf8()..).unwrap_or_default();
+                    }
+                }
+            }
+        }
+        if !stripped {
+            return Some(out);
+        }
+        let kept = out.trim_end();
+        (!kept.trim_start().is_empty()).then(|| kept.to_string())
+    }
+
+    /// Rust literals the quote rules get wrong: `r#"..."#` opens a raw
+    /// string (its length is that of the opener), and a `'x'` char literal
+    /// is taken whole so a quote inside it opens nothing.
+    fn literal(&mut self, before: &str, rest: &str) -> Option<usize> {
+        if !self.language.raw_strings {
+            return None;
+        }
+        let after_word = before
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if let Some(raw) = rest.strip_prefix("br").or_else(|| rest.strip_prefix('r'))
+            && !after_word
+        {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            if raw.get(hashes..)?.starts_with('"') {
+                self.state = State::Raw(hashes);
+                return Some(rest.len() - raw.len() + hashes + 1);
+            }
+        }
+        let body = rest.strip_prefix('\'')?;
+        let end = if body.starts_with('\\') {
+            body.get(2..)?.find('\'')? + 2
+        } else {
+            let len = body.chars().next()?.len_utf8();
+            body.get(len..)?.starts_with('\'').then_some(len)?
+        };
+        Some(end + 2)
+    }
+}
+
+/// A `#` (or other word-like marker) only starts a comment at the start of
+/// a word, so `$#` and `a#b` stay code; `//` and `--` start one anywhere.
+fn starts_comment(before: &str, rest: &str, marker: &str) -> bool {
+    if !rest.starts_with(marker) {
+        return false;
+    }
+    marker != "#" || before.chars().next_back().is_none_or(char::is_whitespace)
+}
@@ -0,0 +1,69 @@
+//! `--notebook`: the cell sources of Jupyter notebooks instead of their
+//! JSON, which is mostly outputs (base64 images included) and metadata.
+
+use crate::options::NotebookMode;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Whether `path` is read as a notebook under `mode`.
+pub(crate) fn is_notebook(path: &Path, mode: NotebookMode) -> bool {
+    mode != NotebookMode::Raw
+        && path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("ipynb"))
+}
+
+/// The cells of the notebook at `path` (see `notebook_source`); `None` when
+/// it is not read as a notebook.
+pub(crate) fn notebook_text(path: &Path, mode: NotebookMode) -> Option<io::Result<String>> {
+    if !is_notebook(path, mode) {
+        return None;
+    }
+    match fs::read(path) {
+        Ok(json) => notebook_source(&String::from_utf8_lossy(&json), mode).map(Ok),
+        Err(e) => Some(Err(e)),
+    }
+}
+
+/// The cells `mode` keeps, in order. `Code` joins them with a blank line;
+/// `All` puts a `# %%` (or `# %% [markdown]`) line before each cell, as in
+/// the percent format editors and jupytext use. `None` when `json` is not
+/// an nbformat 4 notebook, which is then emitted as is.
+fn notebook_source(json: &str, mode: NotebookMode) -> Option<String> {
+    let notebook: Value = serde_json::from_str(json).ok()?;
+    let cells = notebook.get("cells")?.as_array()?;
+
+    let mut parts = Vec::new();
+    for cell in cells {
+        let kind = cell.get("cell_type").and_then(Value::as_str);
+        let marker = match (kind, mode) {
+            (Some("code"), NotebookMode::Code) => None,
+            (Some("code"), NotebookMode::All) => Some("# %%"),
+            (Some("markdown"), NotebookMode::All) => Some("# %% [markdown]"),
+            _ => continue,
+        };
+        let source = cell_source(cell);
+        parts.push(match marker {
+            Some(marker) => format!("{marker}\n{source}"),
+            None => source,
+        });
+    }
+
+    let mut text = parts.join("\n\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    Some(text)
+}
+
+/// `source` is one string or a list of lines that keep their newlines.
+fn cell_source(cell: &Value) -> String {
+    let source = match cell.get("source") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    };
+    source.trim_end_matches('\n').to_string()
+}
@@ -0,0 +1,617 @@
+//! Plain-data configuration for a [`Collector`](crate::Collector).
+
+use crate::frontmatter::FrontMatterFilter;
+use crate::paths::PathRewrite;
+use clap::ValueEnum;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Everything that controls a collection run.
+/// Raw values only; patterns are compiled by `Collector::new`.
+#[derive(Clone, Debug)]
+pub struct CollectOptions {
+    // Filters
+    /// Extensions to allow (or to reject with `extension_inv`), without the dot.
+    pub extensions: Option<Vec<String>>,
+    pub extension_inv: bool,
+    /// Regexes applied to the file name, relative path or content (see `scope`),
+    /// combined by `regex_mode`.
+    pub regex: Vec<String>,
+    pub regex_mode: RegexMode,
+    pub regex_inv: bool,
+    pub scope: Scope,
+    /// Match `regex` and `pattern` case-insensitively.
+    pub ignore_case: bool,
+    /// Treat `regex` and `pattern` as literal text.
+    pub fixed_strings: bool,
+    /// Content pattern; only files with a matching line are emitted.
+    pub pattern: Option<String>,
+    pub show_matches: bool,
+    /// Inclusive size bounds in bytes.
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modification time bounds (exclusive).
+    pub newer_than: Option<SystemTime>,
+    pub older_than: Option<SystemTime>,
+    /// Owning user / group (name or id, `!` negates) and permission bits
+    /// (`644` exact, `-002` all of, `/111` any of). Unix only.
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub perm: Option<String>,
+    /// Filter expressions over path, name, ext, size, mtime, depth and type
+    /// (see `--filter`); every one must match.
+    pub filter: Vec<String>,
+    /// Only files in any of these git sets (empty = no git filter).
+    pub git: Vec<GitSelection>,
+    /// Only paths changed since the merge base of this git ref and HEAD
+    /// (committed or not); they replace the walk.
+    pub since_ref: Option<String>,
+    /// Leave out what `git archive` would: tracked paths with the
+    /// `export-ignore` attribute, and everything below such directories.
+    pub export_ignore: bool,
+    /// Entry kinds to emit (empty = everything but directories).
+    pub types: Vec<EntryType>,
+    /// Only files whose content-detected MIME type matches one of these
+    /// (`image/png`, `image/*`); empty = no type filter.
+    pub mime: Vec<String>,
+    /// Only Markdown files whose front matter passes all of these.
+    pub frontmatter_filters: Vec<FrontMatterFilter>,
+
+    // Walker Config
+    pub base_path: PathBuf,
+    /// Read candidate paths from this file (`-` = stdin) instead of walking.
+    pub files_from: Option<PathBuf>,
+    /// Exactly these paths, as found by an earlier walk (an interactive
+    /// selection); they replace the walk like `files_from`.
+    pub selected: Option<Vec<PathBuf>>,
+    pub depth: Option<usize>,
+    /// Skip entries shallower than this (1 = direct children of the base path).
+    pub min_depth: Option<usize>,
+    pub exclude: Option<Vec<String>>,
+    /// Files of gitignore-style exclude patterns, rooted at the base path.
+    pub exclude_from: Vec<PathBuf>,
+    /// Files the caller writes to (`--output`), left out of the walk when
+    /// they lie inside it.
+    pub skip_files: Vec<PathBuf>,
+    /// Gitignore-style globs on the relative path (`!` negates). Any
+    /// positive glob turns the set into a whitelist.
+    pub globs: Vec<String>,
+    pub no_default_excludes: bool,
+    pub include_hidden: bool,
+    pub follow_symlinks: bool,
+    /// Walk into zip / tar / tar.gz files: their members are filtered and
+    /// emitted under virtual paths (`bundle.zip!/src/main.rs`) instead of
+    /// the archive itself.
+    pub archives: bool,
+    /// Separators of displayed paths (archive member names always use `/`).
+    pub path_style: PathStyle,
+    /// Applied in order to displayed paths (after `path_style`); archive
+    /// member names are left alone.
+    pub path_rewrite: Vec<PathRewrite>,
+    /// How symlink entries are emitted (`Skip` also stops them being followed).
+    pub symlinks: SymlinkMode,
+    /// List the NTFS alternate data streams of each file; `ads_content` also
+    /// emits their content after the file's own (Windows only).
+    pub ads: bool,
+    pub ads_content: bool,
+    /// Emit the extended attributes of each file (`user.*`, `security.*`,
+    /// `com.apple.*`).
+    pub xattrs: bool,
+    /// Emit the format, dimensions and EXIF fields (camera, capture time,
+    /// GPS position) of image files; `BinaryMode::Skip` keeps them.
+    pub image_meta: bool,
+    /// Emit a file reached through several hardlinks once, listing the
+    /// other paths as its aliases (the walk is buffered).
+    pub dedupe_hardlinks: bool,
+    /// Do not cross file system boundaries (mount points) while walking.
+    pub same_file_system: bool,
+    /// Worker threads (0 = all cores). More than one sorts output by path.
+    pub threads: usize,
+    /// How the walk waits on the file system.
+    pub backend: Backend,
+    /// Output order; anything but `None` buffers the full result first.
+    pub sort: SortKey,
+    pub reverse: bool,
+
+    // Output Config
+    pub absolute_path: bool,
+    /// Directory displayed paths are relative to (default: `base_path`);
+    /// paths outside of it get `..` steps.
+    pub relative_to: Option<PathBuf>,
+    pub max_bytes: Option<u64>,
+    pub read_content: bool,
+    /// How binary files are rendered with `read_content` (`Skip` also drops
+    /// them from the results).
+    pub binary: BinaryMode,
+    /// How `binary` files are told apart from text.
+    pub binary_check: BinaryCheck,
+    /// What happens to Git LFS pointer files with `read_content`.
+    pub lfs: LfsMode,
+    /// What `read_content` shows of Jupyter notebooks (`.ipynb`).
+    pub notebook: NotebookMode,
+    /// ANSI syntax highlighting of text content (text format). The CLI only
+    /// enables it when writing to a terminal.
+    pub highlight: bool,
+    /// Color listed paths by type and extension, following `LS_COLORS`
+    /// (text list mode). The CLI resolves `--color` into this flag.
+    pub color: bool,
+    /// Regexes whose matches are masked in emitted text content (line by
+    /// line), with `redact_with` or `███`.
+    pub redact: Vec<String>,
+    pub redact_with: Option<String>,
+    /// Drop comments from text content, per the file's language.
+    pub strip_comments: bool,
+    /// Collapse runs of blank lines in text content into one.
+    pub squeeze_blank: bool,
+    /// Emit the line endings of text content as these (CRLF and LF alike).
+    pub normalize_eol: Option<LineEnding>,
+    /// Drop the UTF-8 byte order mark that starts text content.
+    pub strip_bom: bool,
+    /// Cut lines of text content longer than this many bytes, with a marker
+    /// in place of the rest.
+    pub max_line_bytes: Option<u64>,
+    /// Show a marker instead of the content of lockfiles, source maps,
+    /// minified bundles and files marked as generated.
+    pub skip_generated: bool,
+    /// The same output on every OS for the same tree: sorted by name (unless
+    /// another `sort` is given), `/` separators, LF line endings, and plain
+    /// modes without owners in archives. Expanded by `Collector::new`.
+    pub stable: bool,
+    /// Prefix content lines with their line number.
+    pub line_numbers: bool,
+    /// Keep only the first / last N lines of each file (after `max_bytes`).
+    pub head_lines: Option<usize>,
+    pub tail_lines: Option<usize>,
+    /// Keep only these line ranges of each file (or of the file a range
+    /// names); the gaps become markers.
+    pub lines: Vec<LineRange>,
+    /// Terminate listed paths with NUL instead of a newline (text list mode).
+    pub print0: bool,
+    pub format: OutputFormat,
+    /// Archive formats: store uid / gid without user and group names (tar),
+    /// or plain `0644` / `0755` modes and no owner instead of the real ones.
+    pub numeric_owner: bool,
+    pub no_perms: bool,
+    /// Compresses everything `Collector::write` emits, in any format.
+    pub compress: Option<Compression>,
+    pub metadata: Vec<MetaField>,
+    /// Per-file layout for text output with `{path}`, `{size}`, `{content}`...
+    /// placeholders, replacing the built-in blocks.
+    pub template: Option<String>,
+    /// Replaces the `=== path ===` line of content blocks, with the
+    /// `template` placeholders except `{content}`.
+    pub header_template: Option<String>,
+    /// Written once before / after all text records, with or without
+    /// `template` (`\n` and `\t` escapes).
+    pub template_header: Option<String>,
+    pub template_footer: Option<String>,
+    /// Columns of CSV / TSV output.
+    pub columns: Vec<Column>,
+    /// Content hash emitted next to each path / as a record field.
+    pub hash: Option<HashAlgorithm>,
+    /// Annotate each file with its last commit: hash, author and date.
+    pub git_meta: bool,
+    /// Emit the YAML / TOML front matter of Markdown files.
+    pub frontmatter: bool,
+    /// Annotate each file with an estimated token count.
+    pub count_tokens: bool,
+    /// Token budget for the whole run (implies `count_tokens`).
+    pub max_tokens: Option<u64>,
+    /// Stop after this many entries.
+    pub max_files: Option<usize>,
+    /// Stop starting new records once this many bytes were written.
+    pub max_output_bytes: Option<u64>,
+    /// `Collector::write_split`: start a new part before one would exceed
+    /// this many bytes, or hold more than this many records.
+    pub split_bytes: Option<u64>,
+    pub split_files: Option<usize>,
+    /// Emit the content of each file as chunks of at most this many bytes,
+    /// one record each (implies `read_content`).
+    pub chunk_size: Option<u64>,
+    /// Bytes of whole lines each chunk repeats from the end of the previous
+    /// one.
+    pub chunk_overlap: u64,
+    /// Progress file: rewritten while records are written, resumed from when
+    /// it exists, removed once a run completes. The caller continues the
+    /// output at the recorded offset.
+    pub checkpoint: Option<PathBuf>,
+    /// State file with the size and mtime of every path from the last
+    /// `Collector::write`; only new or changed paths are emitted, and the
+    /// file is updated after each successful write.
+    pub incremental: Option<PathBuf>,
+    pub budget_strategy: BudgetStrategy,
+    /// Also record every per-file error as a JSON line (path, operation,
+    /// kind, message) in this file, whatever `quiet` says.
+    pub errors: Option<PathBuf>,
+    /// Suppress warnings on stderr.
+    pub quiet: bool,
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self {
+            extensions: None,
+            extension_inv: false,
+            regex: Vec::new(),
+            regex_mode: RegexMode::Any,
+            regex_inv: false,
+            scope: Scope::Name,
+            ignore_case: false,
+            fixed_strings: false,
+            pattern: None,
+            show_matches: false,
+            min_size: None,
+            max_size: None,
+            newer_than: None,
+            older_than: None,
+            owner: None,
+            group: None,
+            perm: None,
+            filter: Vec::new(),
+            git: Vec::new(),
+            since_ref: None,
+            export_ignore: false,
+            types: Vec::new(),
+            mime: Vec::new(),
+            frontmatter_filters: Vec::new(),
+            base_path: PathBuf::from("."),
+            files_from: None,
+            selected: None,
+            depth: None,
+            min_depth: None,
+            exclude: None,
+            exclude_from: Vec::new(),
+            skip_files: Vec::new(),
+            globs: Vec::new(),
+            no_default_excludes: false,
+            include_hidden: false,
+            follow_symlinks: false,
+            archives: false,
+            path_style: PathStyle::Native,
+            path_rewrite: Vec::new(),
+            symlinks: SymlinkMode::Report,
+            ads: false,
+            ads_content: false,
+            xattrs: false,
+            image_meta: false,
+            dedupe_hardlinks: false,
+            same_file_system: false,
+            threads: 1,
+            backend: Backend::Std,
+            sort: SortKey::None,
+            reverse: false,
+            absolute_path: false,
+            relative_to: None,
+            max_bytes: None,
+            read_content: false,
+            binary: BinaryMode::Suppress,
+            binary_check: BinaryCheck::Loose,
+            lfs: LfsMode::Flag,
+            notebook: NotebookMode::Raw,
+            highlight: false,
+            color: false,
+            redact: Vec::new(),
+            redact_with: None,
+            strip_comments: false,
+            squeeze_blank: false,
+            normalize_eol: None,
+            strip_bom: false,
+            max_line_bytes: None,
+            skip_generated: false,
+            stable: false,
+            line_numbers: false,
+            head_lines: None,
+            tail_lines: None,
+            lines: Vec::new(),
+            print0: false,
+            format: OutputFormat::Text,
+            numeric_owner: false,
+            no_perms: false,
+            compress: None,
+            metadata: Vec::new(),
+            template: None,
+            header_template: None,
+            template_header: None,
+            template_footer: None,
+            columns: vec![Column::Path, Column::Size, Column::Mtime],
+            hash: None,
+            git_meta: false,
+            frontmatter: false,
+            count_tokens: false,
+            max_tokens: None,
+            max_files: None,
+            max_output_bytes: None,
+            split_bytes: None,
+            split_files: None,
+            chunk_size: None,
+            chunk_overlap: 0,
+            checkpoint: None,
+            incremental: None,
+            budget_strategy: BudgetStrategy::Stop,
+            errors: None,
+            quiet: false,
+        }
+    }
+}
+
+/// A `lines` selection of 1-based, inclusive line numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    /// Relative path (as listed) the range is for; `None` = every file.
+    pub path: Option<String>,
+    pub start: usize,
+    /// Last line; `None` = to the end of the file.
+    pub end: Option<usize>,
+}
+
+impl LineRange {
+    pub fn contains(&self, line: usize) -> bool {
+        line >= self.start && self.end.is_none_or(|end| line <= end)
+    }
+}
+
+/// When the CLI emits ANSI colors (`--color`).
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and `NO_COLOR` is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+/// How several `regex` patterns combine.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum RegexMode {
+    /// At least one pattern matches.
+    Any,
+    /// Every pattern matches.
+    All,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Scope {
+    Name,
+    Path,
+    /// File content (text files only, up to `max_bytes`).
+    Content,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+    /// Comma separated listing (RFC 4180 quoting), one row per file.
+    Csv,
+    /// Tab separated listing.
+    Tsv,
+    /// Directory tree of the matched files, like the `tree` command.
+    Tree,
+    /// Self-contained HTML page: collapsible tree plus highlighted content.
+    Html,
+    /// Tar archive of the matched files (see `compress`).
+    Tar,
+    /// Zip archive (deflate) of the matched files.
+    Zip,
+}
+
+impl OutputFormat {
+    /// Delimited listings (`columns` picks the fields).
+    pub fn is_delimited(self) -> bool {
+        matches!(self, Self::Csv | Self::Tsv)
+    }
+
+    /// Archive formats store files instead of rendering records.
+    pub fn is_archive(self) -> bool {
+        matches!(self, Self::Tar | Self::Zip)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum BinaryMode {
+    /// Keep the file, replace its content with a marker.
+    Suppress,
+    /// Leave binary files out entirely.
+    Skip,
+    /// Hex dump (xxd layout), bounded by `max_bytes` or 4 KiB.
+    Hex,
+    Base64,
+    /// Pass the bytes through unchanged.
+    Raw,
+}
+
+/// Git LFS pointer files: the small text files git keeps in place of large
+/// ones until `git lfs` fetches the content.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum LfsMode {
+    /// Emit the pointer text like any other content.
+    Keep,
+    /// Replace the content with a marker naming the object and its size.
+    Flag,
+    /// Leave pointer files out entirely.
+    Skip,
+    /// Emit the real content, from the local LFS store or fetched with
+    /// `git lfs smudge`; flagged when that fails.
+    Smudge,
+}
+
+/// Jupyter notebooks in content output.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum NotebookMode {
+    /// The notebook JSON as stored, outputs and metadata included.
+    Raw,
+    /// The sources of the code cells only.
+    Code,
+    /// Code and markdown cells, each after a `# %%` marker line.
+    All,
+}
+
+/// Line endings of text content with `normalize_eol`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    Lf,
+    /// `\r\n`, as on Windows.
+    Crlf,
+}
+
+/// How files are classified as binary.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum BinaryCheck {
+    /// Like `Loose`, and any null byte makes a file binary.
+    Strict,
+    /// Known binary signatures, or a high share of control characters.
+    /// UTF-8 and UTF-16 text with a BOM is text (UTF-16 is decoded).
+    Loose,
+    /// Everything is text; bytes pass through unchanged.
+    Off,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum SortKey {
+    /// Walker order (path order when parallel).
+    None,
+    /// Relative path, lexicographic.
+    Name,
+    Size,
+    Mtime,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+impl HashAlgorithm {
+    /// Field name used in headers and records.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+            Self::Md5 => "md5",
+        }
+    }
+}
+
+/// What to do once the token budget would be exceeded.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum BudgetStrategy {
+    /// Stop at the first file that does not fit (streams).
+    Stop,
+    /// Drop the largest files until the rest fits (buffers the file list).
+    DropLargest,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum GitSelection {
+    /// Files in the index (`git ls-files`).
+    Tracked,
+    /// Files that differ from HEAD, staged or not.
+    Modified,
+    /// Files with staged changes.
+    Staged,
+    /// Untracked files that are not ignored.
+    Untracked,
+}
+
+/// Entry kinds for `types`. With `follow_symlinks`, links count as their
+/// target, so only broken links are `Symlink`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum EntryType {
+    #[value(name = "f", alias = "file")]
+    File,
+    #[value(name = "d", alias = "dir")]
+    Dir,
+    #[value(name = "l", alias = "symlink")]
+    Symlink,
+    /// FIFOs, sockets and device files (or links to them). Only emitted
+    /// when asked for, and never read.
+    #[value(name = "s", alias = "special")]
+    Special,
+}
+
+/// Separators of the paths shown in records.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum PathStyle {
+    /// As the platform writes them.
+    Native,
+    /// `/` everywhere (`src/main.rs`, also on Windows).
+    Forward,
+    /// `\` everywhere (`src\main.rs`, also on Unix).
+    Backslash,
+}
+
+/// I/O backend of the walk.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Backend {
+    /// One blocking syscall at a time per walker thread.
+    Std,
+    /// Experimental, Linux only: the standard walk, with the metadata (and
+    /// content heads) of each directory's children read ahead in batches on
+    /// an io_uring. Needs the `io-uring` feature; falls back to `Std` where
+    /// io_uring is unavailable.
+    IoUring,
+}
+
+/// How symlinks appear in the results.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum SymlinkMode {
+    /// As the link's own path; broken links are warned about.
+    Report,
+    /// Left out (and never followed).
+    Skip,
+    /// As the resolved target path: relative when it lies under the base
+    /// path, absolute otherwise.
+    Target,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Column {
+    Path,
+    Size,
+    Mtime,
+    /// Lowercase extension without the dot.
+    Ext,
+    /// Content hash (`hash` algorithm, sha256 by default).
+    Hash,
+    Tokens,
+    /// MIME type detected from the content.
+    Mime,
+    /// Last commit hash, author and commit date (implies `git_meta`).
+    Commit,
+    Author,
+    Committed,
+}
+
+impl Column {
+    /// Columns filled from the last commit of each file.
+    pub fn is_git(self) -> bool {
+        matches!(self, Self::Commit | Self::Author | Self::Committed)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum MetaField {
+    Size,
+    Mtime,
+    Ctime,
+    Perms,
+    /// Owning user and group (names, or ids without one). Unix only.
+    Owner,
+    Group,
+    /// POSIX ACL entries, `-` without any beyond the mode bits. Linux only.
+    Acl,
+    /// SELinux context (or SMACK / AppArmor label), `-` without one.
+    Context,
+    Type,
+    /// MIME type detected from the content.
+    Mime,
+}
@@ -0,0 +1,1223 @@
+//! Record writers: text blocks, JSON / NDJSON objects and content streaming.
+
+use crate::ads::{Stream, stream_path, streams};
+use crate::archive::archive_name;
+use crate::binary::{PROBE_LEN, UTF8_BOM, decode_utf16, looks_binary, utf16_bom, write_binary};
+use crate::chunk::chunks;
+use crate::collector::{Entry, entry_size};
+use crate::color::paint_path;
+use crate::documents::{document_text, extracts};
+use crate::frontmatter::field_text;
+use crate::hash::hash_file;
+use crate::highlight::HighlightWriter;
+use crate::image::{ImageMeta, image_meta};
+use crate::metadata::format_time;
+use crate::metadata::{file_mime, format_metadata, metadata_values};
+use crate::mime::detect_mime;
+use crate::minify::minify;
+use crate::notebook::{is_notebook, notebook_text};
+use crate::options::{
+    BinaryMode, CollectOptions, Column, HashAlgorithm, LineEnding, LineRange, OutputFormat,
+    PathStyle,
+};
+use crate::paths;
+use crate::redact::{RedactWriter, Redactor};
+use crate::search::LineMatch;
+use crate::template::{Template, Templates};
+use crate::xattrs::xattrs;
+use memchr::memchr;
+use serde_json::{Map, Value};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The file behind the record writer, when the output is one (see
+/// `Collector::write_file`). Content written unchanged is copied straight
+/// into it, which std turns into `copy_file_range` / `sendfile` on Linux.
+#[derive(Clone, Copy)]
+pub(crate) struct DirectOutput<'a> {
+    pub(crate) file: &'a File,
+    /// The output byte counter, which never sees the copied bytes otherwise.
+    pub(crate) written: &'a AtomicU64,
+}
+
+/// Handles file reading and writing with buffering.
+/// Returns io::Result to allow easier BrokenPipe handling in main.
+pub(crate) fn process_file(
+    entry: &Entry,
+    options: &CollectOptions,
+    templates: &Templates,
+    redactor: Option<&Redactor>,
+    index: usize,
+    writer: &mut dyn Write,
+    direct: Option<DirectOutput<'_>>,
+) -> io::Result<()> {
+    let path = entry.path();
+
+    // 1. Path Formatting
+    let path_display = display_path(path, options);
+
+    if let Some(template) = &templates.record {
+        return template.render(entry, &path_display, options, redactor, index, writer);
+    }
+    if let Some(size) = options.chunk_size {
+        return write_chunks(entry, &path_display, size, options, redactor, index, writer);
+    }
+
+    match options.format {
+        OutputFormat::Text => {
+            let header = templates.header.as_ref().map(|h| (h, index));
+            write_text_record(
+                entry,
+                &path_display,
+                options,
+                header,
+                redactor,
+                writer,
+                direct,
+            )
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            write_json_record(entry, &path_display, options, redactor, index, writer)
+        }
+        // Rendered as a whole by `write_tree` / `write_html`
+        OutputFormat::Tree | OutputFormat::Html => Ok(()),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            write_delimited_record(entry, &path_display, options, writer)
+        }
+        // Archives are assembled by archive::write_archive, never per record
+        OutputFormat::Tar | OutputFormat::Zip => Err(io::Error::other(
+            "archive formats cannot be rendered per record",
+        )),
+    }
+}
+
+/// A path as records show it: relative to `relative_to` (or the base path),
+/// or absolute, then restyled and rewritten. A path with no relative form
+/// (another Windows drive) is shown absolute.
+pub(crate) fn display_path(path: &Path, options: &CollectOptions) -> PathBuf {
+    let relative_to = options.relative_to.as_deref().unwrap_or(&options.base_path);
+    let relative = (!options.absolute_path)
+        .then(|| paths::relative(path, relative_to))
+        .flatten();
+    let path = match relative {
+        Some(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+        Some(relative) => relative,
+        None => paths::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+    };
+    let path = match options.path_style {
+        PathStyle::Native => path,
+        style => PathBuf::from(paths::styled(&path, style)),
+    };
+    if options.path_rewrite.is_empty() {
+        return path;
+    }
+    let text = options
+        .path_rewrite
+        .iter()
+        .fold(path.to_string_lossy().into_owned(), |text, rewrite| {
+            rewrite.apply(&text)
+        });
+    PathBuf::from(text)
+}
+
+/// Hardlinks folded into the entry, shown like its own path.
+fn alias_paths(entry: &Entry, options: &CollectOptions) -> Vec<String> {
+    entry
+        .aliases()
+        .iter()
+        .map(|alias| display_path(alias, options).to_string_lossy().into_owned())
+        .collect()
+}
+
+/// `{"name", "size"}` of an alternate data stream, plus its text content
+/// (`null` when binary) with `ads_content`.
+fn stream_record(
+    path: &Path,
+    stream: Stream,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> Value {
+    let mut record = Map::new();
+    let content = options.ads_content.then(|| {
+        read_text_content(&stream_path(path, &stream.name), options, redactor)
+            .ok()
+            .flatten()
+    });
+    record.insert("name".into(), Value::from(stream.name));
+    record.insert("size".into(), Value::from(stream.size));
+    if let Some(content) = content {
+        record.insert("content".into(), Value::from(content));
+    }
+    Value::Object(record)
+}
+
+/// Requested per-file annotations (metadata, hash, tokens), e.g. `size=12`.
+pub(crate) fn header_fields(entry: &Entry, options: &CollectOptions) -> Vec<String> {
+    let mut fields = Vec::new();
+    if let Some(m) = entry.metadata()
+        && !options.metadata.is_empty()
+    {
+        fields.push(format_metadata(
+            entry.content_path(),
+            m,
+            &options.metadata,
+            options.binary_check,
+        ));
+    }
+    if let Some(algorithm) = options.hash {
+        let digest = hash_file(entry.content_path(), algorithm).unwrap_or_else(|_| "-".to_string());
+        fields.push(format!("{}={}", algorithm.name(), digest));
+    }
+    if let Some(tokens) = entry.tokens() {
+        fields.push(format!("tokens={tokens}"));
+    }
+    if options.git_meta {
+        match entry.git_commit() {
+            Some(commit) => fields.push(format!(
+                "commit={} author={} committed={}",
+                commit.short_hash(),
+                commit.author,
+                format_time(Some(commit.time))
+            )),
+            None => fields.push("commit=-".to_string()),
+        }
+    }
+    if !entry.aliases().is_empty() {
+        fields.push(format!("aliases={}", alias_paths(entry, options).join(";")));
+    }
+    if options.xattrs {
+        match xattrs(entry.path()) {
+            Ok(attrs) => fields.extend(
+                attrs
+                    .into_iter()
+                    .map(|(name, value)| format!("{name}={value}")),
+            ),
+            Err(_) => fields.push("xattrs=-".to_string()),
+        }
+    }
+    if options.image_meta
+        && let Ok(Some(image)) = image_meta(entry.content_path())
+    {
+        fields.extend(image_fields(&image));
+    }
+    if options.frontmatter
+        && let Some(front_matter) = entry.front_matter()
+    {
+        fields.extend(
+            front_matter
+                .iter()
+                .map(|(key, value)| format!("{key}={}", field_text(value))),
+        );
+    }
+    if options.ads {
+        match streams(entry.path()) {
+            Ok(streams) if streams.is_empty() => {}
+            Ok(streams) => {
+                let list: Vec<String> = streams
+                    .iter()
+                    .map(|s| format!("{}({})", s.name, s.size))
+                    .collect();
+                fields.push(format!("ads={}", list.join(";")));
+            }
+            Err(_) => fields.push("ads=-".to_string()),
+        }
+    }
+    fields
+}
+
+/// Classic "=== path ===" blocks (or bare paths in list mode). `header`
+/// replaces the `===` line, rendered with the record's index.
+fn write_text_record(
+    entry: &Entry,
+    path_display: &Path,
+    options: &CollectOptions,
+    header: Option<(&Template, usize)>,
+    redactor: Option<&Redactor>,
+    writer: &mut dyn Write,
+    direct: Option<DirectOutput<'_>>,
+) -> io::Result<()> {
+    let path = entry.path();
+
+    // 2. Write Header (metadata and hash are appended in brackets when requested)
+    let fields = header_fields(entry, options);
+    let meta_suffix = if fields.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", fields.join(", "))
+    };
+
+    if let Some((header, index)) = header.filter(|_| options.read_content) {
+        header.render(entry, path_display, options, redactor, index, writer)?;
+        writer.write_all(b"\n")?;
+    } else if options.read_content {
+        writeln!(writer, "=== {}{} ===", path_display.display(), meta_suffix)?;
+    } else if options.print0 {
+        // Raw bytes: the path must survive exactly for `xargs -0`
+        writer.write_all(path_display.as_os_str().as_encoded_bytes())?;
+        writer.write_all(meta_suffix.as_bytes())?;
+        writer.write_all(b"\0")?;
+    } else if options.color {
+        let display = path_display.display().to_string();
+        writeln!(writer, "{}{}", paint_path(path, &display), meta_suffix)?;
+    } else {
+        writeln!(writer, "{}{}", path_display.display(), meta_suffix)?;
+    }
+
+    // 3. Pattern matches (only populated with --show-matches)
+    for m in entry.matches().iter().filter(|_| !options.print0) {
+        let text = redactor.map_or_else(|| m.text.clone(), |r| r.text(m.text.clone()));
+        writeln!(writer, "  {}: {}", m.number, text)?;
+    }
+
+    // 4. Content Streaming (The optimization core)
+    if let Some(marker) = entry.content_marker().filter(|_| options.read_content) {
+        writeln!(writer, "\n{marker}\n")?;
+    } else if options.read_content {
+        stream_file_content(entry.content_path(), writer, options, redactor, direct)?;
+    }
+
+    // 5. Alternate data streams, each as a block of its own
+    if options.ads_content {
+        for stream in streams(path).unwrap_or_default() {
+            let display = stream_path(path_display, &stream.name);
+            writeln!(writer, "=== {} ===", display.display())?;
+            stream_file_content(
+                &stream_path(path, &stream.name),
+                writer,
+                options,
+                redactor,
+                None,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// One JSON object per file. For `json` the records are joined into an array
+/// (the brackets are written by main), for `ndjson` each one is a line.
+fn write_json_record(
+    entry: &Entry,
+    path_display: &Path,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+    index: usize,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let path = entry.path();
+
+    let mut record = Map::new();
+    record.insert(
+        "path".into(),
+        Value::from(path_display.to_string_lossy().into_owned()),
+    );
+
+    if let Some(m) = entry.metadata() {
+        record.insert("size".into(), Value::from(m.len()));
+        if !options.metadata.is_empty() {
+            let fields = metadata_values(
+                entry.content_path(),
+                m,
+                &options.metadata,
+                options.binary_check,
+            )
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+            record.insert("metadata".into(), Value::Object(fields));
+        }
+    }
+
+    if let Some(algorithm) = options.hash {
+        let digest = hash_file(entry.content_path(), algorithm).ok();
+        record.insert(algorithm.name().into(), Value::from(digest));
+    }
+
+    if let Some(tokens) = entry.tokens() {
+        record.insert("tokens".into(), Value::from(tokens));
+    }
+
+    if options.git_meta {
+        let value = entry.git_commit().map_or(Value::Null, |commit| {
+            let mut git = Map::new();
+            git.insert("commit".into(), Value::from(commit.hash.clone()));
+            git.insert("author".into(), Value::from(commit.author.clone()));
+            git.insert(
+                "committed".into(),
+                Value::from(format_time(Some(commit.time))),
+            );
+            Value::Object(git)
+        });
+        record.insert("git".into(), value);
+    }
+
+    if !entry.aliases().is_empty() {
+        record.insert("aliases".into(), Value::from(alias_paths(entry, options)));
+    }
+
+    if options.xattrs {
+        let value = match xattrs(path) {
+            Ok(attrs) => Value::Object(
+                attrs
+                    .into_iter()
+                    .map(|(name, value)| (name, Value::from(value)))
+                    .collect(),
+            ),
+            Err(e) => Value::from(e.to_string()),
+        };
+        record.insert("xattrs".into(), value);
+    }
+
+    if options.image_meta {
+        let value = match image_meta(entry.content_path()) {
+            Ok(Some(image)) => image_value(&image),
+            _ => Value::Null,
+        };
+        record.insert("image".into(), value);
+    }
+
+    if options.frontmatter {
+        let value = entry
+            .front_matter()
+            .map_or(Value::Null, |fields| Value::Object(fields.clone()));
+        record.insert("frontmatter".into(), value);
+    }
+
+    if options.ads {
+        let value = match streams(path) {
+            Ok(streams) => Value::Array(
+                streams
+                    .into_iter()
+                    .map(|stream| stream_record(path, stream, options, redactor))
+                    .collect(),
+            ),
+            Err(e) => Value::from(e.to_string()),
+        };
+        record.insert("streams".into(), value);
+    }
+
+    if options.pattern.is_some() && options.show_matches {
+        let matches = match redactor {
+            Some(redactor) => entry
+                .matches()
+                .iter()
+                .map(|m| LineMatch {
+                    number: m.number,
+                    text: redactor.text(m.text.clone()),
+                })
+                .collect(),
+            None => entry.matches().to_vec(),
+        };
+        record.insert("matches".into(), serde_json::to_value(matches)?);
+    }
+
+    if let Some(pointer) = entry.lfs_pointer().filter(|_| options.read_content) {
+        let mut lfs = Map::new();
+        lfs.insert("oid".into(), Value::from(pointer.oid.clone()));
+        lfs.insert("size".into(), Value::from(pointer.size));
+        record.insert("truncated".into(), Value::from(false));
+        record.insert("content".into(), Value::Null);
+        record.insert("lfs".into(), Value::Object(lfs));
+    } else if let Some(generated) = entry.generated().filter(|_| options.read_content) {
+        record.insert("truncated".into(), Value::from(false));
+        record.insert("content".into(), Value::Null);
+        record.insert("generated".into(), Value::from(generated.name()));
+    } else if options.read_content {
+        let source = entry.content_path();
+        let truncated = truncation_note(entry_size(entry), options).is_some();
+        record.insert("truncated".into(), Value::from(truncated));
+        match read_text_content(source, options, redactor) {
+            Ok(Some(text)) => {
+                let text = shape_text(path, text, options)?;
+                record.insert("content".into(), Value::from(text));
+            }
+            Ok(None) => {
+                let (content, encoding) = match options.binary {
+                    BinaryMode::Suppress | BinaryMode::Skip => (Value::Null, None),
+                    mode => {
+                        let mut buffer = Vec::new();
+                        write_binary(
+                            File::open(source)?,
+                            &mut buffer,
+                            mode,
+                            options.max_bytes,
+                            false,
+                        )?;
+                        let text = String::from_utf8_lossy(&buffer).into_owned();
+                        (Value::from(text), Some(mode))
+                    }
+                };
+                record.insert("content".into(), content);
+                record.insert("binary".into(), Value::from(true));
+                match encoding {
+                    Some(BinaryMode::Hex) => record.insert("encoding".into(), "hex".into()),
+                    Some(BinaryMode::Base64) => record.insert("encoding".into(), "base64".into()),
+                    _ => None,
+                };
+            }
+            Err(e) => {
+                record.insert("content".into(), Value::Null);
+                record.insert("error".into(), Value::from(e.to_string()));
+            }
+        }
+    }
+
+    if options.format == OutputFormat::Json && index > 0 {
+        writer.write_all(b",\n")?;
+    }
+    serde_json::to_writer(&mut *writer, &record)?;
+    if options.format == OutputFormat::Ndjson {
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// `--chunk-size`: one record per chunk of the file's text content. A file
+/// without chunks (binary or empty) still gets one record, with a `null`
+/// chunk in JSON and a marker in text.
+fn write_chunks(
+    entry: &Entry,
+    path_display: &Path,
+    size: u64,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+    index: usize,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let text = match entry.content_marker() {
+        Some(marker) => Some(marker),
+        None => read_text_content(entry.content_path(), options, redactor)?
+            .map(|text| shape_text(entry.path(), text, options))
+            .transpose()?,
+    };
+    let size = usize::try_from(size).unwrap_or(usize::MAX);
+    let overlap = usize::try_from(options.chunk_overlap).unwrap_or(usize::MAX);
+    let pieces = text
+        .as_deref()
+        .map(|text| chunks(text, size, overlap))
+        .unwrap_or_default();
+    let path = path_display.to_string_lossy();
+
+    if options.format == OutputFormat::Text {
+        if pieces.is_empty() {
+            let marker = if text.is_none() {
+                "<Binary content suppressed>"
+            } else {
+                "<Empty File>"
+            };
+            return writeln!(writer, "=== {path} [no chunks] ===\n\n{marker}\n");
+        }
+        for chunk in &pieces {
+            writeln!(
+                writer,
+                "=== {path} [chunk {} of {}, lines {}-{}, bytes {}-{}] ===\n",
+                chunk.index + 1,
+                pieces.len(),
+                chunk.lines.0,
+                chunk.lines.1,
+                chunk.bytes.start,
+                chunk.bytes.end
+            )?;
+            writer.write_all(chunk.text.as_bytes())?;
+            writer.write_all(if chunk.text.ends_with('\n') {
+                b"\n"
+            } else {
+                b"\n\n"
+            })?;
+        }
+        return Ok(());
+    }
+
+    let mut records = Vec::new();
+    if pieces.is_empty() {
+        let mut record = Map::new();
+        record.insert("path".into(), Value::from(path.as_ref()));
+        record.insert("chunk".into(), Value::Null);
+        record.insert("chunks".into(), Value::from(0));
+        record.insert("text".into(), Value::Null);
+        if text.is_none() {
+            record.insert("binary".into(), Value::from(true));
+        }
+        records.push(record);
+    }
+    for chunk in &pieces {
+        let mut record = Map::new();
+        record.insert("path".into(), Value::from(path.as_ref()));
+        record.insert("chunk".into(), Value::from(chunk.index));
+        record.insert("chunks".into(), Value::from(pieces.len()));
+        record.insert("start_byte".into(), Value::from(chunk.bytes.start));
+        record.insert("end_byte".into(), Value::from(chunk.bytes.end));
+        record.insert("start_line".into(), Value::from(chunk.lines.0));
+        record.insert("end_line".into(), Value::from(chunk.lines.1));
+        record.insert("text".into(), Value::from(chunk.text));
+        records.push(record);
+    }
+    for (i, record) in records.iter().enumerate() {
+        if options.format == OutputFormat::Json && (index > 0 || i > 0) {
+            writer.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut *writer, record)?;
+        if options.format == OutputFormat::Ndjson {
+            writer.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+/// Header row of CSV / TSV output.
+pub(crate) fn write_delimited_header(
+    options: &CollectOptions,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let names: Vec<&str> = options
+        .columns
+        .iter()
+        .map(|column| match column {
+            Column::Path => "path",
+            Column::Size => "size",
+            Column::Mtime => "mtime",
+            Column::Ext => "ext",
+            Column::Hash => options.hash.unwrap_or(HashAlgorithm::Sha256).name(),
+            Column::Tokens => "tokens",
+            Column::Mime => "mime",
+            Column::Commit => "commit",
+            Column::Author => "author",
+            Column::Committed => "committed",
+        })
+        .collect();
+    write_row(&names, options.format, writer)
+}
+
+fn write_delimited_record(
+    entry: &Entry,
+    path_display: &Path,
+    options: &CollectOptions,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let path = entry.path();
+    let fields: Vec<String> = options
+        .columns
+        .iter()
+        .map(|column| match column {
+            Column::Path => path_display.to_string_lossy().into_owned(),
+            Column::Size => entry
+                .metadata()
+                .map_or_else(String::new, |m| m.len().to_string()),
+            Column::Mtime => format_time(entry.metadata().and_then(|m| m.modified().ok())),
+            Column::Ext => path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            Column::Hash => hash_file(
+                entry.content_path(),
+                options.hash.unwrap_or(HashAlgorithm::Sha256),
+            )
+            .unwrap_or_default(),
+            Column::Tokens => entry.tokens().map(|t| t.to_string()).unwrap_or_default(),
+            Column::Mime => entry
+                .metadata()
+                .map_or_else(
+                    || detect_mime(entry.content_path(), options.binary_check).unwrap_or("-"),
+                    |m| file_mime(entry.content_path(), m, options.binary_check),
+                )
+                .to_string(),
+            Column::Commit => entry
+                .git_commit()
+                .map(|c| c.hash.clone())
+                .unwrap_or_default(),
+            Column::Author => entry
+                .git_commit()
+                .map(|c| c.author.clone())
+                .unwrap_or_default(),
+            Column::Committed => entry
+                .git_commit()
+                .map(|c| format_time(Some(c.time)))
+                .unwrap_or_default(),
+        })
+        .collect();
+    write_row(&fields, options.format, writer)
+}
+
+/// `--image-meta` header fields; what the image does not carry is left out.
+fn image_fields(image: &ImageMeta) -> Vec<String> {
+    let mut fields = vec![format!("format={}", image.format)];
+    if let Some((width, height)) = image.dimensions {
+        fields.push(format!("dimensions={width}x{height}"));
+    }
+    if let Some(camera) = &image.camera {
+        fields.push(format!("camera={camera}"));
+    }
+    if let Some(taken) = &image.taken {
+        fields.push(format!("taken={taken}"));
+    }
+    if let Some((latitude, longitude)) = image.gps {
+        fields.push(format!("gps={latitude:.6},{longitude:.6}"));
+    }
+    fields
+}
+
+/// The JSON `"image"` object, with nulls for what the image does not carry.
+fn image_value(image: &ImageMeta) -> Value {
+    let mut record = Map::new();
+    record.insert("format".into(), Value::from(image.format));
+    record.insert("width".into(), Value::from(image.dimensions.map(|d| d.0)));
+    record.insert("height".into(), Value::from(image.dimensions.map(|d| d.1)));
+    record.insert("camera".into(), Value::from(image.camera.clone()));
+    record.insert("taken".into(), Value::from(image.taken.clone()));
+    let gps = image.gps.map_or(Value::Null, |(latitude, longitude)| {
+        let mut gps = Map::new();
+        gps.insert("latitude".into(), Value::from(latitude));
+        gps.insert("longitude".into(), Value::from(longitude));
+        Value::Object(gps)
+    });
+    record.insert("gps".into(), gps);
+    Value::Object(record)
+}
+
+/// CSV quotes fields containing separators, quotes or line breaks (RFC 4180);
+/// TSV has no quoting, so tabs and line breaks are escaped as `\t`, `\n`, `\r`.
+fn write_row(
+    fields: &[impl AsRef<str>],
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let csv = format == OutputFormat::Csv;
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        let field = field.as_ref();
+        if i > 0 {
+            line.push(if csv { ',' } else { '\t' });
+        }
+        if csv && field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else if csv {
+            line.push_str(field);
+        } else {
+            for c in field.chars() {
+                match c {
+                    '\t' => line.push_str("\\t"),
+                    '\n' => line.push_str("\\n"),
+                    '\r' => line.push_str("\\r"),
+                    '\\' => line.push_str("\\\\"),
+                    c => line.push(c),
+                }
+            }
+        }
+    }
+    // CRLF per RFC 4180 for CSV
+    line.push_str(if csv { "\r\n" } else { "\n" });
+    writer.write_all(line.as_bytes())
+}
+
+/// What `--content` shows instead of the bytes of a document (its text) or
+/// a notebook (its cells), cut to `max_bytes`. `None` for other files.
+pub(crate) fn converted_text(path: &Path, options: &CollectOptions) -> Option<io::Result<String>> {
+    let text =
+        document_text(path, options.binary).or_else(|| notebook_text(path, options.notebook))?;
+    Some(text.map(|mut text| {
+        if let Some(max) = options.max_bytes.and_then(|max| usize::try_from(max).ok()) {
+            text.truncate(text.floor_char_boundary(max));
+        }
+        text
+    }))
+}
+
+/// Reads (up to max_bytes of) a file as text for structured formats.
+/// Returns `None` for binary files; UTF-16 is decoded, invalid UTF-8 is
+/// replaced lossily, comments and blank runs are dropped as asked and
+/// `redactor` masks its matches.
+fn read_text_content(
+    path: &Path,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> io::Result<Option<String>> {
+    let text = match converted_text(path, options) {
+        Some(text) => text?,
+        None => {
+            let file = File::open(path)?;
+            let mut buffer = Vec::new();
+            file.take(options.max_bytes.unwrap_or(u64::MAX))
+                .read_to_end(&mut buffer)?;
+
+            let head = buffer.get(..PROBE_LEN).unwrap_or(&buffer);
+            if looks_binary(head, options.binary_check) {
+                return Ok(None);
+            }
+            match utf16_bom(head, options.binary_check) {
+                Some(order) => decode_utf16(&buffer, order),
+                None => String::from_utf8_lossy(&buffer).into_owned(),
+            }
+        }
+    };
+    let text = match text.strip_prefix('\u{feff}') {
+        Some(rest) if options.strip_bom => rest.to_string(),
+        _ => text,
+    };
+    // Comments and blank runs are found in LF text; CRLF comes back last
+    let text = if options.normalize_eol.is_some() {
+        text.replace("\r\n", "\n")
+    } else {
+        text
+    };
+    let text = if options.strip_comments || options.squeeze_blank {
+        minify(path, &text, options.strip_comments, options.squeeze_blank)
+    } else {
+        text
+    };
+    let text = match redactor {
+        Some(redactor) => redactor.text(text),
+        None => text,
+    };
+    // Cut after redaction, which could miss half a secret
+    let text = match options.max_line_bytes {
+        Some(max) => clamp_lines(text, usize::try_from(max).unwrap_or(usize::MAX)),
+        None => text,
+    };
+    Ok(Some(match options.normalize_eol {
+        Some(LineEnding::Crlf) => text.replace('\n', "\r\n"),
+        _ => text,
+    }))
+}
+
+/// `--max-line-bytes`: lines longer than `max` bytes (line break not
+/// counted) keep their first `max` bytes, cut at a character boundary, and
+/// a `<line truncated: N of M bytes>` marker.
+fn clamp_lines(text: String, max: usize) -> String {
+    if !text.split('\n').any(|line| line.len() > max) {
+        return text;
+    }
+    let mut clamped = String::new();
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        if body.len() <= max {
+            clamped.push_str(line);
+            continue;
+        }
+        let kept = body
+            .get(..body.floor_char_boundary(max))
+            .unwrap_or_default();
+        clamped.push_str(kept);
+        clamped.push_str(&format!(
+            " <line truncated: {} of {} bytes>",
+            kept.len(),
+            body.len()
+        ));
+        clamped.push_str(line.get(body.len()..).unwrap_or_default());
+    }
+    clamped
+}
+
+/// Text content after `max_bytes` and the line options, or `None` for
+/// binary files (which go through `write_binary` instead).
+pub(crate) fn read_shaped_text(
+    path: &Path,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> io::Result<Option<String>> {
+    read_text_content(path, options, redactor)?
+        .map(|text| shape_text(path, text, options))
+        .transpose()
+}
+
+/// File content without the `===` block framing or padding newlines (for
+/// `--template`). Binary files go through the `--binary` mode; a cut file
+/// ends with the truncation note on its own line.
+pub(crate) fn write_bare_content(
+    path: &Path,
+    size: u64,
+    writer: &mut dyn Write,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> io::Result<()> {
+    match read_shaped_text(path, options, redactor)? {
+        Some(text) => writer.write_all(text.as_bytes())?,
+        None => write_binary(
+            File::open(path)?,
+            writer,
+            options.binary,
+            options.max_bytes,
+            false,
+        )?,
+    }
+    match truncation_note(size, options) {
+        Some(note) => writeln!(writer, "\n{note}"),
+        None => Ok(()),
+    }
+}
+
+/// Reads file with binary detection and streams to output.
+/// Uses a 8KB buffer to detect binary files (null bytes) and respects max_bytes immediately.
+fn stream_file_content(
+    path: &Path,
+    writer: &mut dyn Write,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+    direct: Option<DirectOutput<'_>>,
+) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            writeln!(writer, "\n<Error opening file: {}>\n", e)?;
+            return Ok(());
+        }
+    };
+
+    let size = file.metadata().map_or(0, |m| m.len());
+    let mut reader = BufReader::new(file);
+    // 8KB buffer for heuristic binary check
+    let mut buffer = [0u8; PROBE_LEN];
+
+    // Read first chunk
+    let n = reader.read(&mut buffer)?;
+
+    if n == 0 {
+        writeln!(writer, "\n<Empty File>\n")?;
+        return Ok(());
+    }
+
+    let head = buffer.get(..n).unwrap_or_default();
+
+    // UTF-16 is decoded as a whole, through the structured formats' reader,
+    // and so is text that loses comments, blank lines, its BOM or changes
+    // line endings (the line options then apply to what is left), or comes
+    // out of a document
+    let reshaped = options.strip_comments
+        || options.squeeze_blank
+        || options.normalize_eol.is_some()
+        || options.max_line_bytes.is_some()
+        || (options.strip_bom && head.starts_with(UTF8_BOM));
+    if utf16_bom(head, options.binary_check).is_some()
+        || extracts(path, options.binary)
+        || is_notebook(path, options.notebook)
+        || (reshaped && !looks_binary(head, options.binary_check))
+    {
+        let text = read_shaped_text(path, options, redactor)?.unwrap_or_default();
+        writer.write_all(b"\n")?;
+        if options.highlight {
+            let prefix = options.line_numbers.then_some(LINE_NUMBER_SEPARATOR);
+            let mut highlighted = HighlightWriter::new(path, writer, prefix);
+            highlighted.write_all(text.as_bytes())?;
+            highlighted.flush()?;
+        } else {
+            writer.write_all(text.as_bytes())?;
+        }
+        write_truncation(writer, size, options)?;
+        writer.write_all(b"\n\n")?;
+        return Ok(());
+    }
+
+    // Signatures, control characters and (strict) null bytes mean binary
+    if looks_binary(head, options.binary_check) {
+        let head = io::Cursor::new(buffer.get(..n).unwrap_or_default());
+        writer.write_all(b"\n")?;
+        write_binary(
+            head.chain(reader),
+            writer,
+            options.binary,
+            options.max_bytes,
+            true,
+        )?;
+        write_truncation(writer, size, options)?;
+        writer.write_all(b"\n\n")?;
+        return Ok(());
+    }
+
+    // Determine the absolute limit logic
+    let limit = options.max_bytes.unwrap_or(u64::MAX);
+
+    // Line windows need whole lines, so they leave the fast path
+    let ranges = selected_ranges(path, options);
+    if options.head_lines.is_some() || options.tail_lines.is_some() || !ranges.is_empty() {
+        let head = io::Cursor::new(buffer.get(..n).unwrap_or_default());
+        writer.write_all(b"\n")?;
+        let mut highlighted;
+        let target: &mut dyn Write = if options.highlight {
+            let prefix = options.line_numbers.then_some(LINE_NUMBER_SEPARATOR);
+            highlighted = HighlightWriter::new(path, writer, prefix);
+            &mut highlighted
+        } else {
+            writer
+        };
+        let lines = head.chain(reader).take(limit);
+        if ranges.is_empty() {
+            write_line_window(lines, target, options, redactor)?;
+        } else {
+            write_line_ranges(lines, target, options, &ranges, redactor)?;
+        }
+        if options.highlight {
+            target.flush()?;
+        }
+        write_truncation(writer, size, options)?;
+        writer.write_all(b"\n\n")?;
+        return Ok(());
+    }
+
+    // Calculate how many bytes from the INITIAL buffer we are allowed to write.
+    // If limit is 100 but we read 8192, we only write 100.
+    // If limit is 1GB and we read 8192, we write 8192.
+    let bytes_to_write_from_buffer = usize::try_from(std::cmp::min(n as u64, limit))
+        .expect("Unexpected error trying to convert limit to usize.");
+
+    writer.write_all(b"\n")?;
+
+    let mut numbered;
+    let target: &mut dyn Write = if options.line_numbers {
+        numbered = NumberedWriter::new(writer);
+        &mut numbered
+    } else {
+        writer
+    };
+    // Highlighting comes first, so line numbers are added to colored lines
+    let mut highlighted;
+    let highlight: &mut dyn Write = if options.highlight {
+        highlighted = HighlightWriter::new(path, target, None);
+        &mut highlighted
+    } else {
+        target
+    };
+    // Redaction sees the raw lines, before any escape codes
+    let mut redacting;
+    let content: &mut dyn Write = match redactor {
+        Some(redactor) => {
+            redacting = RedactWriter::new(highlight, redactor);
+            &mut redacting
+        }
+        None => highlight,
+    };
+
+    content.write_all(
+        buffer
+            .get(..bytes_to_write_from_buffer)
+            .expect("Failed to read file"),
+    )?;
+
+    // If we haven't reached the limit yet AND there might be more file content
+    if limit > bytes_to_write_from_buffer as u64 {
+        let remaining_allowance = limit - bytes_to_write_from_buffer as u64;
+
+        // Use 'take' to wrap the reader, ensuring we never cross the boundary
+        // during the streaming copy.
+        let mut limited_reader = reader.take(remaining_allowance);
+
+        match direct.filter(|_| !options.line_numbers && !options.highlight && redactor.is_none()) {
+            // Zero-copy: file to file in kernel space (after what is buffered)
+            Some(DirectOutput { mut file, written }) => {
+                content.flush()?;
+                let copied = io::copy(&mut limited_reader, &mut file)?;
+                written.fetch_add(copied, Ordering::Relaxed);
+            }
+            None => {
+                io::copy(&mut limited_reader, content)?;
+            }
+        }
+    }
+    if options.highlight || redactor.is_some() {
+        content.flush()?;
+    }
+
+    write_truncation(writer, size, options)?;
+    writer.write_all(b"\n\n")?;
+
+    Ok(())
+}
+
+/// `<truncated: N of M bytes>` when `max_bytes` cut a file of `size` bytes.
+pub(crate) fn truncation_note(size: u64, options: &CollectOptions) -> Option<String> {
+    options
+        .max_bytes
+        .filter(|&max| size > max)
+        .map(|max| format!("<truncated: {max} of {size} bytes>"))
+}
+
+/// The truncation note on a line of its own, after the cut content.
+fn write_truncation(writer: &mut dyn Write, size: u64, options: &CollectOptions) -> io::Result<()> {
+    match truncation_note(size, options) {
+        Some(note) => write!(writer, "\n{note}"),
+        None => Ok(()),
+    }
+}
+
+/// Prefixes every line written through it with its number (`cat -n` style).
+struct NumberedWriter<'a> {
+    inner: &'a mut dyn Write,
+    line: usize,
+    at_line_start: bool,
+}
+
+impl<'a> NumberedWriter<'a> {
+    fn new(inner: &'a mut dyn Write) -> Self {
+        Self {
+            inner,
+            line: 0,
+            at_line_start: true,
+        }
+    }
+}
+
+impl Write for NumberedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.at_line_start {
+                self.line += 1;
+                write_line_number(self.inner, self.line)?;
+                self.at_line_start = false;
+            }
+            let end = memchr(b'\n', rest).map_or(rest.len(), |i| i + 1);
+            let (line, tail) = rest.split_at(end);
+            self.inner.write_all(line)?;
+            self.at_line_start = line.ends_with(b"\n");
+            rest = tail;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Ends the `--line-numbers` prefix.
+const LINE_NUMBER_SEPARATOR: &[u8] = b" | ";
+
+fn write_line_number(writer: &mut dyn Write, number: usize) -> io::Result<()> {
+    write!(writer, "{number:>6}")?;
+    writer.write_all(LINE_NUMBER_SEPARATOR)
+}
+
+/// Line windows and numbering for structured formats, where content is a string.
+fn shape_text(path: &Path, text: String, options: &CollectOptions) -> io::Result<String> {
+    let mut buffer = Vec::with_capacity(text.len() + text.len() / 4);
+    let ranges = selected_ranges(path, options);
+    if !ranges.is_empty() {
+        write_line_ranges(text.as_bytes(), &mut buffer, options, &ranges, None)?;
+    } else if options.head_lines.is_some() || options.tail_lines.is_some() {
+        write_line_window(text.as_bytes(), &mut buffer, options, None)?;
+    } else if options.line_numbers {
+        NumberedWriter::new(&mut buffer).write_all(text.as_bytes())?;
+    } else {
+        return Ok(text);
+    }
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+/// The `<truncated: N lines>` marker for lines left out.
+fn write_skipped_lines(writer: &mut dyn Write, skipped: usize) -> io::Result<()> {
+    let plural = if skipped == 1 { "" } else { "s" };
+    writeln!(writer, "<truncated: {skipped} line{plural}>")
+}
+
+/// `--head-lines` / `--tail-lines`: keeps the first and/or last lines and
+/// replaces the rest with a `<truncated: N lines>` marker. Only the tail is
+/// buffered; line numbers stay those of the original file.
+fn write_line_window(
+    mut reader: impl BufRead,
+    writer: &mut dyn Write,
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> io::Result<()> {
+    let head = options.head_lines.unwrap_or(0);
+    let tail = options.tail_lines.unwrap_or(0);
+    let emit = |writer: &mut dyn Write, number: usize, line: &[u8]| {
+        emit_line(writer, number, line, options, redactor)
+    };
+
+    let mut kept = VecDeque::with_capacity(tail.min(1024));
+    let mut line = Vec::new();
+    let mut total = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        total += 1;
+
+        if total <= head {
+            emit(writer, total, &line)?;
+        } else if tail > 0 {
+            if kept.len() == tail {
+                kept.pop_front();
+            }
+            kept.push_back((total, line.clone()));
+        }
+    }
+
+    let skipped = total - total.min(head) - kept.len();
+    if skipped > 0 {
+        write_skipped_lines(writer, skipped)?;
+    }
+    for (number, line) in &kept {
+        emit(writer, *number, line)?;
+    }
+    Ok(())
+}
+
+/// `lines` ranges for one file: the global ones and those naming it.
+fn selected_ranges<'o>(path: &Path, options: &'o CollectOptions) -> Vec<&'o LineRange> {
+    if options.lines.is_empty() {
+        return Vec::new();
+    }
+    let name = archive_name(path, options);
+    options
+        .lines
+        .iter()
+        .filter(|range| range.path.as_ref().is_none_or(|p| *p == name))
+        .collect()
+}
+
+/// `--lines`: keeps the lines inside any of `ranges` and replaces each gap
+/// with a `<truncated: N lines>` marker. Line numbers stay the file's own.
+fn write_line_ranges(
+    mut reader: impl BufRead,
+    writer: &mut dyn Write,
+    options: &CollectOptions,
+    ranges: &[&LineRange],
+    redactor: Option<&Redactor>,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut number = 0;
+    let mut skipped = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        number += 1;
+        if ranges.iter().any(|range| range.contains(number)) {
+            if skipped > 0 {
+                write_skipped_lines(writer, skipped)?;
+                skipped = 0;
+            }
+            emit_line(writer, number, &line, options, redactor)?;
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped > 0 {
+        write_skipped_lines(writer, skipped)?;
+    }
+    Ok(())
+}
+
+/// One kept line of a line window or range, numbered and redacted as asked.
+fn emit_line(
+    writer: &mut dyn Write,
+    number: usize,
+    line: &[u8],
+    options: &CollectOptions,
+    redactor: Option<&Redactor>,
+) -> io::Result<()> {
+    if options.line_numbers {
+        write_line_number(writer, number)?;
+    }
+    match redactor {
+        Some(redactor) => writer.write_all(&redactor.apply(line)),
+        None => writer.write_all(line),
+    }
+}
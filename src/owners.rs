@@ -0,0 +1,108 @@
+//! Unix ownership and mode bits: `--owner`, `--group`, `--perm` and the
+//! `owner` / `group` metadata fields.
+
+use anyhow::{Context, Result};
+use std::fs::{self, Metadata};
+use std::sync::OnceLock;
+
+const PASSWD: &str = "/etc/passwd";
+const GROUP: &str = "/etc/group";
+
+/// `(name, id)` pairs of `/etc/passwd`, read once. Accounts only known to
+/// other NSS sources (LDAP, systemd-homed) are missing; ids still work.
+fn users() -> &'static [(String, u32)] {
+    static USERS: OnceLock<Vec<(String, u32)>> = OnceLock::new();
+    USERS.get_or_init(|| read_database(PASSWD))
+}
+
+fn groups() -> &'static [(String, u32)] {
+    static GROUPS: OnceLock<Vec<(String, u32)>> = OnceLock::new();
+    GROUPS.get_or_init(|| read_database(GROUP))
+}
+
+/// Both files are `name:password:id:...` lines.
+fn read_database(path: &str) -> Vec<(String, u32)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((name.to_string(), id))
+        })
+        .collect()
+}
+
+/// A user name or numeric uid.
+pub(crate) fn user_id(spec: &str) -> Result<u32> {
+    lookup_id(spec, users(), "user", PASSWD)
+}
+
+/// A group name or numeric gid.
+pub(crate) fn group_id(spec: &str) -> Result<u32> {
+    lookup_id(spec, groups(), "group", GROUP)
+}
+
+fn lookup_id(spec: &str, table: &[(String, u32)], kind: &str, file: &str) -> Result<u32> {
+    if let Ok(id) = spec.parse() {
+        return Ok(id);
+    }
+    table
+        .iter()
+        .find(|(name, _)| name == spec)
+        .map(|&(_, id)| id)
+        .with_context(|| format!("Unknown {kind} '{spec}' (not in {file}); give its numeric id"))
+}
+
+/// The user name of a uid, or the uid itself when it has none.
+pub(crate) fn user_name(uid: u32) -> String {
+    known_user(uid).map_or_else(|| uid.to_string(), str::to_string)
+}
+
+pub(crate) fn group_name(gid: u32) -> String {
+    known_group(gid).map_or_else(|| gid.to_string(), str::to_string)
+}
+
+/// The name of a uid, if it has one.
+pub(crate) fn known_user(uid: u32) -> Option<&'static str> {
+    lookup_name(uid, users())
+}
+
+pub(crate) fn known_group(gid: u32) -> Option<&'static str> {
+    lookup_name(gid, groups())
+}
+
+fn lookup_name(id: u32, table: &'static [(String, u32)]) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|&&(_, known)| known == id)
+        .map(|(name, _)| name.as_str())
+}
+
+/// `(uid, gid)` of a file; `None` where files have no Unix owner.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps, reason = "`None` on other platforms")]
+pub(crate) fn ownership(meta: &Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.uid(), meta.gid()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ownership(_meta: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// Permission bits, with setuid, setgid and sticky (`0o7777` at most).
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps, reason = "`None` on other platforms")]
+pub(crate) fn mode(meta: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn mode(_meta: &Metadata) -> Option<u32> {
+    None
+}
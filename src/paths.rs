@@ -0,0 +1,191 @@
+//! Path handling across platforms: Windows verbatim prefixes, relative
+//! display paths, their separator style and `--path-rewrite`.
+
+use crate::options::PathStyle;
+use anyhow::{Context, Result, anyhow, bail};
+use regex::Regex;
+use std::fs;
+use std::io;
+use std::path::{self as std_path, Component, MAIN_SEPARATOR, Path, PathBuf};
+
+/// `fs::canonicalize` without the verbatim prefix Windows adds to its result
+/// (`\\?\C:\x` becomes `C:\x`, `\\?\UNC\server\share` becomes
+/// `\\server\share`), so canonical paths compare and print like the ones
+/// users type. The standard library re-adds the prefix by itself when a path
+/// is too long to open without it.
+pub(crate) fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    fs::canonicalize(path).map(strip_verbatim)
+}
+
+/// Drops a `\\?\` prefix when the path means the same without it. Other
+/// verbatim forms (`\\?\GLOBALROOT\...`, volume GUIDs) are kept.
+pub(crate) fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{unc}"));
+    }
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if is_drive_path(rest) => PathBuf::from(rest),
+        _ => path,
+    }
+}
+
+/// `C:\...` (or a bare `C:`).
+fn is_drive_path(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.next() == Some(':')
+        && chars.next().is_none_or(|c| c == '\\')
+}
+
+/// `path` relative to the directory `base`, with `..` steps when it lies
+/// outside of it. Paths that do not simply start with `base` are compared
+/// through their canonical parent directories (symlinks themselves are not
+/// resolved). `None` when no relative path exists, as across Windows drives.
+pub(crate) fn relative(path: &Path, base: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return Some(relative.to_path_buf());
+    }
+    let path = resolved(path)?;
+    let base = canonicalize(base).ok()?;
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    // Different drives or roots share nothing to climb up to
+    match (path_parts.peek(), base_parts.peek()) {
+        (Some(Component::Prefix(a)), Some(Component::Prefix(b))) if a != b => return None,
+        _ => {}
+    }
+    while path_parts.peek().is_some() && path_parts.peek() == base_parts.peek() {
+        path_parts.next();
+        base_parts.next();
+    }
+    let mut relative: PathBuf = base_parts.map(|_| Component::ParentDir).collect();
+    relative.extend(path_parts);
+    Some(relative)
+}
+
+/// An absolute form of `path` for `relative`: its parent canonicalized,
+/// the last component kept as it is.
+fn resolved(path: &Path) -> Option<PathBuf> {
+    let absolute = std_path::absolute(path).ok()?;
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => Some(
+            canonicalize(parent)
+                .unwrap_or_else(|_| parent.to_path_buf())
+                .join(name),
+        ),
+        _ => canonicalize(&absolute).ok(),
+    }
+}
+
+/// A displayed path with the separators `style` asks for.
+pub(crate) fn styled(path: &Path, style: PathStyle) -> String {
+    let text = path.to_string_lossy();
+    match style {
+        PathStyle::Native => text.into_owned(),
+        PathStyle::Forward => text.replace(MAIN_SEPARATOR, "/"),
+        PathStyle::Backslash => text.replace('/', "\\"),
+    }
+}
+
+/// A parsed `--path-rewrite` expression, `s/REGEX/REPLACEMENT/[g]`, applied
+/// to displayed paths (after `path_style`). Any character may stand in for
+/// `/` as the delimiter; a backslash escapes it inside either part.
+#[derive(Clone, Debug)]
+pub struct PathRewrite {
+    regex: Regex,
+    /// In `regex` syntax (`$1`, `${name}`); sed's `\1` and `&` are converted.
+    replacement: String,
+    /// `g`: every match instead of the first.
+    global: bool,
+}
+
+impl PathRewrite {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid --path-rewrite '{spec}' (expected s/REGEX/REPLACEMENT/)");
+        let mut chars = spec.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid());
+        }
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && *c != '\\')
+            .ok_or_else(invalid)?;
+
+        // Pattern, replacement and flags; an escaped delimiter is kept as
+        // the plain character, other escapes are passed on
+        let mut parts = [String::new(), String::new(), String::new()];
+        let mut current = 0;
+        while let Some(c) = chars.next() {
+            if c == delimiter && current < 2 {
+                current += 1;
+                continue;
+            }
+            let Some(part) = parts.get_mut(current) else {
+                return Err(invalid());
+            };
+            match c {
+                '\\' => match chars.next() {
+                    Some(next) if next == delimiter => part.push(next),
+                    Some(next) => {
+                        part.push('\\');
+                        part.push(next);
+                    }
+                    None => part.push('\\'),
+                },
+                c => part.push(c),
+            }
+        }
+        if current < 2 {
+            return Err(invalid());
+        }
+        let [pattern, replacement, flags] = parts;
+        let global = match flags.as_str() {
+            "" => false,
+            "g" => true,
+            other => {
+                bail!("Unknown --path-rewrite flag '{other}' in '{spec}' (only g is supported)")
+            }
+        };
+        let regex = Regex::new(&pattern)
+            .with_context(|| format!("Invalid --path-rewrite pattern '{pattern}'"))?;
+        Ok(Self {
+            regex,
+            replacement: sed_replacement(&replacement),
+            global,
+        })
+    }
+
+    /// The rewritten path; unchanged when the pattern does not match.
+    pub(crate) fn apply(&self, path: &str) -> String {
+        let limit = if self.global { 0 } else { 1 };
+        self.regex
+            .replacen(path, limit, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+/// sed replacement syntax to `regex` syntax: `\1`..`\9` and `&` become
+/// groups, any other escaped character (`\&`, `\\`, `\$`) is literal.
+/// `$1` / `${name}` keep working as in `regex`.
+fn sed_replacement(replacement: &str) -> String {
+    let mut out = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => {
+                    out.push_str("${");
+                    out.push(d);
+                    out.push('}');
+                }
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            '&' => out.push_str("${0}"),
+            c => out.push(c),
+        }
+    }
+    out
+}
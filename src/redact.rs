@@ -0,0 +1,108 @@
+//! `--redact`: masking pattern matches in emitted content.
+
+use crate::options::CollectOptions;
+use anyhow::{Context, Result};
+use memchr::memchr;
+use regex::bytes::{NoExpand, Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Replacement when `redact_with` is unset.
+pub(crate) const DEFAULT_REPLACEMENT: &str = "███";
+
+/// The `redact` patterns as one alternation, compiled once per collector.
+#[derive(Debug)]
+pub(crate) struct Redactor {
+    regex: Regex,
+    replacement: Vec<u8>,
+}
+
+impl Redactor {
+    /// `None` without patterns. `ignore_case` applies like it does to `regex`.
+    pub(crate) fn new(options: &CollectOptions) -> Result<Option<Self>> {
+        if options.redact.is_empty() {
+            return Ok(None);
+        }
+        for pattern in &options.redact {
+            Regex::new(pattern).with_context(|| format!("Invalid --redact pattern '{pattern}'"))?;
+        }
+        let joined = options
+            .redact
+            .iter()
+            .map(|p| format!("(?:{p})"))
+            .collect::<Vec<_>>()
+            .join("|");
+        let regex = RegexBuilder::new(&joined)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .context("Invalid --redact patterns")?;
+        let replacement = options
+            .redact_with
+            .as_deref()
+            .unwrap_or(DEFAULT_REPLACEMENT)
+            .as_bytes()
+            .to_vec();
+        Ok(Some(Self { regex, replacement }))
+    }
+
+    /// Masks every match in `line`; borrowed when nothing matched.
+    pub(crate) fn apply<'t>(&self, line: &'t [u8]) -> Cow<'t, [u8]> {
+        self.regex.replace_all(line, NoExpand(&self.replacement))
+    }
+
+    /// `apply` over a whole text, line by line (matches never span lines,
+    /// as in the streamed output).
+    pub(crate) fn text(&self, text: String) -> String {
+        let mut out = Vec::with_capacity(text.len());
+        for line in text.as_bytes().split_inclusive(|&b| b == b'\n') {
+            out.extend_from_slice(&self.apply(line));
+        }
+        String::from_utf8(out)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+    }
+}
+
+/// Redacts whole lines on their way to `inner`; a trailing partial line is
+/// held back until `flush`.
+pub(crate) struct RedactWriter<'a> {
+    inner: &'a mut dyn Write,
+    redactor: &'a Redactor,
+    pending: Vec<u8>,
+}
+
+impl<'a> RedactWriter<'a> {
+    pub(crate) fn new(inner: &'a mut dyn Write, redactor: &'a Redactor) -> Self {
+        Self {
+            inner,
+            redactor,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Write for RedactWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(end) = memchr(b'\n', rest) {
+            let (line, tail) = rest.split_at(end + 1);
+            if self.pending.is_empty() {
+                self.inner.write_all(&self.redactor.apply(line))?;
+            } else {
+                self.pending.extend_from_slice(line);
+                let line = std::mem::take(&mut self.pending);
+                self.inner.write_all(&self.redactor.apply(&line))?;
+            }
+            rest = tail;
+        }
+        self.pending.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.inner.write_all(&self.redactor.apply(&line))?;
+        }
+        self.inner.flush()
+    }
+}
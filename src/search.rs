@@ -0,0 +1,186 @@
+//! Content search for `--pattern` and `--scope content`.
+
+use crate::binary::{PROBE_LEN, decode_utf16, looks_binary, utf16_bom};
+use crate::options::{BinaryCheck, RegexMode};
+use memchr::{memchr, memchr_iter};
+use regex::bytes::Regex as BytesRegex;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Read size for files of this size and up. Lines are matched in place in
+/// the read buffer, so large files cost one copy and few syscalls. Files are
+/// not memory-mapped: a map faults (SIGBUS) when the file is truncated while
+/// being searched.
+const LARGE_FILE: u64 = 1024 * 1024;
+
+/// A single line matched by the content pattern.
+#[derive(Clone, Debug, Serialize)]
+pub struct LineMatch {
+    /// 1-based line number.
+    #[serde(rename = "line")]
+    pub number: usize,
+    /// Line text without its terminator (lossy UTF-8).
+    pub text: String,
+}
+
+/// Scans a file line by line for the content pattern.
+/// Returns `None` when the file does not match (or is binary), otherwise the
+/// matching lines (only collected when `collect_lines` is set).
+pub(crate) fn search_file_content(
+    path: &Path,
+    pattern: &BytesRegex,
+    collect_lines: bool,
+    check: BinaryCheck,
+) -> io::Result<Option<Vec<LineMatch>>> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut matches = Vec::new();
+    let mut found = false;
+    let mut number = 0;
+    let text = scan_file(file, size, check, |line| {
+        number += 1;
+        if !pattern.is_match(line) {
+            return true;
+        }
+        found = true;
+        if collect_lines {
+            let text = String::from_utf8_lossy(line);
+            matches.push(LineMatch {
+                number,
+                text: text.trim_end_matches(['\r', '\n']).to_string(),
+            });
+        }
+        collect_lines
+    })?;
+
+    Ok((text && found).then_some(matches))
+}
+
+/// Checks whether the first `max_bytes` match the `--regex` patterns: any of
+/// them on some line, or (`RegexMode::All`) each of them on some line.
+/// Returns `None` for binary files, which never take part in content scope.
+pub(crate) fn content_matches(
+    path: &Path,
+    regexes: &[BytesRegex],
+    mode: RegexMode,
+    max_bytes: Option<u64>,
+    check: BinaryCheck,
+) -> io::Result<Option<bool>> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len().min(max_bytes.unwrap_or(u64::MAX));
+
+    let mut seen = vec![false; regexes.len()];
+    let mut done = false;
+    let text = scan_file(file, size, check, |line| {
+        for (regex, seen) in regexes.iter().zip(&mut seen) {
+            *seen = *seen || regex.is_match(line);
+        }
+        done = match mode {
+            RegexMode::Any => seen.iter().any(|&s| s),
+            RegexMode::All => seen.iter().all(|&s| s),
+        };
+        !done
+    })?;
+
+    Ok(text.then_some(done))
+}
+
+/// `scan_lines` over the first `size` bytes of `file` (its length, or less
+/// to stop early).
+pub(crate) fn scan_file(
+    file: File,
+    size: u64,
+    check: BinaryCheck,
+    visit: impl FnMut(&[u8]) -> bool,
+) -> io::Result<bool> {
+    scan_lines(file.take(size), size, check, visit)
+}
+
+/// Calls `visit` with each line of `data` until it returns `false`.
+fn visit_lines(data: &[u8], visit: &mut impl FnMut(&[u8]) -> bool) {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let len = memchr(b'\n', rest).map_or(rest.len(), |i| i + 1);
+        let (line, tail) = rest.split_at(len);
+        if !visit(line) {
+            return;
+        }
+        rest = tail;
+    }
+}
+
+/// Calls `visit` with every line (terminator included) until it returns
+/// `false`. Returns `false` without visiting anything when the file is binary
+/// (same heuristic as the streamer, on the first 8 KiB). UTF-16 text is
+/// decoded first, so patterns match it like any other text.
+///
+/// Lines are sliced out of the read buffer rather than copied, and files of
+/// `LARGE_FILE` and up are read a megabyte at a time. The buffer only grows
+/// past that for a longer line.
+fn scan_lines(
+    mut reader: impl Read,
+    size: u64,
+    check: BinaryCheck,
+    mut visit: impl FnMut(&[u8]) -> bool,
+) -> io::Result<bool> {
+    let capacity = if size >= LARGE_FILE {
+        LARGE_FILE
+    } else {
+        size.max(PROBE_LEN as u64)
+    };
+    let mut buffer = vec![0; usize::try_from(capacity).unwrap_or(PROBE_LEN)];
+    // Bytes at the start of `buffer` carried over from the previous read
+    let mut kept = 0;
+    let mut probed = false;
+
+    loop {
+        if kept == buffer.len() {
+            buffer.resize(buffer.len() * 2, 0);
+        }
+        let n = match reader.read(buffer.get_mut(kept..).unwrap_or_default()) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let end = kept + n;
+        let data = buffer.get(..end).unwrap_or_default();
+
+        if !probed && (end >= PROBE_LEN || n == 0) {
+            probed = true;
+            let head = data.get(..PROBE_LEN).unwrap_or(data);
+            if looks_binary(head, check) {
+                return Ok(false);
+            }
+            if let Some(order) = utf16_bom(head, check) {
+                let mut bytes = data.to_vec();
+                reader.read_to_end(&mut bytes)?;
+                visit_lines(decode_utf16(&bytes, order).as_bytes(), &mut visit);
+                return Ok(true);
+            }
+        }
+        if !probed {
+            // Keep reading until the whole probe is in
+            kept = end;
+            continue;
+        }
+
+        let mut start = 0;
+        for newline in memchr_iter(b'\n', data) {
+            if !visit(data.get(start..=newline).unwrap_or_default()) {
+                return Ok(true);
+            }
+            start = newline + 1;
+        }
+        if n == 0 {
+            if start < end {
+                visit(data.get(start..).unwrap_or_default());
+            }
+            return Ok(true);
+        }
+        buffer.copy_within(start..end, 0);
+        kept = end - start;
+    }
+}
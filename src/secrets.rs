@@ -0,0 +1,166 @@
+//! Built-in credential rules for `--scan-secrets`.
+
+use crate::options::BinaryCheck;
+use crate::search::scan_file;
+use regex::Regex;
+use serde::Serialize;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A likely secret in a matched file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Finding {
+    /// Relative path, as in the listing.
+    pub path: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// Rule that fired (`aws-access-key-id`, `private-key`, ...).
+    pub rule: &'static str,
+    /// The matched value, masked past its first characters.
+    pub secret: String,
+}
+
+/// Characters of a secret that stay readable in a finding.
+const VISIBLE: usize = 4;
+
+/// Minimum Shannon entropy (bits per character) for the generic rules.
+const ASSIGNMENT_ENTROPY: f64 = 3.5;
+const STRING_ENTROPY: f64 = 4.5;
+
+/// (name, pattern, minimum entropy of the captured value). Capture group 1,
+/// when present, is the secret; otherwise the whole match.
+const RULES: &[(&str, &str, Option<f64>)] = &[
+    (
+        "aws-access-key-id",
+        r"\b((?:AKIA|ASIA|ABIA|ACCA)[0-9A-Z]{16})\b",
+        None,
+    ),
+    (
+        "aws-secret-access-key",
+        r#"(?i)aws_?secret_?(?:access_?)?key\W{0,5}([A-Za-z0-9/+=]{40})\b"#,
+        None,
+    ),
+    (
+        "private-key",
+        r"-----BEGIN (?:RSA |DSA |EC |OPENSSH |PGP |ENCRYPTED )?PRIVATE KEY(?: BLOCK)?-----",
+        None,
+    ),
+    (
+        "github-token",
+        r"\b((?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36})\b",
+        None,
+    ),
+    ("github-token", r"\b(github_pat_[A-Za-z0-9_]{82})\b", None),
+    ("gitlab-token", r"\b(glpat-[A-Za-z0-9_\-]{20})\b", None),
+    ("slack-token", r"\b(xox[baprs]-[A-Za-z0-9\-]{10,})\b", None),
+    (
+        "slack-webhook",
+        r"(https://hooks\.slack\.com/services/T[A-Za-z0-9_]+/B[A-Za-z0-9_]+/[A-Za-z0-9_]+)",
+        None,
+    ),
+    ("google-api-key", r"\b(AIza[0-9A-Za-z_\-]{35})\b", None),
+    ("stripe-key", r"\b((?:sk|rk)_live_[0-9A-Za-z]{24,})\b", None),
+    ("npm-token", r"\b(npm_[A-Za-z0-9]{36})\b", None),
+    (
+        "jwt",
+        r"\b(eyJ[A-Za-z0-9_\-]{10,}\.eyJ[A-Za-z0-9_\-]{10,}\.[A-Za-z0-9_\-]{10,})\b",
+        None,
+    ),
+    (
+        "generic-secret",
+        r#"(?i)(?:secret|token|passw(?:or)?d|api_?key|access_?key|auth_?key|credential)[A-Za-z0-9_\-]*["']?\s*[:=]\s*["']([^"'\s]{16,})["']"#,
+        Some(ASSIGNMENT_ENTROPY),
+    ),
+    (
+        "high-entropy-string",
+        r#"["']([A-Za-z0-9+/=_\-]{32,})["']"#,
+        Some(STRING_ENTROPY),
+    ),
+];
+
+/// The compiled ruleset; build once per run.
+#[derive(Debug)]
+pub(crate) struct SecretRules {
+    rules: Vec<(&'static str, Regex, Option<f64>)>,
+}
+
+impl SecretRules {
+    pub(crate) fn new() -> Result<Self, regex::Error> {
+        let rules = RULES
+            .iter()
+            .map(|&(name, pattern, entropy)| Ok((name, Regex::new(pattern)?, entropy)))
+            .collect::<Result<_, regex::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Every finding in a text file (none for binary files). A line reports
+    /// each value once, under the first rule that matches it.
+    pub(crate) fn scan(
+        &self,
+        path: &Path,
+        name: &str,
+        size: u64,
+        check: BinaryCheck,
+    ) -> io::Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        let mut number = 0;
+        scan_file(File::open(path)?, size, check, |line| {
+            number += 1;
+            let text = String::from_utf8_lossy(line);
+            let mut seen: Vec<&str> = Vec::new();
+            for (rule, regex, entropy) in &self.rules {
+                for captures in regex.captures_iter(&text) {
+                    let Some(value) = captures.get(1).or_else(|| captures.get(0)) else {
+                        continue;
+                    };
+                    let value = value.as_str();
+                    if entropy.is_some_and(|min| shannon_entropy(value) < min)
+                        || seen.iter().any(|s| s.contains(value) || value.contains(s))
+                    {
+                        continue;
+                    }
+                    seen.push(value);
+                    findings.push(Finding {
+                        path: name.to_string(),
+                        line: number,
+                        rule,
+                        secret: mask(value, rule),
+                    });
+                }
+            }
+            true
+        })?;
+        Ok(findings)
+    }
+}
+
+/// Private key headers are shown as is; everything else keeps only its
+/// first characters.
+fn mask(value: &str, rule: &str) -> String {
+    if rule == "private-key" {
+        return value.to_string();
+    }
+    let visible: String = value.chars().take(VISIBLE).collect();
+    let hidden = value.chars().count().saturating_sub(VISIBLE).min(16);
+    format!("{visible}{}", "*".repeat(hidden))
+}
+
+/// Bits per character of the value's character distribution.
+fn shannon_entropy(value: &str) -> f64 {
+    let mut counts = [0_u32; 256];
+    for byte in value.bytes() {
+        if let Some(count) = counts.get_mut(usize::from(byte)) {
+            *count += 1;
+        }
+    }
+    let len = f64::from(u32::try_from(value.len()).unwrap_or(u32::MAX));
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = f64::from(count) / len;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,589 @@
+//! `--serve` / `--http`: the matched paths kept in memory and queried over
+//! a Unix socket (JSON lines) or HTTP. `--mcp` (in `mcp`) shares the index.
+
+use crate::collector::Collector;
+use crate::options::{CollectOptions, OutputFormat};
+use crate::units::{parse_size, parse_time};
+use crate::watch::on_changes;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock, mpsc};
+use std::time::{Duration, Instant, SystemTime};
+
+/// `--serve` / `--http`: the matched paths of the server's own command line,
+/// kept in memory so a query filters them instead of walking the tree.
+#[derive(Debug)]
+pub(crate) struct Server {
+    /// The server's options; each query replaces the fields it gives.
+    pub(crate) base: CollectOptions,
+    /// Walks the tree for the index and tells which events touch it.
+    pub(crate) indexer: Collector,
+    pub(crate) index: RwLock<Vec<PathBuf>>,
+    /// One walk at a time, whether requested or triggered by --watch.
+    refreshing: Mutex<()>,
+}
+
+/// One query: a JSON line on the socket, or the parameters of an HTTP
+/// request. Fields are named after the CLI flags and all optional; a query
+/// without any returns the whole index.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Request {
+    /// query (default), refresh or ping. Socket only.
+    pub(crate) op: Option<String>,
+    extension: Option<Vec<String>>,
+    no_extension: Option<Vec<String>>,
+    regex: Vec<String>,
+    scope: Option<String>,
+    ignore_case: bool,
+    fixed_strings: bool,
+    glob: Vec<String>,
+    filter: Vec<String>,
+    pattern: Option<String>,
+    /// Sizes and times as on the command line (`10K`, `2d`); sizes may
+    /// also be plain numbers.
+    min_size: Option<Value>,
+    max_size: Option<Value>,
+    newer_than: Option<String>,
+    older_than: Option<String>,
+    pub(crate) content: bool,
+    max_bytes: Option<u64>,
+    max_files: Option<usize>,
+    max_tokens: Option<u64>,
+    /// Socket: render into `output` instead of the `files` records.
+    /// HTTP: json or ndjson.
+    pub(crate) format: Option<String>,
+}
+
+impl Server {
+    pub(crate) fn new(mut base: CollectOptions) -> Result<Self> {
+        // Replies are data, never drawn on a terminal
+        base.color = false;
+        base.highlight = false;
+        base.compress = None;
+
+        let mut walk = base.clone();
+        walk.read_content = false;
+        walk.max_files = None;
+        walk.max_tokens = None;
+        walk.max_output_bytes = None;
+        Ok(Self {
+            base,
+            indexer: Collector::new(walk)?,
+            index: RwLock::new(Vec::new()),
+            refreshing: Mutex::new(()),
+        })
+    }
+
+    /// Walks the tree again and swaps in the new index; queries keep
+    /// answering from the old one meanwhile.
+    pub(crate) fn refresh(&self) -> Result<usize> {
+        let _walking = self
+            .refreshing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let started = Instant::now();
+        let paths: Vec<PathBuf> = self
+            .indexer
+            .entries()?
+            .map(|entry| entry.path().to_path_buf())
+            .collect();
+        let indexed = paths.len();
+        *self.index.write().unwrap_or_else(PoisonError::into_inner) = paths;
+        log::info!("Indexed {indexed} paths in {:.2?}", started.elapsed());
+        Ok(indexed)
+    }
+
+    fn indexed(&self) -> usize {
+        self.index
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// The server's options with the request's filters in place of theirs,
+    /// over the indexed paths. `format` is left to the caller.
+    pub(crate) fn options(&self, request: Request) -> Result<CollectOptions> {
+        let mut options = self.base.clone();
+        if let Some(extensions) = request.extension {
+            options.extensions = Some(extensions);
+            options.extension_inv = false;
+        } else if let Some(extensions) = request.no_extension {
+            options.extensions = Some(extensions);
+            options.extension_inv = true;
+        }
+        if !request.regex.is_empty() {
+            options.regex = request.regex;
+        }
+        if let Some(scope) = &request.scope {
+            options.scope = parse_value(scope, "scope")?;
+        }
+        options.ignore_case |= request.ignore_case;
+        options.fixed_strings |= request.fixed_strings;
+        if !request.glob.is_empty() {
+            options.globs = request.glob;
+        }
+        if !request.filter.is_empty() {
+            options.filter = request.filter;
+        }
+        if request.pattern.is_some() {
+            options.pattern = request.pattern;
+        }
+        if let Some(size) = &request.min_size {
+            options.min_size = Some(request_size(size, "min_size")?);
+        }
+        if let Some(size) = &request.max_size {
+            options.max_size = Some(request_size(size, "max_size")?);
+        }
+        if let Some(time) = &request.newer_than {
+            options.newer_than = Some(request_time(time, "newer_than")?);
+        }
+        if let Some(time) = &request.older_than {
+            options.older_than = Some(request_time(time, "older_than")?);
+        }
+        options.read_content |= request.content;
+        if request.max_bytes.is_some() {
+            options.max_bytes = request.max_bytes;
+        }
+        if request.max_files.is_some() {
+            options.max_files = request.max_files;
+        }
+        if request.max_tokens.is_some() {
+            options.max_tokens = request.max_tokens;
+        }
+        options.selected = Some(
+            self.index
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        );
+        Ok(options)
+    }
+
+    /// Answers the requests of one socket client, a line each way, until it
+    /// hangs up.
+    fn connection(&self, mut reader: impl BufRead, mut writer: impl Write) {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader
+                .by_ref()
+                .take(REQUEST_LIMIT)
+                .read_until(b'\n', &mut line)
+            {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            // The rest of an oversized line cannot be told from the next
+            // request, so the connection ends with it
+            let oversized = !line.ends_with(b"\n") && line.len() as u64 >= REQUEST_LIMIT;
+            let text = String::from_utf8_lossy(&line);
+            if !oversized && text.trim().is_empty() {
+                continue;
+            }
+            let reply = if oversized {
+                json!({ "ok": false, "error": format!("Request longer than {REQUEST_LIMIT} bytes") })
+            } else {
+                self.answer(&text).unwrap_or_else(|e| {
+                    log::debug!("Request failed: {e:#}");
+                    json!({ "ok": false, "error": format!("{e:#}") })
+                })
+            };
+            if writeln!(writer, "{reply}")
+                .and_then(|()| writer.flush())
+                .is_err()
+                || oversized
+            {
+                break;
+            }
+        }
+    }
+
+    fn answer(&self, line: &str) -> Result<Value> {
+        let request: Request = serde_json::from_str(line).context("Invalid request")?;
+        match request.op.as_deref().unwrap_or("query") {
+            "query" => self.query(request),
+            "refresh" => Ok(json!({ "ok": true, "indexed": self.refresh()? })),
+            "ping" => Ok(json!({ "ok": true, "indexed": self.indexed() })),
+            op => anyhow::bail!("Unknown op '{op}' (expected query, refresh or ping)"),
+        }
+    }
+
+    fn query(&self, request: Request) -> Result<Value> {
+        let format = request
+            .format
+            .as_deref()
+            .map(|format| parse_value::<OutputFormat>(format, "format"))
+            .transpose()?;
+        if format.is_some_and(OutputFormat::is_archive) {
+            anyhow::bail!("Archive formats are not served; run collect --format tar instead");
+        }
+        let mut options = self.options(request)?;
+        options.format = format.unwrap_or(OutputFormat::Ndjson);
+
+        let collector = Collector::new(options)?;
+        let mut buffer = Vec::new();
+        let summary = collector.write(&mut buffer)?;
+        if format.is_some() {
+            return Ok(json!({
+                "ok": true,
+                "count": summary.files,
+                "output": String::from_utf8_lossy(&buffer),
+            }));
+        }
+        let files = buffer
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<serde_json::Result<Vec<Value>>>()
+            .context("Failed to read back the records")?;
+        Ok(json!({ "ok": true, "count": files.len(), "files": files }))
+    }
+
+    /// Answers one HTTP request: `GET /list` (paths) or `GET /content`
+    /// (with content), the query fields as URL parameters. Records are
+    /// streamed as they are found, a JSON array or ND-JSON (`format=ndjson`
+    /// or `Accept: application/x-ndjson`), and the connection closes after.
+    fn http_exchange(&self, stream: &TcpStream) -> io::Result<()> {
+        let mut reader = io::BufReader::new(Read::take(stream, HTTP_HEAD_LIMIT));
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut ndjson = false;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.trim().eq_ignore_ascii_case("accept")
+            {
+                ndjson = value.contains("application/x-ndjson");
+            }
+        }
+        let mut writer = BufWriter::new(stream);
+
+        let mut parts = line.split_whitespace();
+        let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        log::debug!("{method} {target}");
+        let content = match (method, path) {
+            ("GET", "/list") => false,
+            ("GET", "/content") => true,
+            ("GET", _) => {
+                let error = format!("Not found: {path} (try /list or /content)");
+                return http_error(&mut writer, "404 Not Found", &error);
+            }
+            _ => return http_error(&mut writer, "405 Method Not Allowed", "Only GET is served"),
+        };
+        let collector = match self.http_collector(query, content, &mut ndjson) {
+            Ok(collector) => collector,
+            Err(e) => return http_error(&mut writer, "400 Bad Request", &format!("{e:#}")),
+        };
+
+        let content_type = if ndjson {
+            "application/x-ndjson"
+        } else {
+            "application/json"
+        };
+        write!(
+            writer,
+            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nConnection: close\r\n\r\n"
+        )?;
+        collector.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn http_collector(&self, query: &str, content: bool, ndjson: &mut bool) -> Result<Collector> {
+        let request = query_request(query)?;
+        if request.op.is_some() {
+            anyhow::bail!("'op' is only read on the socket");
+        }
+        match request.format.as_deref() {
+            None => {}
+            Some("ndjson") => *ndjson = true,
+            Some("json") => *ndjson = false,
+            Some(other) => anyhow::bail!("Invalid format '{other}' (expected json, ndjson)"),
+        }
+        let mut options = self.options(request)?;
+        options.read_content = content;
+        options.format = if *ndjson {
+            OutputFormat::Ndjson
+        } else {
+            OutputFormat::Json
+        };
+        Collector::new(options)
+    }
+}
+
+/// One socket request line, at most.
+const REQUEST_LIMIT: u64 = 1024 * 1024;
+
+/// Request line and headers of an HTTP request, at most.
+const HTTP_HEAD_LIMIT: u64 = 16 * 1024;
+
+/// How long an HTTP client may leave a read or a write waiting before its
+/// connection is dropped, so stalled clients do not hold a worker.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connections answered at once on the socket and on the HTTP address each.
+const MAX_CONNECTIONS: usize = 32;
+
+fn http_error(writer: &mut impl Write, status: &str, message: &str) -> io::Result<()> {
+    let body = json!({ "ok": false, "error": message }).to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()
+}
+
+/// The parameters of an HTTP query string as a `Request`: the CLI names
+/// (`-` or `_`), repeated or comma separated for lists, flags as
+/// `ignore-case` or `ignore-case=true`.
+fn query_request(query: &str) -> Result<Request> {
+    let mut fields = serde_json::Map::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        let key = percent_decode(key)?.replace('-', "_");
+        let value = percent_decode(value)?;
+        match key.as_str() {
+            "extension" | "no_extension" | "regex" | "glob" | "filter" => {
+                let items = fields
+                    .entry(key.as_str())
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(items) = items {
+                    if key.ends_with("extension") {
+                        items.extend(value.split(',').map(Value::from));
+                    } else {
+                        items.push(Value::from(value));
+                    }
+                }
+            }
+            "ignore_case" | "fixed_strings" | "content" => {
+                let enabled = match value.as_str() {
+                    "" | "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => anyhow::bail!("Invalid {key} '{value}' (expected true or false)"),
+                };
+                fields.insert(key, Value::from(enabled));
+            }
+            "max_bytes" | "max_files" | "max_tokens" => {
+                let number: u64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid {key} '{value}'"))?;
+                fields.insert(key, Value::from(number));
+            }
+            _ => {
+                fields.insert(key, Value::from(value));
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(fields)).context("Invalid query")
+}
+
+/// `%XX` escapes and `+` for spaces, as browsers send query strings.
+fn percent_decode(text: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .with_context(|| format!("Invalid escape in '{text}'"))?;
+                bytes.push(hex);
+                rest = rest.get(2..).unwrap_or_default();
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).with_context(|| format!("'{text}' is not UTF-8"))
+}
+
+fn request_size(size: &Value, field: &str) -> Result<u64> {
+    match size {
+        Value::Number(number) => number.as_u64(),
+        Value::String(text) => parse_size(text).ok(),
+        _ => None,
+    }
+    .with_context(|| format!("Invalid {field} {size} (expected e.g. 512, \"10K\", \"1.5M\")"))
+}
+
+fn request_time(time: &str, field: &str) -> Result<SystemTime> {
+    parse_time(time).map_err(|e| anyhow::anyhow!("Invalid {field}: {e}"))
+}
+
+/// A `ValueEnum` from its command line spelling, e.g. `"ndjson"`.
+pub(crate) fn parse_value<T: ValueEnum>(value: &str, field: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(ValueEnum::to_possible_value)
+            .map(|v| v.get_name().to_string())
+            .collect();
+        anyhow::anyhow!(
+            "Invalid {field} '{value}' (expected {})",
+            expected.join(", ")
+        )
+    })
+}
+
+/// Indexes the tree, then answers every connection on the socket and the
+/// HTTP address (a thread each) until killed; with `watch_mode` file system
+/// events refresh the index.
+pub fn serve(
+    options: CollectOptions,
+    socket: Option<&Path>,
+    http: Option<&str>,
+    watch_mode: bool,
+) -> Result<()> {
+    let unix = socket.map(bind_socket).transpose()?;
+    let tcp = http
+        .map(|addr| TcpListener::bind(addr).with_context(|| format!("Failed to listen on {addr}")))
+        .transpose()?;
+    let server = Server::new(options)?;
+    server.refresh()?;
+    if let Some(socket) = socket {
+        log::info!("Serving queries on {}", socket.display());
+    }
+    if let Some(addr) = tcp.as_ref().and_then(|tcp| tcp.local_addr().ok()) {
+        log::info!("Serving http://{addr}/list and /content");
+    }
+
+    let server = &server;
+    std::thread::scope(|scope| {
+        if watch_mode {
+            let own: Vec<PathBuf> = socket
+                .and_then(|socket| fs::canonicalize(socket).ok())
+                .into_iter()
+                .collect();
+            scope.spawn(move || follow_changes(server, &own));
+        }
+        if let Some(listener) = unix {
+            scope.spawn(move || accept_socket(server, listener));
+        }
+        if let Some(listener) = tcp {
+            scope.spawn(move || {
+                answer_connections(listener.incoming(), |stream: TcpStream| {
+                    let exchanged = stream
+                        .set_read_timeout(Some(HTTP_TIMEOUT))
+                        .and_then(|()| stream.set_write_timeout(Some(HTTP_TIMEOUT)))
+                        .and_then(|()| server.http_exchange(&stream));
+                    if let Err(e) = exchanged {
+                        log::debug!("HTTP connection ended: {e}");
+                    }
+                });
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Hands each accepted connection to one of `MAX_CONNECTIONS` workers,
+/// until the listener fails for good. While all of them are busy, new
+/// clients wait in the listen backlog.
+fn answer_connections<S: Send>(
+    incoming: impl Iterator<Item = io::Result<S>>,
+    answer: impl Fn(S) + Sync,
+) {
+    let (queue, streams) = mpsc::sync_channel::<S>(0);
+    let streams = Mutex::new(streams);
+    let (answer, streams) = (&answer, &streams);
+    std::thread::scope(|workers| {
+        for _ in 0..MAX_CONNECTIONS {
+            workers.spawn(move || {
+                loop {
+                    let next = streams
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    let Ok(stream) = next else {
+                        return;
+                    };
+                    answer(stream);
+                }
+            });
+        }
+        for stream in incoming {
+            match stream {
+                Ok(stream) => {
+                    if queue.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("Failed to accept a connection: {e}"),
+            }
+        }
+        // Lets the workers finish once the queue is empty
+        drop(queue);
+    });
+}
+
+/// Refreshes the index after every relevant burst of file system events.
+pub(crate) fn follow_changes(server: &Server, own: &[PathBuf]) {
+    let watched = on_changes(&server.indexer, own, || {
+        if let Err(e) = server.refresh() {
+            log::error!("Failed to refresh the index: {e:#}");
+        }
+        Ok(true)
+    });
+    if let Err(e) = watched {
+        log::error!("{e:#}");
+    }
+}
+
+#[cfg(unix)]
+type SocketListener = std::os::unix::net::UnixListener;
+
+/// Unix domain sockets are not in std elsewhere.
+#[cfg(not(unix))]
+type SocketListener = std::convert::Infallible;
+
+#[cfg(unix)]
+fn bind_socket(socket: &Path) -> Result<SocketListener> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    if let Ok(metadata) = fs::symlink_metadata(socket) {
+        // Never remove what is not ours to replace
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{}: path exists and is not a socket", socket.display());
+        }
+        if UnixStream::connect(socket).is_ok() {
+            anyhow::bail!("A server is already listening on {}", socket.display());
+        }
+        // Left behind by a server that was killed
+        fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+    SocketListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))
+}
+
+#[cfg(not(unix))]
+fn bind_socket(_socket: &Path) -> Result<SocketListener> {
+    anyhow::bail!("--serve needs Unix domain sockets; use --http")
+}
+
+#[cfg(unix)]
+fn accept_socket(server: &Server, listener: SocketListener) {
+    answer_connections(listener.incoming(), |stream| {
+        server.connection(io::BufReader::new(&stream), &stream);
+    });
+}
+
+#[cfg(not(unix))]
+fn accept_socket(_server: &Server, listener: SocketListener) {
+    match listener {}
+}
@@ -0,0 +1,233 @@
+//! `--template` / `--header-template`: user-defined text per file, with
+//! `{placeholder}` fields.
+
+use crate::collector::{Entry, entry_size};
+use crate::hash::hash_file;
+use crate::languages::language_of;
+use crate::metadata::format_time;
+use crate::options::{CollectOptions, HashAlgorithm};
+use crate::output::write_bare_content;
+use crate::redact::Redactor;
+use anyhow::{Result, bail};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Every placeholder name, for error messages and the guide.
+const PLACEHOLDERS: &str = "path, name, ext, lang, size, mtime, hash, tokens, commit, author, \
+     committed, depth, index, content";
+
+/// The parsed `--template` and `--header-template`, shared by all records.
+#[derive(Debug, Default)]
+pub(crate) struct Templates {
+    /// Replaces the whole text block of a file.
+    pub(crate) record: Option<Template>,
+    /// Replaces the `=== path ===` line of content blocks.
+    pub(crate) header: Option<Template>,
+}
+
+/// A parsed template: literal text and placeholders, in order.
+#[derive(Debug)]
+pub(crate) struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Text(String),
+    Field(Placeholder),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Placeholder {
+    Path,
+    Name,
+    Ext,
+    Lang,
+    Size,
+    Mtime,
+    Hash,
+    Tokens,
+    Commit,
+    Author,
+    Committed,
+    Depth,
+    Index,
+    Content,
+}
+
+impl Template {
+    /// `{name}` inserts a field, `{{` / `}}` are literal braces, and `\n`,
+    /// `\t`, `\\` are escapes (see `unescape`). `flag` names the option in
+    /// errors.
+    pub(crate) fn parse(source: &str, flag: &str) -> Result<Self> {
+        let source = unescape(source);
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(n) => name.push(n),
+                            None => bail!("Unclosed placeholder '{{{name}' in {flag}"),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "path" => Placeholder::Path,
+                        "name" => Placeholder::Name,
+                        "ext" => Placeholder::Ext,
+                        "lang" => Placeholder::Lang,
+                        "size" => Placeholder::Size,
+                        "mtime" => Placeholder::Mtime,
+                        "hash" => Placeholder::Hash,
+                        "tokens" => Placeholder::Tokens,
+                        "commit" => Placeholder::Commit,
+                        "author" => Placeholder::Author,
+                        "committed" => Placeholder::Committed,
+                        "depth" => Placeholder::Depth,
+                        "index" => Placeholder::Index,
+                        "content" => Placeholder::Content,
+                        other => {
+                            bail!("Unknown placeholder '{{{other}}}' in {flag} ({PLACEHOLDERS})")
+                        }
+                    };
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => bail!("Unmatched '}}' in {flag} (use '}}}}' for a literal brace)"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Self { segments })
+    }
+
+    pub(crate) fn uses(&self, placeholder: Placeholder) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, Segment::Field(p) if *p == placeholder))
+    }
+
+    /// Whether a placeholder needs the files' last commits.
+    pub(crate) fn uses_git(&self) -> bool {
+        [
+            Placeholder::Commit,
+            Placeholder::Author,
+            Placeholder::Committed,
+        ]
+        .into_iter()
+        .any(|p| self.uses(p))
+    }
+
+    /// Writes one record. `{content}` is the file content without the text
+    /// block framing (`max_bytes`, binary handling and line options apply);
+    /// `{index}` is the 1-based record number.
+    pub(crate) fn render(
+        &self,
+        entry: &Entry,
+        path_display: &Path,
+        options: &CollectOptions,
+        redactor: Option<&Redactor>,
+        index: usize,
+        writer: &mut dyn Write,
+    ) -> io::Result<()> {
+        let path = entry.path();
+        for segment in &self.segments {
+            let field = match segment {
+                Segment::Text(text) => {
+                    writer.write_all(text.as_bytes())?;
+                    continue;
+                }
+                Segment::Field(field) => *field,
+            };
+            let value = match field {
+                Placeholder::Path => path_display.display().to_string(),
+                Placeholder::Name => path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                Placeholder::Ext => path
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default(),
+                Placeholder::Lang => language_of(path).name.to_string(),
+                Placeholder::Size => match entry.metadata() {
+                    Some(m) => m.len().to_string(),
+                    None => fs::metadata(entry.content_path())
+                        .map_or_else(|_| "-".into(), |m| m.len().to_string()),
+                },
+                Placeholder::Mtime => {
+                    let mtime = match entry.metadata() {
+                        Some(m) => m.modified().ok(),
+                        None => fs::metadata(entry.content_path())
+                            .and_then(|m| m.modified())
+                            .ok(),
+                    };
+                    format_time(mtime)
+                }
+                Placeholder::Hash => hash_file(
+                    entry.content_path(),
+                    options.hash.unwrap_or(HashAlgorithm::Sha256),
+                )
+                .unwrap_or_else(|_| "-".to_string()),
+                Placeholder::Tokens => entry
+                    .tokens()
+                    .map_or_else(|| "-".to_string(), |t| t.to_string()),
+                Placeholder::Commit => entry
+                    .git_commit()
+                    .map_or_else(|| "-".to_string(), |c| c.hash.clone()),
+                Placeholder::Author => entry
+                    .git_commit()
+                    .map_or_else(|| "-".to_string(), |c| c.author.clone()),
+                Placeholder::Committed => format_time(entry.git_commit().map(|c| c.time)),
+                Placeholder::Depth => entry.depth().to_string(),
+                Placeholder::Index => (index + 1).to_string(),
+                Placeholder::Content => match entry.content_marker() {
+                    Some(marker) => marker,
+                    None => {
+                        let source = entry.content_path();
+                        write_bare_content(source, entry_size(entry), writer, options, redactor)?;
+                        continue;
+                    }
+                },
+            };
+            writer.write_all(value.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Backslash escapes for template strings, which are hard to pass with real
+/// newlines on a command line: `\n`, `\t`, `\\`. Anything else is kept as is.
+pub(crate) fn unescape(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,84 @@
+//! TODO / FIXME comment extraction for `--todos`.
+
+use crate::options::BinaryCheck;
+use crate::search::scan_file;
+use regex::Regex;
+use serde::Serialize;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Block comment closers dropped from the end of a marker line.
+const CLOSERS: &[&str] = &["*/", "-->", "-}", "*)", "#>"];
+
+/// Markers used when none are given.
+pub const DEFAULT_TODO_MARKERS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// A marker comment in a matched file.
+#[derive(Clone, Debug, Serialize)]
+pub struct Todo {
+    /// Relative path, as in the listing.
+    pub path: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// The marker found (`TODO`, `FIXME`, ...).
+    pub marker: String,
+    /// The line from the marker on, trimmed (`TODO(ana): split this`).
+    pub text: String,
+}
+
+/// Markers compiled into one case-sensitive, whole-word regex.
+#[derive(Debug)]
+pub(crate) struct TodoScanner {
+    regex: Regex,
+}
+
+impl TodoScanner {
+    pub(crate) fn new(markers: &[String]) -> Result<Self, regex::Error> {
+        let alternation = markers
+            .iter()
+            .map(|m| regex::escape(m))
+            .collect::<Vec<_>>()
+            .join("|");
+        Ok(Self {
+            regex: Regex::new(&format!(r"\b({alternation})\b"))?,
+        })
+    }
+
+    /// Every marker line of a text file (none for binary files), the first
+    /// marker of a line deciding its `marker`.
+    pub(crate) fn scan(
+        &self,
+        path: &Path,
+        name: &str,
+        size: u64,
+        check: BinaryCheck,
+    ) -> io::Result<Vec<Todo>> {
+        let mut todos = Vec::new();
+        let mut number = 0;
+        scan_file(File::open(path)?, size, check, |line| {
+            number += 1;
+            let text = String::from_utf8_lossy(line);
+            if let Some(found) = self.regex.find(&text) {
+                todos.push(Todo {
+                    path: name.to_string(),
+                    line: number,
+                    marker: found.as_str().to_string(),
+                    text: comment_text(text.get(found.start()..).unwrap_or_default()),
+                });
+            }
+            true
+        })?;
+        Ok(todos)
+    }
+}
+
+fn comment_text(text: &str) -> String {
+    let text = text.trim();
+    CLOSERS
+        .iter()
+        .find_map(|closer| text.strip_suffix(closer))
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
@@ -0,0 +1,97 @@
+//! Token estimation for LLM packing (`--count-tokens`, `--max-tokens`).
+//!
+//! No tokenizer is bundled: the estimate is the common ~4 bytes per token
+//! rule of thumb, applied to the bytes that would actually be emitted.
+
+use crate::binary::{PROBE_LEN, binary_limit, looks_binary, rendered_len, utf16_bom};
+use crate::collector::Entry;
+use crate::options::CollectOptions;
+use crate::output::{converted_text, read_shaped_text};
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::Path;
+
+const BYTES_PER_TOKEN: u64 = 4;
+
+/// Estimated tokens for a file's emitted content (0 for binary files, which
+/// are suppressed in content output).
+pub(crate) fn estimate_tokens(
+    path: &Path,
+    meta: Option<&Metadata>,
+    options: &CollectOptions,
+) -> io::Result<u64> {
+    Ok(emitted_bytes(path, meta, options)?.map_or(0, tokens_for))
+}
+
+/// Content bytes `--content` would write for a file, after `max_bytes` and
+/// `max_line_bytes` (binary files as rendered by `binary`). `None` for suppressed binary files.
+pub(crate) fn emitted_bytes(
+    path: &Path,
+    meta: Option<&Metadata>,
+    options: &CollectOptions,
+) -> io::Result<Option<u64>> {
+    if let Some(text) = converted_text(path, options) {
+        return Ok(Some(text?.len() as u64));
+    }
+    let (max_bytes, binary, check) = (options.max_bytes, options.binary, options.binary_check);
+
+    let mut head = [0u8; PROBE_LEN];
+    let n = File::open(path)?.read(&mut head)?;
+    let head = head.get(..n).unwrap_or_default();
+    let size = meta.map_or(n as u64, Metadata::len);
+
+    if looks_binary(head, check) {
+        let rendered = rendered_len(binary, size.min(binary_limit(binary, max_bytes)));
+        return Ok((rendered > 0).then_some(rendered));
+    }
+
+    // Clamped lines can leave a fraction of the file
+    if options.max_line_bytes.is_some() {
+        return Ok(read_shaped_text(path, options, None)?.map(|text| text.len() as u64));
+    }
+
+    let text = max_bytes.map_or(size, |max| size.min(max));
+    // Decoded to UTF-8: about one byte per code unit for source code
+    if utf16_bom(head, check).is_some() {
+        return Ok(Some(text / 2));
+    }
+    Ok(Some(text))
+}
+
+pub(crate) fn tokens_for(bytes: u64) -> u64 {
+    bytes.div_ceil(BYTES_PER_TOKEN)
+}
+
+/// Drops the largest entries until the total fits the budget, keeping the
+/// order of the remaining ones. Returns how many were dropped.
+pub(crate) fn drop_largest(entries: &mut Vec<Entry>, budget: u64) -> usize {
+    let tokens = |e: &Entry| e.tokens().unwrap_or(0);
+    let mut total: u64 = entries.iter().map(tokens).sum();
+    if total <= budget {
+        return 0;
+    }
+
+    let mut by_size: Vec<usize> = (0..entries.len()).collect();
+    by_size.sort_unstable_by_key(|&i| std::cmp::Reverse(entries.get(i).map_or(0, tokens)));
+
+    let mut dropped = vec![false; entries.len()];
+    let mut count = 0;
+    for i in by_size {
+        if total <= budget {
+            break;
+        }
+        total -= entries.get(i).map_or(0, tokens);
+        if let Some(flag) = dropped.get_mut(i) {
+            *flag = true;
+        }
+        count += 1;
+    }
+
+    let mut index = 0;
+    entries.retain(|_| {
+        let keep = !dropped.get(index).copied().unwrap_or(false);
+        index += 1;
+        keep
+    });
+    count
+}
@@ -0,0 +1,105 @@
+//! `--copy-to` / `--move-to`: replicate matched files under a destination tree.
+
+use crate::archive::archive_name;
+use crate::collector::Collector;
+use crate::errors::io_kind;
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TransferMode {
+    Copy,
+    /// Rename when possible, copy + delete across file systems.
+    Move,
+}
+
+/// Outcome of a transfer run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransferSummary {
+    pub files: usize,
+    pub bytes: u64,
+    pub failures: usize,
+}
+
+impl Collector {
+    /// Copies or moves every matched file to `dest`, keeping its path relative
+    /// to the base path. Each file is written to a temporary name and renamed
+    /// into place, so readers never see a partial file.
+    pub fn transfer(&self, dest: &Path, mode: TransferMode) -> Result<TransferSummary> {
+        // Archive members only have virtual paths, and cannot be moved out
+        if self.options().archives {
+            anyhow::bail!("--copy-to and --move-to cannot be used with --archives");
+        }
+        // Walk first: the destination may live inside the tree being walked
+        let paths: Vec<PathBuf> = self.entries()?.map(|e| e.path().to_path_buf()).collect();
+        fs::create_dir_all(dest)
+            .with_context(|| format!("Failed to create destination {}", dest.display()))?;
+
+        let mut summary = TransferSummary::default();
+        for path in paths {
+            let target = dest.join(archive_name(&path, self.options()));
+            match transfer_file(&path, &target, mode) {
+                Ok(bytes) => {
+                    summary.files += 1;
+                    summary.bytes += bytes;
+                }
+                Err(e) => {
+                    summary.failures += 1;
+                    self.warn(&format!(
+                        "Error transferring {} to {}: {}",
+                        path.display(),
+                        target.display(),
+                        e
+                    ));
+                    self.record_error(Some(&path), "transferring", &io_kind(&e), &e.to_string());
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn transfer_file(source: &Path, target: &Path, mode: TransferMode) -> io::Result<u64> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if mode == TransferMode::Move {
+        let bytes = fs::metadata(source)?.len();
+        match fs::rename(source, target) {
+            Ok(()) => return Ok(bytes),
+            // Different file system: fall back to copy + delete
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let bytes = copy_atomic(source, target)?;
+    if mode == TransferMode::Move {
+        fs::remove_file(source)?;
+    }
+    Ok(bytes)
+}
+
+/// Copies to a hidden sibling, restores the mtime, then renames over `target`.
+fn copy_atomic(source: &Path, target: &Path) -> io::Result<u64> {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = target.with_file_name(format!(".{name}.collect-tmp"));
+
+    let result = fs::copy(source, &tmp).and_then(|bytes| {
+        if let Ok(mtime) = fs::metadata(source).and_then(|m| m.modified()) {
+            File::options()
+                .write(true)
+                .open(&tmp)?
+                .set_modified(mtime)?;
+        }
+        fs::rename(&tmp, target)?;
+        Ok(bytes)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
@@ -0,0 +1,115 @@
+//! `--format tree`: the matched files drawn as a directory tree rooted at the base path.
+
+use crate::archive::archive_name;
+use crate::collector::Entry;
+use crate::options::{CollectOptions, EntryType, SortKey};
+use std::io::{self, Write};
+
+/// One directory level. Children keep the order they were first seen in,
+/// so `--sort` still applies within each directory (unsorted walks are
+/// ordered by name, like `tree`).
+#[derive(Default)]
+pub(crate) struct Node {
+    pub(crate) children: Vec<(String, Self)>,
+    pub(crate) is_dir: bool,
+    /// Position of the entry in the walk (leaves only; used by `--format html`).
+    pub(crate) index: Option<usize>,
+}
+
+impl Node {
+    /// The node for a `/` separated relative name, creating it and its
+    /// parent directories as needed.
+    pub(crate) fn insert(&mut self, name: &str) -> &mut Self {
+        let mut node = self;
+        let mut parts = name.split('/').peekable();
+        while let Some(part) = parts.next() {
+            node = node.child(part);
+            if parts.peek().is_some() {
+                node.is_dir = true;
+            }
+        }
+        node
+    }
+
+    fn child(&mut self, name: &str) -> &mut Self {
+        let index = match self.children.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.children.push((name.to_string(), Self::default()));
+                self.children.len() - 1
+            }
+        };
+        &mut self
+            .children
+            .get_mut(index)
+            .expect("child index is in range")
+            .1
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, child) in &mut self.children {
+            child.sort();
+        }
+    }
+}
+
+/// Renders every entry, then a `N directories, M files` footer like `tree`.
+/// Returns the number of entries.
+pub(crate) fn write_tree(
+    entries: impl Iterator<Item = Entry>,
+    options: &CollectOptions,
+    writer: &mut dyn Write,
+) -> io::Result<usize> {
+    let mut root = Node::default();
+    let mut files = 0;
+    let mut count = 0;
+    for entry in entries {
+        count += 1;
+        let node = root.insert(&archive_name(entry.path(), options));
+        // Selected directories (`--type d`) are leaves unless files below matched too
+        if options.types.contains(&EntryType::Dir) && entry.path().is_dir() {
+            node.is_dir = true;
+        } else {
+            files += 1;
+        }
+    }
+    if options.sort == SortKey::None {
+        root.sort();
+    }
+
+    writeln!(writer, "{}", options.base_path.display())?;
+    let mut dirs = 0;
+    write_children(&root, "", writer, &mut dirs)?;
+    writeln!(
+        writer,
+        "\n{dirs} director{}, {files} file{}",
+        if dirs == 1 { "y" } else { "ies" },
+        if files == 1 { "" } else { "s" }
+    )?;
+    Ok(count)
+}
+
+fn write_children(
+    node: &Node,
+    prefix: &str,
+    writer: &mut dyn Write,
+    dirs: &mut usize,
+) -> io::Result<()> {
+    let last = node.children.len().saturating_sub(1);
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let (branch, indent) = if i == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        if child.is_dir {
+            *dirs += 1;
+            writeln!(writer, "{prefix}{branch}{name}/")?;
+            write_children(child, &format!("{prefix}{indent}"), writer, dirs)?;
+        } else {
+            writeln!(writer, "{prefix}{branch}{name}")?;
+        }
+    }
+    Ok(())
+}
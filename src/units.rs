@@ -0,0 +1,192 @@
+//! Parsers for human-friendly CLI values (sizes, dates, durations and line
+//! ranges).
+
+use crate::metadata::days_from_civil;
+use crate::options::LineRange;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parses line ranges: `100:200`, `100:` (to the end), `:50`, a single line
+/// `42`, or any of those behind a relative path (`src/main.rs:100:200`).
+pub fn parse_line_range(input: &str) -> Result<LineRange, String> {
+    let invalid =
+        || format!("invalid line range '{input}' (expected [PATH:]START:END, e.g. 100:200)");
+    let number = |s: &str| -> Result<Option<usize>, String> {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        match s.parse::<usize>() {
+            Ok(0) => Err(format!("line numbers start at 1 in '{input}'")),
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(invalid()),
+        }
+    };
+    let is_number = |s: &str| s.is_empty() || s.bytes().all(|b| b.is_ascii_digit());
+
+    let mut parts = input.rsplitn(3, ':');
+    let last = parts.next().unwrap_or_default();
+    let (path, start, end) = match (parts.next(), parts.next()) {
+        // `PATH:START:END`, or a path with colons and a single line
+        (Some(start), Some(path)) if is_number(start) => (Some(path), start, last),
+        (Some(start), Some(path)) => (
+            Some(input.get(..path.len() + 1 + start.len()).unwrap_or(path)),
+            last,
+            last,
+        ),
+        // `START:END` or `PATH:LINE`
+        (Some(first), None) if is_number(first) => (None, first, last),
+        (Some(path), None) => (Some(path), last, last),
+        (None, _) => (None, last, last),
+    };
+    let start = number(start)?;
+    let end = number(end)?;
+    if start.is_none() && end.is_none() && !input.contains(':') {
+        return Err(invalid());
+    }
+    let start = start.unwrap_or(1);
+    if end.is_some_and(|end| end < start) {
+        return Err(format!("line range '{input}' ends before it starts"));
+    }
+    Ok(LineRange {
+        path: path.filter(|p| !p.is_empty()).map(str::to_string),
+        start,
+        end,
+    })
+}
+
+/// Parses sizes like `512`, `1K`, `10M`, `1.5G` or `2KiB` into bytes.
+/// Units are binary (1K = 1024 bytes), matching `du -h`.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let s = input.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        other => return Err(format!("unknown size unit '{other}' in '{input}'")),
+    };
+
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size '{input}' is too large"));
+    }
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{input}'"))?;
+    let bytes = (value * multiplier as f64).round();
+    if !bytes.is_finite() || bytes < 0.0 || bytes >= u64::MAX as f64 {
+        return Err(format!("size '{input}' is out of range"));
+    }
+    // Range checked above
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Ok(bytes as u64)
+}
+
+/// Formats a byte count for humans (`512 B`, `1.5 KiB`, `3.2 GiB`).
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = "KiB";
+    for next in UNITS.iter().skip(1) {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Parses a point in time: either an absolute UTC date (`2024-01-31`,
+/// `2024-01-31T12:00`, `2024-01-31 12:00:00`) or a duration ago (`2d`,
+/// `3h30m`, `1w`). Duration units: s, m/min, h, d, w, y (365 days).
+pub fn parse_time(input: &str) -> Result<SystemTime, String> {
+    let s = input.trim();
+
+    if let Some(duration) = parse_duration(s) {
+        return SystemTime::now()
+            .checked_sub(duration)
+            .ok_or_else(|| format!("duration '{input}' reaches before the epoch"));
+    }
+
+    parse_date(s).ok_or_else(|| {
+        format!("invalid time '{input}' (expected YYYY-MM-DD[THH:MM[:SS]] or a duration like 2d)")
+    })
+}
+
+/// `3h30m` style durations. Returns `None` if the input is not a duration.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total: u64 = 0;
+    let mut rest = s;
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let (number, tail) = rest.split_at(digits);
+        let value: u64 = number.parse().ok()?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, next) = tail.split_at(unit_len);
+        let seconds: u64 = match unit {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" => 3600,
+            "d" => 86_400,
+            "w" => 7 * 86_400,
+            "y" => 365 * 86_400,
+            _ => return None,
+        };
+
+        total = total.checked_add(value.checked_mul(seconds)?)?;
+        rest = next;
+    }
+
+    (!s.is_empty()).then_some(Duration::from_secs(total))
+}
+
+/// `YYYY-MM-DD` with an optional `THH:MM[:SS]` (or space separated) time, as UTC.
+fn parse_date(s: &str) -> Option<SystemTime> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (s, None),
+    };
+
+    let mut parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut secs = days_from_civil(year, month, day) * 86_400;
+    if let Some(time) = time {
+        let mut parts = time.splitn(3, ':').map(str::parse::<i64>);
+        let hours = parts.next()?.ok()?;
+        let minutes = parts.next()?.ok()?;
+        let seconds = parts.next().transpose().ok()?.unwrap_or(0);
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+        secs += hours * 3600 + minutes * 60 + seconds;
+    }
+
+    match u64::try_from(secs) {
+        Ok(secs) => UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+        Err(_) => UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+    }
+}
@@ -0,0 +1,325 @@
+//! `--archives`: zip and tar files opened like directories. Their members
+//! get virtual paths (`bundle.zip!/src/main.rs`). Each matched member has an
+//! empty stand-in file in a scratch directory carrying its size and mtime,
+//! so the pipeline filters on metadata like for any other file; the content
+//! is only unpacked once a filter or the output reads it.
+
+use crate::metadata::days_from_civil;
+use flate2::read::MultiGzDecoder;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Separates the archive path from the member name in virtual paths.
+pub(crate) const MEMBER_SEPARATOR: &str = "!/";
+
+/// Members larger than this are left out (with a warning) rather than
+/// unpacked.
+const MEMBER_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Bytes unpacked at once, over all archives of a collector; members past
+/// it are left unread.
+const TOTAL_LIMIT: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Attempts at a scratch directory name nobody else has taken.
+const SCRATCH_ATTEMPTS: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// The container format of `path`, by name.
+pub(crate) fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// `archive!/name`.
+pub(crate) fn member_path(archive: &Path, name: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(MEMBER_SEPARATOR);
+    path.push(name);
+    PathBuf::from(path)
+}
+
+/// A matched member and its files in the scratch directory.
+#[derive(Debug)]
+pub(crate) struct Member {
+    /// Path inside the archive, `/` separated.
+    pub(crate) name: String,
+    /// Empty stand-in with the member's size and mtime, for its metadata.
+    pub(crate) stat: PathBuf,
+    /// The content once unpacked, keeping the member's own name (and
+    /// extension, for language detection).
+    pub(crate) data: PathBuf,
+    size: u64,
+}
+
+/// Private directory for archive members, removed with the collector.
+#[derive(Debug)]
+pub(crate) struct Scratch {
+    dir: PathBuf,
+    next: AtomicUsize,
+    /// Bytes of content unpacked and not yet removed.
+    used: Arc<AtomicU64>,
+    /// `quiet`: members left out are not warned about.
+    quiet: bool,
+}
+
+impl Scratch {
+    /// Creates a new directory under a random name, readable by the user
+    /// only; a name that already exists is never reused.
+    pub(crate) fn new(quiet: bool) -> io::Result<Self> {
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        let mut attempt = 0;
+        loop {
+            let mut random = RandomState::new().build_hasher();
+            random.write_u32(attempt);
+            let dir = std::env::temp_dir().join(format!("collect-{:016x}", random.finish()));
+            match builder.create(&dir) {
+                Ok(()) => {
+                    return Ok(Self {
+                        dir,
+                        next: AtomicUsize::new(0),
+                        used: Arc::new(AtomicU64::new(0)),
+                        quiet,
+                    });
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::AlreadyExists && attempt < SCRATCH_ATTEMPTS =>
+                {
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Lists the regular files of the archive at `path` whose names `want`
+    /// accepts and sets up their stand-ins; nothing is unpacked yet.
+    /// Directories, links and the rest are passed over.
+    pub(crate) fn open(
+        &self,
+        path: &Path,
+        kind: ArchiveKind,
+        want: &dyn Fn(&str) -> bool,
+    ) -> io::Result<Unpacked> {
+        let mut unpacked = Unpacked {
+            archive: path.to_path_buf(),
+            kind,
+            members: Vec::new(),
+            used: Arc::clone(&self.used),
+            quiet: self.quiet,
+            written: OnceLock::new(),
+        };
+        each_file(path, kind, &mut |name, size, mtime, _| {
+            if !want(name) {
+                return Ok(true);
+            }
+            if size > MEMBER_LIMIT {
+                warn(
+                    self.quiet,
+                    &format!(
+                        "Skipping {}: {size} bytes, more than archive members may unpack to",
+                        member_path(path, name).display()
+                    ),
+                );
+                return Ok(true);
+            }
+            let n = self.next.fetch_add(1, Ordering::Relaxed);
+            let base = name.rsplit('/').next().unwrap_or_default();
+            let member = Member {
+                name: name.to_string(),
+                stat: self.dir.join(format!("{n}.stat")),
+                data: self.dir.join(format!("{n}-{base}")),
+                size,
+            };
+            // Pushed first, so the stand-in is removed even if this fails
+            let stat = member.stat.clone();
+            unpacked.members.push(member);
+            let file = File::create(&stat)?;
+            file.set_len(size)?;
+            if let Some(mtime) = mtime {
+                file.set_modified(mtime)?;
+            }
+            Ok(true)
+        })?;
+        Ok(unpacked)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The matched members of one archive. Their files are removed when the
+/// last entry holding them is dropped.
+#[derive(Debug)]
+pub(crate) struct Unpacked {
+    archive: PathBuf,
+    kind: ArchiveKind,
+    pub(crate) members: Vec<Member>,
+    used: Arc<AtomicU64>,
+    quiet: bool,
+    /// Bytes unpacked, once done.
+    written: OnceLock<u64>,
+}
+
+impl Unpacked {
+    /// Unpacks the content of every member in one pass over the archive,
+    /// the first time it is asked for. Members left out (over the total
+    /// limit, or after a broken archive) have no content file, so reading
+    /// them fails like for a vanished file.
+    pub(crate) fn unpack(&self) {
+        self.written.get_or_init(|| {
+            let mut written = 0;
+            if let Err(e) = self.unpack_all(&mut written) {
+                warn(
+                    self.quiet,
+                    &format!("Error unpacking {}: {e}", self.archive.display()),
+                );
+            }
+            written
+        });
+    }
+
+    fn unpack_all(&self, written: &mut u64) -> io::Result<()> {
+        let mut pending: HashMap<&str, &Member> = HashMap::new();
+        for member in &self.members {
+            pending.entry(member.name.as_str()).or_insert(member);
+        }
+        each_file(&self.archive, self.kind, &mut |name, _, _, reader| {
+            let Some(member) = pending.remove(name) else {
+                return Ok(true);
+            };
+            let bytes = member.size;
+            if self.used.fetch_add(bytes, Ordering::Relaxed) + bytes > TOTAL_LIMIT {
+                self.used.fetch_sub(bytes, Ordering::Relaxed);
+                warn(
+                    self.quiet,
+                    &format!(
+                        "Skipping {}: archive members already fill the space they may use",
+                        member_path(&self.archive, name).display()
+                    ),
+                );
+                return Ok(!pending.is_empty());
+            }
+            *written += bytes;
+            let mut file = File::create(&member.data)?;
+            io::copy(&mut reader.take(bytes), &mut file)?;
+            Ok(!pending.is_empty())
+        })
+    }
+}
+
+impl Drop for Unpacked {
+    fn drop(&mut self) {
+        for member in &self.members {
+            let _ = fs::remove_file(&member.stat);
+            let _ = fs::remove_file(&member.data);
+        }
+        if let Some(written) = self.written.get() {
+            self.used.fetch_sub(*written, Ordering::Relaxed);
+        }
+    }
+}
+
+fn warn(quiet: bool, message: &str) {
+    if !quiet {
+        log::warn!("{message}");
+    }
+}
+
+/// What `each_file` hands over per regular file: its name, size, mtime and
+/// content; returns whether to go on.
+type Visit<'a> = dyn FnMut(&str, u64, Option<SystemTime>, &mut dyn Read) -> io::Result<bool> + 'a;
+
+/// Goes through the regular files of the archive at `path` in order.
+fn each_file(path: &Path, kind: ArchiveKind, visit: &mut Visit<'_>) -> io::Result<()> {
+    let file = File::open(path)?;
+    match kind {
+        ArchiveKind::Zip => each_zip_file(file, visit),
+        ArchiveKind::Tar => each_tar_file(file, visit),
+        ArchiveKind::TarGz => each_tar_file(MultiGzDecoder::new(file), visit),
+    }
+}
+
+fn each_zip_file(file: File, visit: &mut Visit<'_>) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        if !entry.is_file() || entry.is_symlink() {
+            continue;
+        }
+        let name = entry
+            .name()
+            .map_err(io::Error::other)?
+            .trim_start_matches('/')
+            .to_string();
+        if name.is_empty() {
+            continue;
+        }
+        let mtime = entry.last_modified().map(|t| {
+            let days = days_from_civil(t.year().into(), t.month().into(), t.day().into());
+            let seconds = days * 86_400
+                + i64::from(t.hour()) * 3600
+                + i64::from(t.minute()) * 60
+                + i64::from(t.second());
+            UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).unwrap_or(0))
+        });
+        let size = entry.size();
+        if !visit(&name, size, mtime, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn each_tar_file(reader: impl Read, visit: &mut Visit<'_>) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().replace('\\', "/");
+        let name = name
+            .trim_start_matches("./")
+            .trim_start_matches('/')
+            .to_string();
+        if name.is_empty() {
+            continue;
+        }
+        let mtime = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        let size = entry.size();
+        if !visit(&name, size, mtime, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
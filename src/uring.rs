@@ -0,0 +1,250 @@
+//! `--backend io-uring` (Linux): read-ahead for cold-cache scans. The walker
+//! itself stays the same; as it enters each directory, a background thread
+//! queues a `statx` for every child (and an `openat` / `read` of the first
+//! bytes of each file when content is read) on an io_uring, many
+//! in flight at once. The walker's own syscalls, one at a time, then find
+//! inodes and pages in memory instead of waiting on the disk for each.
+//! Built with the `io-uring` feature only; without it the backend falls
+//! back to `std`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+
+/// Directories to read ahead, fed by the walker; stops the thread that
+/// works through them when dropped with the walk.
+#[derive(Debug)]
+pub(crate) struct Prefetch {
+    queue: Sender<PathBuf>,
+    done: Arc<AtomicBool>,
+}
+
+impl Prefetch {
+    /// Sets up the ring and its thread. Fails where io_uring is missing:
+    /// other systems, kernels before 5.6, or a sandbox that blocks it.
+    pub(crate) fn start(contents: bool) -> io::Result<Self> {
+        let (queue, directories) = mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        ring::spawn(directories, Arc::clone(&done), contents)?;
+        Ok(Self { queue, done })
+    }
+
+    /// Reads the children of `dir` ahead of the walker.
+    pub(crate) fn directory(&self, dir: &Path) {
+        // The thread only goes away after an io_uring failure
+        let _ = self.queue.send(dir.to_path_buf());
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        // Directories still queued are not worth reading once the walk is over
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+mod ring {
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc::Receiver;
+
+    pub(super) fn spawn(
+        _directories: Receiver<PathBuf>,
+        _done: Arc<AtomicBool>,
+        _contents: bool,
+    ) -> io::Result<()> {
+        let reason = if cfg!(target_os = "linux") {
+            "collect was built without the io-uring feature"
+        } else {
+            "io_uring is only available on Linux"
+        };
+        Err(io::Error::new(io::ErrorKind::Unsupported, reason))
+    }
+}
+
+/// The `io-uring` crate hands raw pointers to the kernel, so pushing
+/// operations is `unsafe`.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[allow(unsafe_code)]
+mod ring {
+    use io_uring::{IoUring, opcode, types};
+    use std::ffi::CString;
+    use std::fs;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::Receiver;
+    use std::thread;
+
+    /// Children handled per round trip; each takes up to two submission
+    /// queue entries (statx and openat).
+    const BATCH: usize = 64;
+
+    /// Bytes of each file read ahead: enough for the binary probe and to set
+    /// off the kernel's own read-ahead for the rest.
+    const READ_AHEAD: u32 = 64 * 1024;
+
+    /// `user_data` of the openat of child `i` (its statx is `i`).
+    const OPEN: u64 = 1 << 32;
+
+    pub(super) fn spawn(
+        directories: Receiver<PathBuf>,
+        done: Arc<AtomicBool>,
+        contents: bool,
+    ) -> io::Result<()> {
+        let ring = IoUring::new(u32::try_from(BATCH * 2).unwrap_or(u32::MAX))?;
+        thread::Builder::new()
+            .name("collect-uring".into())
+            .spawn(move || {
+                let mut ring = ring;
+                let mut buffers = vec![0u8; BATCH * READ_AHEAD as usize];
+                while let Ok(dir) = directories.recv() {
+                    if done.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let Ok(children) = fs::read_dir(&dir) else {
+                        continue;
+                    };
+                    let children: Vec<Child> = children.flatten().filter_map(Child::new).collect();
+                    for batch in children.chunks(BATCH) {
+                        if let Err(e) = warm(&mut ring, batch, contents, &mut buffers) {
+                            log::debug!("io_uring read-ahead stopped: {e}");
+                            // Operations may still be in flight on the paths
+                            // and buffers, which are leaked rather than freed
+                            std::mem::forget(children);
+                            std::mem::forget(buffers);
+                            return;
+                        }
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    struct Child {
+        path: CString,
+        /// Regular file (not followed through links), worth reading ahead.
+        file: bool,
+    }
+
+    impl Child {
+        fn new(entry: fs::DirEntry) -> Option<Self> {
+            let path = CString::new(entry.path().as_os_str().as_bytes()).ok()?;
+            let file = entry.file_type().is_ok_and(|t| t.is_file());
+            Some(Self { path, file })
+        }
+    }
+
+    /// One round trip for `batch`: statx and openat together, then reads of
+    /// the opened files. Every operation of a step has completed before the
+    /// next starts and before `warm` returns, so the paths and buffers handed
+    /// to the kernel outlive their use. The opened files are closed however
+    /// it ends.
+    fn warm(
+        ring: &mut IoUring,
+        batch: &[Child],
+        contents: bool,
+        buffers: &mut [u8],
+    ) -> io::Result<()> {
+        // SAFETY: plain data the kernel fills in; all zeroes is a valid value
+        let mut stats: Vec<libc::statx> = (0..batch.len())
+            .map(|_| unsafe { std::mem::zeroed() })
+            .collect();
+        let mut ops = Vec::with_capacity(batch.len() * 2);
+        for (i, (child, stat)) in batch.iter().zip(&mut stats).enumerate() {
+            let i = i as u64;
+            let statx = opcode::Statx::new(
+                types::Fd(libc::AT_FDCWD),
+                child.path.as_ptr(),
+                std::ptr::from_mut(stat).cast::<types::statx>(),
+            )
+            .flags(libc::AT_SYMLINK_NOFOLLOW)
+            .mask(libc::STATX_BASIC_STATS)
+            .build()
+            .user_data(i);
+            ops.push(statx);
+            if contents && child.file {
+                let open = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), child.path.as_ptr())
+                    .flags(libc::O_RDONLY | libc::O_CLOEXEC | libc::O_NOFOLLOW | libc::O_NONBLOCK)
+                    .build()
+                    .user_data(OPEN | i);
+                ops.push(open);
+            }
+        }
+        let mut fds = Vec::new();
+        let opened = run(ring, &ops, |user_data, result| {
+            if user_data & OPEN != 0 && result >= 0 {
+                fds.push(result);
+            }
+        });
+        if let Err(e) = opened {
+            close(&fds);
+            std::mem::forget(stats);
+            return Err(e);
+        }
+        if fds.is_empty() {
+            return Ok(());
+        }
+
+        let reads: Vec<_> = fds
+            .iter()
+            .zip(buffers.chunks_exact_mut(READ_AHEAD as usize))
+            .map(|(&fd, buffer)| {
+                opcode::Read::new(types::Fd(fd), buffer.as_mut_ptr(), READ_AHEAD)
+                    .build()
+                    .user_data(0)
+            })
+            .collect();
+        let read = run(ring, &reads, |_, _| {});
+        close(&fds);
+        read
+    }
+
+    /// Closes the files opened by `warm` directly rather than on the ring,
+    /// so they are released even when the ring has failed. A read still in
+    /// flight holds its own reference to the file.
+    fn close(fds: &[i32]) {
+        for &fd in fds {
+            // SAFETY: `fd` was opened by `warm` and is closed exactly once
+            unsafe { libc::close(fd) };
+        }
+    }
+
+    /// Submits `ops` and waits for all of their completions, handing each
+    /// `(user_data, result)` to `done`. `ops` must fit the ring.
+    fn run(
+        ring: &mut IoUring,
+        ops: &[io_uring::squeue::Entry],
+        mut done: impl FnMut(u64, i32),
+    ) -> io::Result<()> {
+        {
+            let mut queue = ring.submission();
+            for op in ops {
+                // SAFETY: what the operations point to (paths, statx and read
+                // buffers) is owned by `warm`, which waits here for all of
+                // them before it returns or drops anything
+                unsafe { queue.push(op) }.map_err(|_| io::Error::other("io_uring queue full"))?;
+            }
+        }
+        let mut pending = ops.len();
+        while pending > 0 {
+            match ring.submit_and_wait(pending) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            for cqe in ring.completion() {
+                done(cqe.user_data(), cqe.result());
+                pending -= 1;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! File system events for `--watch`, shared by the one-shot output and the
+//! index of `--serve` / `--mcp`.
+
+use crate::collector::Collector;
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Quiet period after the last event before re-collecting (editors save in bursts).
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Calls `changed` after each burst of file system events under --path that
+/// touch a file passing the path filters (`own` paths never count), until it
+/// returns false.
+pub fn on_changes(
+    collector: &Collector,
+    own: &[PathBuf],
+    mut changed: impl FnMut() -> Result<bool>,
+) -> Result<()> {
+    let base = &collector.options().base_path;
+    let root =
+        fs::canonicalize(base).with_context(|| format!("Failed to resolve {}", base.display()))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", base.display()))?;
+
+    log::info!("Watching {} for changes (Ctrl+C to stop)", base.display());
+
+    let relevant = |event: notify::Result<Event>| match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+            event.paths.iter().any(|path| {
+                !own.contains(path)
+                    && path
+                        .strip_prefix(&root)
+                        .is_ok_and(|rel| collector.accepts_path(&base.join(rel)))
+            })
+        }
+        Ok(_) => false,
+        Err(e) => {
+            log::warn!("Watch error: {e}");
+            false
+        }
+    };
+
+    while let Ok(event) = rx.recv() {
+        let mut relevant_change = relevant(event);
+        while let Ok(event) = rx.recv_timeout(WATCH_DEBOUNCE) {
+            relevant_change |= relevant(event);
+        }
+
+        if relevant_change && !changed()? {
+            break;
+        }
+    }
+    Ok(())
+}
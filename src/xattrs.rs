@@ -0,0 +1,90 @@
+//! `--xattrs` and the `acl` / `context` metadata fields: extended
+//! attributes of matched files.
+
+use crate::owners::{group_name, user_name};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::io;
+use std::path::Path;
+
+/// Every extended attribute of `path` (not of a symlink's target), sorted by
+/// name. Values are text when they are UTF-8 without control characters (a
+/// trailing NUL, as SELinux labels carry, is dropped) and `base64:...`
+/// otherwise, like the binary `com.apple.quarantine` plist variants.
+pub(crate) fn xattrs(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut attrs = Vec::new();
+    for name in xattr::list(path)? {
+        // Removed between listing and reading
+        let Some(value) = xattr::get(path, &name)? else {
+            continue;
+        };
+        attrs.push((name.to_string_lossy().into_owned(), render(&value)));
+    }
+    attrs.sort();
+    Ok(attrs)
+}
+
+fn render(value: &[u8]) -> String {
+    let trimmed = value.strip_suffix(b"\0").unwrap_or(value);
+    match std::str::from_utf8(trimmed) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => format!("base64:{}", STANDARD.encode(value)),
+    }
+}
+
+/// The POSIX ACL of a file in `getfacl` short form
+/// (`user::rw-,user:alice:r--,group::r--,mask::r--,other::r--`), default
+/// entries of a directory after it as `default:user::rwx,...`. `None` when
+/// the mode bits are all there is.
+pub(crate) fn acl(path: &Path) -> Option<String> {
+    let access = read_acl(path, "system.posix_acl_access", "");
+    let default = read_acl(path, "system.posix_acl_default", "default:");
+    let entries: Vec<String> = access.into_iter().chain(default).flatten().collect();
+    (!entries.is_empty()).then(|| entries.join(","))
+}
+
+/// Decodes Linux's `posix_acl_xattr` layout: a little-endian version (2),
+/// then 8-byte `(tag: u16, perm: u16, id: u32)` entries.
+fn read_acl(path: &Path, name: &str, prefix: &str) -> Option<Vec<String>> {
+    let value = xattr::get(path, name).ok()??;
+    let (version, entries) = value.split_first_chunk::<4>()?;
+    if u32::from_le_bytes(*version) != 2 {
+        return None;
+    }
+    let entries = entries
+        .chunks_exact(8)
+        .filter_map(|entry| {
+            let tag = u16::from_le_bytes([*entry.first()?, *entry.get(1)?]);
+            let perm = u16::from_le_bytes([*entry.get(2)?, *entry.get(3)?]);
+            let id = u32::from_le_bytes(entry.get(4..8)?.try_into().ok()?);
+            let qualifier = match tag {
+                0x01 => "user::".to_string(),
+                0x02 => format!("user:{}:", user_name(id)),
+                0x04 => "group::".to_string(),
+                0x08 => format!("group:{}:", group_name(id)),
+                0x10 => "mask::".to_string(),
+                0x20 => "other::".to_string(),
+                _ => return None,
+            };
+            Some(format!("{prefix}{qualifier}{}", rwx(perm)))
+        })
+        .collect();
+    Some(entries)
+}
+
+fn rwx(perm: u16) -> String {
+    [(4, 'r'), (2, 'w'), (1, 'x')]
+        .iter()
+        .map(|&(bit, c)| if perm & bit == 0 { '-' } else { c })
+        .collect()
+}
+
+/// The security label of a file: its SELinux context
+/// (`system_u:object_r:httpd_sys_content_t:s0`), or else a SMACK or
+/// AppArmor label. AppArmor confines by path, so most files have none.
+pub(crate) fn security_context(path: &Path) -> Option<String> {
+    ["security.selinux", "security.SMACK64", "security.apparmor"]
+        .iter()
+        .find_map(|name| xattr::get(path, name).ok().flatten())
+        .map(|value| render(&value))
+}